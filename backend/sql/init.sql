@@ -54,3 +54,404 @@ CREATE TABLE IF NOT EXISTS place_images (
 );
 
 CREATE INDEX IF NOT EXISTS place_images_place_idx ON place_images (place_id);
+
+-- Table: refresh_tokens
+-- Stores hashed opaque refresh tokens. Every rotation inserts a new row in the same family and
+-- marks the previous one as rotated. Presenting a rotated token revokes the whole family.
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    family_id UUID NOT NULL,                    -- Shared by every token descended from one login
+    token_hash TEXT NOT NULL UNIQUE,            -- SHA-256 of the token, never the token itself
+    replaced_by UUID,                           -- Token issued when this one was rotated
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    rotated_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_idx ON refresh_tokens (family_id);
+CREATE INDEX IF NOT EXISTS refresh_tokens_user_idx ON refresh_tokens (user_id);
+
+-- Table: revoked_access_tokens
+-- Access tokens (by their jti claim) that were explicitly logged out before expiring.
+-- Rows are only needed until the token would have expired anyway.
+CREATE TABLE IF NOT EXISTS revoked_access_tokens (
+    jti UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS revoked_access_tokens_expires_idx ON revoked_access_tokens (expires_at);
+
+-- Table: access_token_cutoffs
+-- "Log out everywhere": every access token issued to the user at or before revoked_before is rejected.
+CREATE TABLE IF NOT EXISTS access_token_cutoffs (
+    user_id UUID PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    revoked_before TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Table: oauth_login_states
+-- Pending server-driven logins started at /auth/{provider}/start. The state parameter is the key
+-- and the row is consumed by the matching callback. PKCE verifier and nonce never leave the server.
+CREATE TABLE IF NOT EXISTS oauth_login_states (
+    state TEXT PRIMARY KEY,
+    provider TEXT NOT NULL,
+    code_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS oauth_login_states_expires_idx ON oauth_login_states (expires_at);
+
+-- Table: sessions
+-- One row per login on a device. Access and refresh tokens carry the session id, so revoking a
+-- session signs that device out. Client details are informational and shown to the user.
+CREATE TABLE IF NOT EXISTS sessions (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    provider TEXT NOT NULL,                     -- Provider used to sign in (e.g. 'google-ios')
+    device_label TEXT,                          -- Optional name supplied by the client
+    user_agent TEXT,
+    ip_address TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS sessions_user_idx ON sessions (user_id);
+
+-- Refresh token families are started per session. Tokens issued before sessions existed have none.
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS session_id UUID REFERENCES sessions (id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_session_idx ON refresh_tokens (session_id);
+
+-- Table: personal_access_tokens
+-- Long-lived credentials for scripts. Only the SHA-256 hash of a token is stored and each token
+-- is limited to the scopes it was created with.
+CREATE TABLE IF NOT EXISTS personal_access_tokens (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,                     -- e.g. '{places:read,images:read}'
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ,                     -- NULL means the token does not expire
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS personal_access_tokens_user_idx ON personal_access_tokens (user_id);
+
+-- Profile fields the user edits directly. Fields listed in user_owned_fields ('name', 'avatar')
+-- are no longer overwritten with provider data on login.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS bio TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS home_city TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS avatar_file_name TEXT;  -- Uploaded avatar in the image store
+ALTER TABLE users ADD COLUMN IF NOT EXISTS user_owned_fields TEXT[] NOT NULL DEFAULT '{}';
+
+-- Structured position of a place. location stays the free-text description clients already send.
+-- Coordinates are WGS84 degrees and are either both set or both NULL.
+ALTER TABLE places ADD COLUMN IF NOT EXISTS latitude DOUBLE PRECISION CHECK (latitude BETWEEN -90 AND 90);
+ALTER TABLE places ADD COLUMN IF NOT EXISTS longitude DOUBLE PRECISION CHECK (longitude BETWEEN -180 AND 180);
+ALTER TABLE places ADD COLUMN IF NOT EXISTS address TEXT;
+ALTER TABLE places ADD COLUMN IF NOT EXISTS place_provider_id TEXT;  -- e.g. a Google Places id
+ALTER TABLE places DROP CONSTRAINT IF EXISTS places_coordinates_check;
+ALTER TABLE places ADD CONSTRAINT places_coordinates_check
+    CHECK ((latitude IS NULL) = (longitude IS NULL));
+
+-- Supports the bounding-box prefilter of nearby and map queries.
+CREATE INDEX IF NOT EXISTS places_user_coordinates_idx ON places (user_id, latitude, longitude)
+    WHERE latitude IS NOT NULL;
+
+-- Search over places. search_vector serves full-text and prefix queries, weighting names above
+-- categories, locations and notes. search_text is matched by trigram similarity to tolerate typos.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+ALTER TABLE places ADD COLUMN IF NOT EXISTS search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', name), 'A')
+    || setweight(to_tsvector('simple', category), 'B')
+    || setweight(to_tsvector('simple', location), 'C')
+    || setweight(to_tsvector('simple', coalesce(note, '')), 'D')
+) STORED;
+ALTER TABLE places ADD COLUMN IF NOT EXISTS search_text TEXT GENERATED ALWAYS AS (
+    lower(name || ' ' || category || ' ' || location || ' ' || coalesce(note, ''))
+) STORED;
+
+CREATE INDEX IF NOT EXISTS places_search_vector_idx ON places USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS places_search_text_trgm_idx ON places USING GIN (search_text gin_trgm_ops);
+
+-- Table: categories
+-- Categories a user files places under. places.category keeps a copy of the name for search and
+-- older clients and is rewritten whenever the category is renamed or merged.
+CREATE TABLE IF NOT EXISTS categories (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    color TEXT,                                 -- '#RRGGBB'
+    icon_key TEXT,                              -- Icon identifier understood by the clients
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS categories_user_name_idx ON categories (user_id, lower(name));
+
+ALTER TABLE places ADD COLUMN IF NOT EXISTS category_id UUID REFERENCES categories (id);
+
+-- Folds the free-text categories of existing places into category rows. Spellings that differ
+-- only in case share one row, named after the most common spelling.
+INSERT INTO categories (id, user_id, name)
+SELECT gen_random_uuid(), user_id, mode() WITHIN GROUP (ORDER BY btrim(category))
+FROM places
+WHERE category_id IS NULL AND btrim(category) <> ''
+GROUP BY user_id, lower(btrim(category))
+ON CONFLICT (user_id, lower(name)) DO NOTHING;
+
+INSERT INTO categories (id, user_id, name)
+SELECT DISTINCT ON (user_id) gen_random_uuid(), user_id, 'Uncategorized'
+FROM places
+WHERE category_id IS NULL AND btrim(category) = ''
+ON CONFLICT (user_id, lower(name)) DO NOTHING;
+
+UPDATE places p
+SET category_id = c.id,
+    category = c.name
+FROM categories c
+WHERE p.category_id IS NULL
+  AND c.user_id = p.user_id
+  AND lower(c.name) = lower(COALESCE(NULLIF(btrim(p.category), ''), 'Uncategorized'));
+
+ALTER TABLE places ALTER COLUMN category_id SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS places_category_idx ON places (category_id);
+
+-- Table: tags
+-- Free-form labels such as 'date night'. A user's tags are unique ignoring case and shared by
+-- all of their places.
+CREATE TABLE IF NOT EXISTS tags (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS tags_user_name_idx ON tags (user_id, lower(name));
+
+-- Table: place_tags
+CREATE TABLE IF NOT EXISTS place_tags (
+    place_id UUID NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    tag_id UUID NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    PRIMARY KEY (place_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS place_tags_tag_idx ON place_tags (tag_id);
+
+-- Table: place_visits
+-- One row per trip to a place. Spend is stored in minor units, e.g. cents, of `currency`.
+CREATE TABLE IF NOT EXISTS place_visits (
+    id UUID PRIMARY KEY,
+    place_id UUID NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    visited_on DATE NOT NULL,
+    rating SMALLINT CHECK (rating BETWEEN 1 AND 5),
+    spend_cents BIGINT CHECK (spend_cents >= 0),
+    currency TEXT,
+    companions TEXT[] NOT NULL DEFAULT '{}',
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS place_visits_place_idx ON place_visits (place_id, visited_on DESC);
+
+-- Images stay with the place when their visit is deleted.
+ALTER TABLE place_images
+    ADD COLUMN IF NOT EXISTS visit_id UUID REFERENCES place_visits (id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS place_images_visit_idx ON place_images (visit_id);
+
+-- Places are memories unless saved to the wishlist, so places saved before statuses existed and
+-- new places without a status are visited.
+ALTER TABLE places ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'visited'
+    CHECK (status IN ('want_to_go', 'visited', 'favorite', 'archived'));
+ALTER TABLE places ALTER COLUMN status SET DEFAULT 'visited';
+ALTER TABLE places ADD COLUMN IF NOT EXISTS planned_on DATE;
+ALTER TABLE places ADD COLUMN IF NOT EXISTS priority SMALLINT CHECK (priority BETWEEN 1 AND 5);
+
+CREATE INDEX IF NOT EXISTS places_user_status_idx ON places (user_id, status);
+
+-- Table: collections
+-- Curated lists of places such as 'Best ramen downtown'. A place can be in many collections.
+CREATE TABLE IF NOT EXISTS collections (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS collections_user_idx ON collections (user_id);
+
+-- Table: collection_places
+-- `position` orders the places of a collection, starting at 0 without gaps.
+CREATE TABLE IF NOT EXISTS collection_places (
+    collection_id UUID NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+    place_id UUID NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (collection_id, place_id)
+);
+
+CREATE INDEX IF NOT EXISTS collection_places_place_idx ON collection_places (place_id);
+
+-- Table: share_links
+-- Read-only links to a place or a collection for people without an account. Only the SHA-256
+-- hash of a link token is stored. A link points at exactly one place or one collection.
+CREATE TABLE IF NOT EXISTS share_links (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    place_id UUID REFERENCES places (id) ON DELETE CASCADE,
+    collection_id UUID REFERENCES collections (id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ,                     -- NULL means the link does not expire
+    revoked_at TIMESTAMPTZ,
+    CHECK ((place_id IS NULL) <> (collection_id IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS share_links_user_idx ON share_links (user_id);
+
+-- Table: collection_members
+-- Who can see or change a collection. Viewers can read it, editors can also add, remove and edit
+-- its places, and owners can also rename or delete it and manage its members and invites.
+-- `collections.user_id` is the user who created the collection, NULL once they deleted their account.
+CREATE TABLE IF NOT EXISTS collection_members (
+    collection_id UUID NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('viewer', 'editor', 'owner')),
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (collection_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS collection_members_user_idx ON collection_members (user_id);
+
+-- Collections created before members existed are owned by their creator.
+INSERT INTO collection_members (collection_id, user_id, role, joined_at)
+SELECT id, user_id, 'owner', created_at
+FROM collections
+WHERE user_id IS NOT NULL
+ON CONFLICT (collection_id, user_id) DO NOTHING;
+
+-- A collection outlives its creator when other members remain. Deleting an account hands
+-- collections the user owned alone to the member who joined first.
+ALTER TABLE collections ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE collections DROP CONSTRAINT IF EXISTS collections_user_id_fkey;
+ALTER TABLE collections ADD CONSTRAINT collections_user_id_fkey
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL;
+
+-- Table: collection_invites
+-- An invite is either addressed to an email address and used up when accepted, or carries a code
+-- that anyone can redeem until the invite expires or is revoked. Only the SHA-256 hash of a code
+-- is stored.
+CREATE TABLE IF NOT EXISTS collection_invites (
+    id UUID PRIMARY KEY,
+    collection_id UUID NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+    invited_by UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('viewer', 'editor', 'owner')),
+    email TEXT,                                 -- lower-cased
+    code_hash TEXT UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    CHECK ((email IS NULL) <> (code_hash IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS collection_invites_collection_idx ON collection_invites (collection_id);
+CREATE INDEX IF NOT EXISTS collection_invites_email_idx ON collection_invites (email);
+
+-- View: place_access
+-- Everyone who can see a place, with the role that decides whether they may change it. The owner
+-- of a place has every right on it, and members of a collection get their collection role on the
+-- places in it.
+CREATE OR REPLACE VIEW place_access AS
+SELECT id AS place_id, user_id, 'owner'::TEXT AS role
+FROM places
+UNION ALL
+SELECT cp.place_id, cm.user_id, cm.role
+FROM collection_places cp
+JOIN collection_members cm ON cm.collection_id = cp.collection_id;
+
+-- Who can find a place through the feed besides its owner. 'friends' means users who follow the
+-- owner and are followed back.
+ALTER TABLE places ADD COLUMN IF NOT EXISTS visibility TEXT NOT NULL DEFAULT 'private'
+    CHECK (visibility IN ('private', 'friends', 'public'));
+
+-- Table: follows
+CREATE TABLE IF NOT EXISTS follows (
+    follower_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    followee_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (follower_id, followee_id),
+    CHECK (follower_id <> followee_id)
+);
+
+CREATE INDEX IF NOT EXISTS follows_followee_idx ON follows (followee_id);
+
+-- Table: user_blocks
+-- Blocking ends follows in both directions and keeps the two users out of each other's feed.
+CREATE TABLE IF NOT EXISTS user_blocks (
+    blocker_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    blocked_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (blocker_id, blocked_id),
+    CHECK (blocker_id <> blocked_id)
+);
+
+CREATE INDEX IF NOT EXISTS user_blocks_blocked_idx ON user_blocks (blocked_id);
+
+-- Table: activity_events
+-- What users did with their places, for the feed of their followers. Events are written for
+-- every place and filtered by the place's current visibility when the feed is read, so making a
+-- place private hides its history as well.
+CREATE TABLE IF NOT EXISTS activity_events (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    place_id UUID NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    visit_id UUID REFERENCES place_visits (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('place_added', 'place_visited', 'place_shared')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS activity_events_user_created_idx
+    ON activity_events (user_id, created_at DESC, id DESC);
+
+-- Table: place_comments
+-- Comments of anyone who can see a place. Authors edit and delete their own, the owner of the
+-- place can delete any of them.
+CREATE TABLE IF NOT EXISTS place_comments (
+    id UUID PRIMARY KEY,
+    place_id UUID NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS place_comments_place_created_idx
+    ON place_comments (place_id, created_at, id);
+CREATE INDEX IF NOT EXISTS place_comments_user_idx ON place_comments (user_id);
+
+-- Table: place_reactions
+-- One emoji per user and place. Reacting again replaces it.
+CREATE TABLE IF NOT EXISTS place_reactions (
+    place_id UUID NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    emoji TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (place_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS place_reactions_user_idx ON place_reactions (user_id);
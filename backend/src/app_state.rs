@@ -1,18 +1,35 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use sqlx::PgPool;
+
 use crate::auth_service::AuthService;
 use crate::jwt::JwtManager;
+use crate::refresh_token::RefreshTokenManager;
 use crate::repository::auth::AuthRepository;
+use crate::repository::category::CategoryRepository;
+use crate::repository::collection::CollectionRepository;
+use crate::repository::comment::CommentRepository;
 use crate::repository::image_store::ImageStore;
 use crate::repository::place::PlaceRepository;
+use crate::repository::share::ShareRepository;
+use crate::repository::social::SocialRepository;
+use crate::repository::token::TokenRepository;
+use crate::repository::visit::VisitRepository;
 
 #[derive(Clone)]
 pub struct AppState {
     auth_providers: Arc<HashMap<String, AuthService>>,
     jwt_manager: Arc<JwtManager>,
+    refresh_token_manager: RefreshTokenManager,
     auth_repository: AuthRepository,
     place_repository: PlaceRepository,
+    category_repository: CategoryRepository,
+    collection_repository: CollectionRepository,
+    comment_repository: CommentRepository,
+    visit_repository: VisitRepository,
+    share_repository: ShareRepository,
+    social_repository: SocialRepository,
     image_store: ImageStore,
 }
 
@@ -20,15 +37,23 @@ impl AppState {
     pub fn new(
         auth_providers: HashMap<String, AuthService>,
         jwt_manager: JwtManager,
+        refresh_token_manager: RefreshTokenManager,
         auth_repository: AuthRepository,
-        place_repository: PlaceRepository,
+        pool: PgPool,
         image_store: ImageStore,
     ) -> Self {
         Self {
             auth_providers: Arc::new(auth_providers),
             jwt_manager: Arc::new(jwt_manager),
+            refresh_token_manager,
             auth_repository,
-            place_repository,
+            place_repository: PlaceRepository::new(pool.clone()),
+            category_repository: CategoryRepository::new(pool.clone()),
+            collection_repository: CollectionRepository::new(pool.clone()),
+            comment_repository: CommentRepository::new(pool.clone()),
+            visit_repository: VisitRepository::new(pool.clone()),
+            share_repository: ShareRepository::new(pool.clone()),
+            social_repository: SocialRepository::new(pool),
             image_store,
         }
     }
@@ -41,6 +66,14 @@ impl AppState {
         self.jwt_manager.as_ref().clone()
     }
 
+    pub fn refresh_token_manager(&self) -> RefreshTokenManager {
+        self.refresh_token_manager.clone()
+    }
+
+    pub fn token_repository(&self) -> TokenRepository {
+        self.refresh_token_manager.repository()
+    }
+
     pub fn auth_repository(&self) -> AuthRepository {
         self.auth_repository.clone()
     }
@@ -49,6 +82,30 @@ impl AppState {
         self.place_repository.clone()
     }
 
+    pub fn category_repository(&self) -> CategoryRepository {
+        self.category_repository.clone()
+    }
+
+    pub fn collection_repository(&self) -> CollectionRepository {
+        self.collection_repository.clone()
+    }
+
+    pub fn comment_repository(&self) -> CommentRepository {
+        self.comment_repository.clone()
+    }
+
+    pub fn visit_repository(&self) -> VisitRepository {
+        self.visit_repository.clone()
+    }
+
+    pub fn share_repository(&self) -> ShareRepository {
+        self.share_repository.clone()
+    }
+
+    pub fn social_repository(&self) -> SocialRepository {
+        self.social_repository.clone()
+    }
+
     pub fn image_store(&self) -> ImageStore {
         self.image_store.clone()
     }
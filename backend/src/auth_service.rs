@@ -1,22 +1,50 @@
+use oauth2::basic::{
+    BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+    BasicTokenType,
+};
 use oauth2::reqwest::async_http_client;
 use oauth2::{
-    basic::BasicClient, AuthUrl, AuthorizationCode, ClientId, PkceCodeVerifier, RedirectUrl,
-    TokenResponse, TokenUrl,
+    AuthUrl, AuthorizationCode, Client, ClientId, ClientSecret, CsrfToken, ExtraTokenFields,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, StandardRevocableToken,
+    StandardTokenResponse, TokenResponse, TokenUrl,
 };
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::id_token::{IdTokenError, IdTokenVerifier};
 use crate::oauth_config::OAuthProviderConfig;
+use crate::oidc_discovery::ProviderMetadata;
 use crate::repository::auth::{AuthRepository, AuthRepositoryError, IdentityProfile, UserRecord};
 
+/// Token response fields defined by OpenID Connect on top of plain OAuth2.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct OidcTokenFields {
+    id_token: Option<String>,
+}
+
+impl ExtraTokenFields for OidcTokenFields {}
+
+type OidcTokenResponse = StandardTokenResponse<OidcTokenFields, BasicTokenType>;
+
+type OidcClient = Client<
+    BasicErrorResponse,
+    OidcTokenResponse,
+    BasicTokenType,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
 #[derive(Clone)]
 pub struct AuthService {
     repository: AuthRepository,
-    client: BasicClient,
-    userinfo_url: Url,
+    client: OidcClient,
+    id_token_verifier: IdTokenVerifier,
+    userinfo_url: Option<Url>,
     http_client: reqwest::Client,
     provider_id: String,
+    scopes: Vec<Scope>,
     mock_profile: Option<MockUserProfile>,
 }
 
@@ -28,6 +56,10 @@ pub enum AuthError {
     TokenExchange(String),
     #[error("failed to fetch user info: {0}")]
     UserInfo(String),
+    #[error("id_token verification failed: {0}")]
+    IdToken(#[from] IdTokenError),
+    #[error("token response did not include an id_token")]
+    MissingIdToken,
 }
 
 #[derive(Debug, Error)]
@@ -40,6 +72,10 @@ pub enum AuthServiceBuildError {
     InvalidRedirectUrl(String),
     #[error("invalid userinfo url: {0}")]
     InvalidUserInfoUrl(String),
+    #[error("invalid JWKS url: {0}")]
+    InvalidJwksUrl(String),
+    #[error("userinfo fallback is enabled but the provider has no userinfo endpoint")]
+    MissingUserInfoEndpoint,
     #[error("failed to build HTTP client: {0}")]
     HttpClient(#[from] reqwest::Error),
 }
@@ -48,32 +84,51 @@ impl AuthService {
     pub fn new(
         repository: AuthRepository,
         config: OAuthProviderConfig,
+        metadata: ProviderMetadata,
     ) -> Result<Self, AuthServiceBuildError> {
-        let auth_url = AuthUrl::new(config.auth_url.clone())
+        let auth_url = AuthUrl::new(metadata.authorization_endpoint)
             .map_err(|err| AuthServiceBuildError::InvalidAuthUrl(err.to_string()))?;
-        let token_url = TokenUrl::new(config.token_url.clone())
+        let token_url = TokenUrl::new(metadata.token_endpoint)
             .map_err(|err| AuthServiceBuildError::InvalidTokenUrl(err.to_string()))?;
         let redirect_url = RedirectUrl::new(config.redirect_uri.clone())
             .map_err(|err| AuthServiceBuildError::InvalidRedirectUrl(err.to_string()))?;
 
-        let client = BasicClient::new(
+        let client = OidcClient::new(
             ClientId::new(config.client_id.clone()),
-            None,
+            config.client_secret.map(ClientSecret::new),
             auth_url,
             Some(token_url),
         )
         .set_redirect_uri(redirect_url);
 
-        let userinfo_url = Url::parse(&config.userinfo_url)
-            .map_err(|err| AuthServiceBuildError::InvalidUserInfoUrl(err.to_string()))?;
+        let userinfo_url = if config.userinfo_fallback {
+            let endpoint = metadata
+                .userinfo_endpoint
+                .ok_or(AuthServiceBuildError::MissingUserInfoEndpoint)?;
+            let url = Url::parse(&endpoint)
+                .map_err(|err| AuthServiceBuildError::InvalidUserInfoUrl(err.to_string()))?;
+            Some(url)
+        } else {
+            None
+        };
+        let jwks_url = Url::parse(&metadata.jwks_uri)
+            .map_err(|err| AuthServiceBuildError::InvalidJwksUrl(err.to_string()))?;
         let http_client = reqwest::Client::builder().build()?;
+        let id_token_verifier = IdTokenVerifier::new(
+            http_client.clone(),
+            jwks_url,
+            config.issuer,
+            config.client_id,
+        );
 
         Ok(Self {
             repository,
             client,
+            id_token_verifier,
             userinfo_url,
             http_client,
             provider_id: config.provider_id,
+            scopes: config.scopes.into_iter().map(Scope::new).collect(),
             mock_profile: None,
         })
     }
@@ -85,7 +140,7 @@ impl AuthService {
         mock_profile: MockUserProfile,
     ) -> Self {
         let provider_id = provider_id.into();
-        let client = BasicClient::new(
+        let client = OidcClient::new(
             ClientId::new("mock-client-id".to_string()),
             None,
             AuthUrl::new("http://localhost/mock-auth".to_string())
@@ -100,39 +155,87 @@ impl AuthService {
                 .expect("static mock redirect url is valid"),
         );
 
-        let userinfo_url = Url::parse("http://localhost/mock-userinfo")
-            .expect("static mock userinfo url is valid");
         let http_client = reqwest::Client::builder()
             .build()
             .expect("static mock http client cannot fail");
+        let id_token_verifier = IdTokenVerifier::new(
+            http_client.clone(),
+            Url::parse("http://localhost/mock-jwks").expect("static mock jwks url is valid"),
+            "http://localhost",
+            "mock-client-id",
+        );
 
         Self {
             repository,
             client,
-            userinfo_url,
+            id_token_verifier,
+            userinfo_url: None,
             http_client,
             provider_id,
+            scopes: vec![Scope::new("openid".to_string())],
             mock_profile: Some(mock_profile),
         }
     }
 
+    /// Builds the provider redirect for a server-driven login. The caller must keep the returned
+    /// state, PKCE verifier and nonce to complete the login in the callback.
+    pub fn authorization_request(&self) -> AuthorizationRequest {
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let nonce = CsrfToken::new_random().secret().to_owned();
+
+        let (url, state) = self
+            .client
+            .authorize_url(CsrfToken::new_random)
+            .add_scopes(self.scopes.iter().cloned())
+            .set_pkce_challenge(pkce_challenge)
+            .add_extra_param("nonce", nonce.clone())
+            .url();
+
+        AuthorizationRequest {
+            url,
+            state: state.secret().to_owned(),
+            code_verifier: pkce_verifier.secret().to_owned(),
+            nonce,
+        }
+    }
+
     pub async fn complete_oauth_flow(
         &self,
         code: &str,
         code_verifier: Option<&str>,
+        nonce: Option<&str>,
     ) -> Result<UserRecord, AuthError> {
+        let profile = self.fetch_identity(code, code_verifier, nonce).await?;
+
+        let user = self
+            .repository
+            .upsert_user_with_identity(IdentityProfile {
+                provider: &self.provider_id,
+                provider_user_id: &profile.sub,
+                email: profile.email.as_deref(),
+                name: profile.name.as_deref(),
+                avatar_url: profile.picture.as_deref(),
+            })
+            .await?;
+
+        Ok(user)
+    }
+
+    /// Exchanges the code and returns the verified provider identity without touching any
+    /// account, e.g. to link it to a user who is already signed in.
+    pub async fn fetch_identity(
+        &self,
+        code: &str,
+        code_verifier: Option<&str>,
+        nonce: Option<&str>,
+    ) -> Result<ProviderProfile, AuthError> {
         if let Some(profile) = &self.mock_profile {
-            return self
-                .repository
-                .upsert_user_with_identity(IdentityProfile {
-                    provider: &self.provider_id,
-                    provider_user_id: &profile.provider_user_id,
-                    email: profile.email.as_deref(),
-                    name: profile.name.as_deref(),
-                    avatar_url: profile.avatar_url.as_deref(),
-                })
-                .await
-                .map_err(AuthError::from);
+            return Ok(ProviderProfile {
+                sub: profile.provider_user_id.clone(),
+                email: profile.email.clone(),
+                name: profile.name.clone(),
+                picture: profile.avatar_url.clone(),
+            });
         }
 
         let mut request = self
@@ -148,27 +251,34 @@ impl AuthService {
             .await
             .map_err(|error| AuthError::TokenExchange(error.to_string()))?;
 
-        let access_token = token_response.access_token().secret().to_owned();
-        let profile = self.fetch_user_info(&access_token).await?;
-
-        let user = self
-            .repository
-            .upsert_user_with_identity(IdentityProfile {
-                provider: &self.provider_id,
-                provider_user_id: &profile.sub,
-                email: profile.email.as_deref(),
-                name: profile.name.as_deref(),
-                avatar_url: profile.picture.as_deref(),
-            })
-            .await?;
-
-        Ok(user)
+        match token_response.extra_fields().id_token.as_deref() {
+            Some(id_token) => {
+                let claims = self.id_token_verifier.verify(id_token, nonce).await?;
+                Ok(ProviderProfile {
+                    sub: claims.sub,
+                    email: claims.email,
+                    name: claims.name,
+                    picture: claims.picture,
+                })
+            }
+            None => {
+                let Some(userinfo_url) = &self.userinfo_url else {
+                    return Err(AuthError::MissingIdToken);
+                };
+                let access_token = token_response.access_token().secret();
+                self.fetch_user_info(userinfo_url, access_token).await
+            }
+        }
     }
 
-    async fn fetch_user_info(&self, access_token: &str) -> Result<ProviderUserInfo, AuthError> {
+    async fn fetch_user_info(
+        &self,
+        userinfo_url: &Url,
+        access_token: &str,
+    ) -> Result<ProviderProfile, AuthError> {
         let response = self
             .http_client
-            .get(self.userinfo_url.clone())
+            .get(userinfo_url.clone())
             .bearer_auth(access_token)
             .send()
             .await
@@ -178,7 +288,7 @@ impl AuthService {
                 AuthError::UserInfo(format!("userinfo endpoint returned error: {err}"))
             })?;
 
-        let profile = response.json::<ProviderUserInfo>().await.map_err(|err| {
+        let profile = response.json::<ProviderProfile>().await.map_err(|err| {
             AuthError::UserInfo(format!("failed to decode user info response: {err}"))
         })?;
 
@@ -186,12 +296,21 @@ impl AuthService {
     }
 }
 
+/// Identity asserted by the provider, from the `id_token` or the userinfo endpoint.
 #[derive(Debug, Deserialize)]
-struct ProviderUserInfo {
-    sub: String,
-    email: Option<String>,
-    name: Option<String>,
-    picture: Option<String>,
+pub struct ProviderProfile {
+    pub sub: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct AuthorizationRequest {
+    pub url: Url,
+    pub state: String,
+    pub code_verifier: String,
+    pub nonce: String,
 }
 
 #[derive(Clone)]
@@ -206,8 +325,12 @@ pub struct MockUserProfile {
 mod tests {
     use super::*;
     use crate::db::create_pool;
+    use crate::oauth_config::DEFAULT_GOOGLE_ISSUER;
     use crate::repository::auth::AuthRepository;
     use crate::sql_init::run_initialization;
+    use crate::test_utils::oidc::{
+        id_token_claims, jwks, provider_config, provider_metadata, TestSigningKey,
+    };
     use sqlx::PgPool;
     use wiremock::matchers::{header, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
@@ -227,7 +350,7 @@ mod tests {
             .expect("failed to apply initialization SQL");
 
         sqlx::query(
-            "TRUNCATE TABLE place_images, places, oauth_identities, users RESTART IDENTITY",
+            "TRUNCATE TABLE place_images, places, oauth_identities, users RESTART IDENTITY CASCADE",
         )
         .execute(&pool)
         .await
@@ -236,59 +359,81 @@ mod tests {
         pool
     }
 
-    fn build_test_config(mock_server: &MockServer) -> OAuthProviderConfig {
-        OAuthProviderConfig {
-            provider_id: "google".to_string(),
-            client_id: "client-id".to_string(),
-            auth_url: format!("{}/auth", mock_server.uri()),
-            token_url: format!("{}/token", mock_server.uri()),
-            userinfo_url: format!("{}/userinfo", mock_server.uri()),
-            redirect_uri: "https://example.com/callback".to_string(),
-        }
+    fn build_service(repository: AuthRepository, mock_server: &MockServer) -> AuthService {
+        AuthService::new(
+            repository,
+            provider_config(&mock_server.uri()),
+            provider_metadata(&mock_server.uri()),
+        )
+        .expect("service init")
     }
 
-    #[tokio::test]
-    async fn exchanges_code_and_links_user() {
-        let pool = setup_pool().await;
-        let repository = AuthRepository::new(pool.clone());
-        let mock_server = MockServer::start().await;
-        let config = build_test_config(&mock_server);
-        let service = AuthService::new(repository.clone(), config).expect("service init");
-
-        let access_token_response = serde_json::json!({
+    async fn mount_token_response(mock_server: &MockServer, id_token: Option<String>) {
+        let mut body = serde_json::json!({
             "access_token": "mock-access-token",
             "token_type": "Bearer",
-            "expires_in": 3600,
-            "refresh_token": "mock-refresh-token"
+            "expires_in": 3600
         });
+        if let Some(id_token) = id_token {
+            body["id_token"] = id_token.into();
+        }
 
         Mock::given(method("POST"))
             .and(path("/token"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(access_token_response))
-            .mount(&mock_server)
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(mock_server)
             .await;
+    }
 
-        let user_info_response = serde_json::json!({
-            "sub": "google-user-123",
-            "email": "user@example.com",
-            "name": "Test User",
-            "picture": "https://example.com/avatar.png"
-        });
-
+    async fn mount_jwks(mock_server: &MockServer, keys: &[&TestSigningKey]) {
         Mock::given(method("GET"))
-            .and(path("/userinfo"))
-            .and(header("authorization", "Bearer mock-access-token"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(user_info_response))
-            .mount(&mock_server)
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(jwks(keys)))
+            .mount(mock_server)
             .await;
+    }
+
+    async fn jwks_fetches(mock_server: &MockServer) -> usize {
+        mock_server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|request| request.url.path() == "/jwks")
+            .count()
+    }
+
+    #[tokio::test]
+    async fn exchanges_code_and_links_user() {
+        let pool = setup_pool().await;
+        let repository = AuthRepository::new(pool.clone());
+        let mock_server = MockServer::start().await;
+        let service = build_service(repository.clone(), &mock_server);
+        let key = TestSigningKey::generate("key-1");
+
+        let mut claims = id_token_claims(&mock_server.uri(), "google-user-123");
+        claims["email"] = "user@example.com".into();
+        claims["name"] = "Test User".into();
+        claims["picture"] = "https://example.com/avatar.png".into();
+        claims["nonce"] = "nonce-1".into();
+        mount_token_response(&mock_server, Some(key.sign(&claims))).await;
+        mount_jwks(&mock_server, &[&key]).await;
 
         let user = service
-            .complete_oauth_flow("test-code", Some("test-verifier"))
+            .complete_oauth_flow("test-code", Some("test-verifier"), Some("nonce-1"))
             .await
             .expect("exchange code");
 
         assert_eq!(user.email.as_deref(), Some("user@example.com"));
         assert_eq!(user.name.as_deref(), Some("Test User"));
+
+        let provider_user_id: String =
+            sqlx::query_scalar("SELECT provider_user_id FROM oauth_identities WHERE user_id = $1")
+                .bind(user.id)
+                .fetch_one(&pool)
+                .await
+                .expect("stored identity");
+        assert_eq!(provider_user_id, "google-user-123");
     }
 
     #[tokio::test]
@@ -296,75 +441,182 @@ mod tests {
         let pool = setup_pool().await;
         let repository = AuthRepository::new(pool.clone());
         let mock_server = MockServer::start().await;
-        let config = build_test_config(&mock_server);
-        let service = AuthService::new(repository.clone(), config).expect("service init");
+        let service = build_service(repository.clone(), &mock_server);
+        let key = TestSigningKey::generate("key-1");
 
-        let access_token_response = serde_json::json!({
-            "access_token": "mock-access-token",
-            "token_type": "Bearer"
-        });
+        let mut initial_claims = id_token_claims(&mock_server.uri(), "google-user-123");
+        initial_claims["email"] = "user@example.com".into();
+        initial_claims["name"] = "Test User".into();
+        initial_claims["picture"] = "https://example.com/avatar.png".into();
+        mount_token_response(&mock_server, Some(key.sign(&initial_claims))).await;
+        mount_jwks(&mock_server, &[&key]).await;
 
-        Mock::given(method("POST"))
-            .and(path("/token"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(access_token_response.clone()))
-            .mount(&mock_server)
-            .await;
+        service
+            .complete_oauth_flow("code-1", Some("verifier-1"), None)
+            .await
+            .expect("first exchange succeeds");
 
-        let initial_profile = serde_json::json!({
-            "sub": "google-user-123",
-            "email": "user@example.com",
-            "name": "Test User",
-            "picture": "https://example.com/avatar.png"
-        });
+        mock_server.reset().await;
 
-        Mock::given(method("GET"))
-            .and(path("/userinfo"))
-            .and(header("authorization", "Bearer mock-access-token"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(initial_profile))
-            .mount(&mock_server)
-            .await;
+        let mut updated_claims = id_token_claims(&mock_server.uri(), "google-user-123");
+        updated_claims["name"] = "Updated Name".into();
+        mount_token_response(&mock_server, Some(key.sign(&updated_claims))).await;
+        mount_jwks(&mock_server, &[&key]).await;
 
-        service
-            .complete_oauth_flow("code-1", Some("verifier-1"))
+        let user = service
+            .complete_oauth_flow("code-2", Some("verifier-2"), None)
             .await
-            .expect("first exchange succeeds");
+            .expect("second exchange succeeds");
+
+        assert_eq!(user.name.as_deref(), Some("Updated Name"));
+        assert_eq!(
+            user.avatar_url.as_deref(),
+            Some("https://example.com/avatar.png")
+        );
+    }
 
+    #[tokio::test]
+    async fn rejects_id_tokens_that_fail_verification() {
+        let pool = setup_pool().await;
+        let repository = AuthRepository::new(pool.clone());
+        let mock_server = MockServer::start().await;
+        let service = build_service(repository.clone(), &mock_server);
+        let key = TestSigningKey::generate("key-1");
+        let impostor = TestSigningKey::generate("key-1");
+        mount_jwks(&mock_server, &[&key]).await;
+
+        let valid = id_token_claims(&mock_server.uri(), "google-user-123");
+        let mut wrong_audience = valid.clone();
+        wrong_audience["aud"] = "someone-else".into();
+        let mut wrong_issuer = valid.clone();
+        wrong_issuer["iss"] = "https://evil.example.com".into();
+        let mut expired = valid.clone();
+        expired["exp"] = (chrono::Utc::now().timestamp() - 3600).into();
+        let mut with_nonce = valid.clone();
+        with_nonce["nonce"] = "nonce-1".into();
+
+        let cases = [
+            ("wrong audience", key.sign(&wrong_audience), None),
+            ("wrong issuer", key.sign(&wrong_issuer), None),
+            ("expired", key.sign(&expired), None),
+            ("bad signature", impostor.sign(&valid), None),
+            ("nonce mismatch", key.sign(&with_nonce), Some("nonce-2")),
+            ("missing nonce", key.sign(&valid), Some("nonce-1")),
+        ];
+
+        for (case, id_token, nonce) in cases {
+            mock_server.reset().await;
+            mount_jwks(&mock_server, &[&key]).await;
+            mount_token_response(&mock_server, Some(id_token)).await;
+
+            let result = service
+                .complete_oauth_flow("code", Some("verifier"), nonce)
+                .await;
+            assert!(
+                matches!(result, Err(AuthError::IdToken(_))),
+                "{case}: expected id_token rejection, got {:?}",
+                result.map(|user| user.id)
+            );
+        }
+
+        let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&pool)
+            .await
+            .expect("count users");
+        assert_eq!(users, 0);
+    }
+
+    #[tokio::test]
+    async fn accepts_google_issuer_variants_and_client_side_nonces() {
+        let pool = setup_pool().await;
+        let repository = AuthRepository::new(pool.clone());
+        let mock_server = MockServer::start().await;
+        let key = TestSigningKey::generate("key-1");
+
+        let mut config = provider_config(&mock_server.uri());
+        config.issuer = DEFAULT_GOOGLE_ISSUER.to_string();
+        let service = AuthService::new(repository, config, provider_metadata(&mock_server.uri()))
+            .expect("service init");
+
+        // Native SDKs add their own nonce, which the server never sees.
+        for issuer in [DEFAULT_GOOGLE_ISSUER, "accounts.google.com"] {
+            let mut claims = id_token_claims(issuer, "google-user-123");
+            claims["nonce"] = "sdk-nonce".into();
+            mock_server.reset().await;
+            mount_jwks(&mock_server, &[&key]).await;
+            mount_token_response(&mock_server, Some(key.sign(&claims))).await;
+
+            service
+                .complete_oauth_flow("code", Some("verifier"), None)
+                .await
+                .unwrap_or_else(|err| panic!("{issuer}: expected login, got {err:?}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn refetches_jwks_only_when_an_unknown_kid_appears() {
+        let pool = setup_pool().await;
+        let repository = AuthRepository::new(pool.clone());
+        let mock_server = MockServer::start().await;
+        let service = build_service(repository.clone(), &mock_server);
+        let old_key = TestSigningKey::generate("key-1");
+        let new_key = TestSigningKey::generate("key-2");
+
+        let claims = id_token_claims(&mock_server.uri(), "google-user-123");
+        mount_token_response(&mock_server, Some(old_key.sign(&claims))).await;
+        mount_jwks(&mock_server, &[&old_key]).await;
+
+        for code in ["code-1", "code-2"] {
+            service
+                .complete_oauth_flow(code, Some("verifier"), None)
+                .await
+                .expect("login with cached key");
+        }
+        assert_eq!(jwks_fetches(&mock_server).await, 1);
+
+        // The provider rotates to a key the cache has not seen yet.
         mock_server.reset().await;
+        mount_token_response(&mock_server, Some(new_key.sign(&claims))).await;
+        mount_jwks(&mock_server, &[&old_key, &new_key]).await;
 
-        let access_token_response_second = serde_json::json!({
-            "access_token": "mock-access-token",
-            "token_type": "Bearer"
-        });
+        service
+            .complete_oauth_flow("code-3", Some("verifier"), None)
+            .await
+            .expect("login with rotated key");
+        assert_eq!(jwks_fetches(&mock_server).await, 1);
+    }
 
-        let updated_profile = serde_json::json!({
-            "sub": "google-user-123",
-            "email": serde_json::Value::Null,
-            "name": "Updated Name",
-            "picture": serde_json::Value::Null
-        });
+    #[tokio::test]
+    async fn falls_back_to_userinfo_when_enabled() {
+        let pool = setup_pool().await;
+        let repository = AuthRepository::new(pool.clone());
+        let mock_server = MockServer::start().await;
+        mount_token_response(&mock_server, None).await;
 
-        Mock::given(method("POST"))
-            .and(path("/token"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(access_token_response_second))
-            .mount(&mock_server)
-            .await;
+        let strict = build_service(repository.clone(), &mock_server);
+        let result = strict.complete_oauth_flow("code", None, None).await;
+        assert!(matches!(result, Err(AuthError::MissingIdToken)));
 
         Mock::given(method("GET"))
             .and(path("/userinfo"))
             .and(header("authorization", "Bearer mock-access-token"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(updated_profile))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "sub": "google-user-123",
+                "email": "user@example.com",
+                "name": "Test User"
+            })))
             .mount(&mock_server)
             .await;
 
-        let user = service
-            .complete_oauth_flow("code-2", Some("verifier-2"))
-            .await
-            .expect("second exchange succeeds");
+        let mut config = provider_config(&mock_server.uri());
+        config.userinfo_fallback = true;
+        let fallback = AuthService::new(repository, config, provider_metadata(&mock_server.uri()))
+            .expect("service init");
 
-        assert_eq!(user.name.as_deref(), Some("Updated Name"));
-        assert_eq!(
-            user.avatar_url.as_deref(),
-            Some("https://example.com/avatar.png")
-        );
+        let user = fallback
+            .complete_oauth_flow("code", None, None)
+            .await
+            .expect("userinfo fallback");
+        assert_eq!(user.email.as_deref(), Some("user@example.com"));
     }
 }
@@ -0,0 +1,129 @@
+/// Mean Earth radius in metres, used for great-circle distances.
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Latitude/longitude box used to prefilter places with a plain b-tree index before the exact
+/// distance is computed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_latitude: f64,
+    pub max_latitude: f64,
+    /// Two inclusive ranges so a box crossing the antimeridian can be expressed. Both are the
+    /// same range when it does not.
+    pub longitude_ranges: [(f64, f64); 2],
+}
+
+impl BoundingBox {
+    /// Box between two corners. A `west` greater than `east` means the box crosses the
+    /// antimeridian.
+    pub fn from_corners(south: f64, west: f64, north: f64, east: f64) -> Self {
+        let longitude_ranges = if west <= east {
+            [(west, east); 2]
+        } else {
+            [(west, 180.0), (-180.0, east)]
+        };
+        Self {
+            min_latitude: south,
+            max_latitude: north,
+            longitude_ranges,
+        }
+    }
+
+    /// Smallest box containing every point within `radius_m` of the centre.
+    pub fn around(latitude: f64, longitude: f64, radius_m: f64) -> Self {
+        let angular_radius = radius_m / EARTH_RADIUS_M;
+        let min_latitude = latitude - angular_radius.to_degrees();
+        let max_latitude = latitude + angular_radius.to_degrees();
+        let all_longitudes = [(-180.0, 180.0); 2];
+
+        // A circle around a pole covers every longitude.
+        if min_latitude <= -90.0 || max_latitude >= 90.0 {
+            return Self {
+                min_latitude: min_latitude.max(-90.0),
+                max_latitude: max_latitude.min(90.0),
+                longitude_ranges: all_longitudes,
+            };
+        }
+
+        let ratio = angular_radius.sin() / latitude.to_radians().cos();
+        if ratio >= 1.0 {
+            return Self {
+                min_latitude,
+                max_latitude,
+                longitude_ranges: all_longitudes,
+            };
+        }
+        let delta = ratio.asin().to_degrees();
+        let (west, east) = (longitude - delta, longitude + delta);
+
+        let longitude_ranges = if west < -180.0 {
+            [(west + 360.0, 180.0), (-180.0, east)]
+        } else if east > 180.0 {
+            [(west, 180.0), (-180.0, east - 360.0)]
+        } else {
+            [(west, east); 2]
+        };
+
+        Self {
+            min_latitude,
+            max_latitude,
+            longitude_ranges,
+        }
+    }
+}
+
+/// Side length in degrees of the grid cells places are clustered into at a web-map zoom level.
+/// A 256px map tile spans `360 / 2^zoom` degrees of longitude and is split into 4x4 cells, so a
+/// cluster marker covers roughly 64px on screen.
+pub fn cluster_cell_degrees(zoom: u8) -> f64 {
+    360.0 / f64::from(1u32 << zoom.min(30)) / 4.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(bbox: &BoundingBox, latitude: f64, longitude: f64) -> bool {
+        (bbox.min_latitude..=bbox.max_latitude).contains(&latitude)
+            && bbox
+                .longitude_ranges
+                .iter()
+                .any(|(west, east)| (*west..=*east).contains(&longitude))
+    }
+
+    #[test]
+    fn box_spans_the_radius_in_every_direction() {
+        // One degree of latitude is roughly 111.2km everywhere.
+        let bbox = BoundingBox::around(52.52, 13.405, 111_195.0);
+        assert!((bbox.min_latitude - 51.52).abs() < 0.001);
+        assert!((bbox.max_latitude - 53.52).abs() < 0.001);
+        // Longitude degrees shrink with latitude, so the box is wider than it is tall.
+        let (west, east) = bbox.longitude_ranges[0];
+        assert!(east - 13.405 > 1.6 && east - 13.405 < 1.7);
+        assert!((13.405 - west - (east - 13.405)).abs() < 1e-9);
+        assert!(!contains(&bbox, 52.52, 15.2));
+    }
+
+    #[test]
+    fn box_wraps_around_the_antimeridian_and_poles() {
+        let bbox = BoundingBox::around(-16.5, 179.9, 50_000.0);
+        assert!(contains(&bbox, -16.5, -179.8));
+        assert!(contains(&bbox, -16.5, 179.7));
+        assert!(!contains(&bbox, -16.5, 0.0));
+
+        let viewport = BoundingBox::from_corners(-17.0, 178.0, -16.0, -178.0);
+        assert!(contains(&viewport, -16.5, 179.0));
+        assert!(contains(&viewport, -16.5, -179.0));
+        assert!(!contains(&viewport, -16.5, 170.0));
+
+        let polar = BoundingBox::around(89.9, 10.0, 50_000.0);
+        assert_eq!(polar.max_latitude, 90.0);
+        assert!(contains(&polar, 89.8, -170.0));
+    }
+
+    #[test]
+    fn cluster_cells_halve_with_every_zoom_level() {
+        assert_eq!(cluster_cell_degrees(0), 90.0);
+        assert_eq!(cluster_cell_degrees(1), 45.0);
+        assert_eq!(cluster_cell_degrees(10), 360.0 / 4096.0);
+    }
+}
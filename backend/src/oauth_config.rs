@@ -1,86 +1,206 @@
+use std::collections::HashSet;
 use std::env::{self, VarError};
 use thiserror::Error;
 
-const DEFAULT_GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
-const DEFAULT_GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
-const DEFAULT_GOOGLE_USERINFO_URL: &str = "https://openidconnect.googleapis.com/v1/userinfo";
+pub(crate) const DEFAULT_GOOGLE_ISSUER: &str = "https://accounts.google.com";
+const DEFAULT_SCOPES: &[&str] = &["openid", "email", "profile"];
+const PROVIDERS_KEY: &str = "OAUTH_PROVIDERS";
 
+/// Static configuration of one OpenID Connect provider. Endpoints are discovered from
+/// `<issuer>/.well-known/openid-configuration` at startup, except for the ones overridden here.
 #[derive(Clone, Debug)]
 pub struct OAuthProviderConfig {
     pub provider_id: String,
     pub client_id: String,
-    pub auth_url: String,
-    pub token_url: String,
-    pub userinfo_url: String,
+    pub client_secret: Option<String>,
+    pub scopes: Vec<String>,
+    pub issuer: String,
     pub redirect_uri: String,
+    /// Fetch the profile from the userinfo endpoint when the token response has no `id_token`.
+    pub userinfo_fallback: bool,
+    pub endpoint_overrides: EndpointOverrides,
+}
+
+/// Endpoints used instead of the discovered ones. Only set through the `GOOGLE_AUTH_URL`,
+/// `GOOGLE_TOKEN_URL` and `GOOGLE_USERINFO_URL` variables that predate discovery.
+#[derive(Clone, Debug, Default)]
+pub struct EndpointOverrides {
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: Option<String>,
+    pub userinfo_endpoint: Option<String>,
 }
 
 #[derive(Debug, Error)]
 pub enum OAuthConfigError {
     #[error("missing environment variable {0}")]
-    MissingEnv(&'static str),
+    MissingEnv(String),
     #[error("invalid unicode in environment variable {0}")]
-    InvalidUnicode(&'static str),
+    InvalidUnicode(String),
+    #[error("invalid OAuth provider id '{0}'")]
+    InvalidProviderId(String),
+    #[error("OAuth provider '{0}' is configured more than once")]
+    DuplicateProvider(String),
     #[error("no OAuth providers configured")]
     NoProviders,
 }
 
 impl OAuthProviderConfig {
     pub fn load_from_env() -> Result<Vec<Self>, OAuthConfigError> {
-        let providers = GOOGLE_ENV_VARIANTS
-            .iter()
-            .filter_map(|variant| Self::google_from_variant(variant).transpose())
-            .collect::<Result<Vec<_>, _>>()?;
+        Self::load_with(|key| env::var(key))
+    }
+
+    /// Reads every provider listed in `OAUTH_PROVIDERS` plus the legacy Google variants.
+    fn load_with(
+        lookup: impl Fn(&str) -> Result<String, VarError>,
+    ) -> Result<Vec<Self>, OAuthConfigError> {
+        let env = EnvLookup(lookup);
+        let mut providers = Vec::new();
+
+        if let Some(declared) = env.optional(PROVIDERS_KEY)? {
+            for provider_id in declared
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+            {
+                providers.push(Self::from_declaration(&env, provider_id)?);
+            }
+        }
+
+        for variant in GOOGLE_ENV_VARIANTS {
+            if let Some(provider) = Self::google_from_variant(&env, variant)? {
+                providers.push(provider);
+            }
+        }
 
         if providers.is_empty() {
             return Err(OAuthConfigError::NoProviders);
         }
 
+        let mut seen = HashSet::new();
+        for provider in &providers {
+            if !seen.insert(provider.provider_id.as_str()) {
+                return Err(OAuthConfigError::DuplicateProvider(
+                    provider.provider_id.clone(),
+                ));
+            }
+        }
+
         Ok(providers)
     }
 
-    fn google_from_variant(variant: &GoogleEnvVariant) -> Result<Option<Self>, OAuthConfigError> {
-        let client_id = match env::var(variant.client_id_key) {
-            Ok(value) => value,
-            Err(VarError::NotPresent) => return Ok(None),
-            Err(VarError::NotUnicode(_)) => {
-                return Err(OAuthConfigError::InvalidUnicode(variant.client_id_key))
-            }
-        };
+    /// Reads `OAUTH_<ID>_*` for a provider declared in `OAUTH_PROVIDERS`, where `<ID>` is the
+    /// provider id upper-cased with `-` replaced by `_`.
+    fn from_declaration(
+        env: &EnvLookup<impl Fn(&str) -> Result<String, VarError>>,
+        provider_id: &str,
+    ) -> Result<Self, OAuthConfigError> {
+        let valid_id = provider_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_id {
+            return Err(OAuthConfigError::InvalidProviderId(provider_id.to_string()));
+        }
+
+        let prefix = format!(
+            "OAUTH_{}",
+            provider_id.to_ascii_uppercase().replace('-', "_")
+        );
+        let key = |suffix: &str| format!("{prefix}_{suffix}");
+
+        Ok(Self {
+            provider_id: provider_id.to_string(),
+            client_id: env.required(&key("CLIENT_ID"))?,
+            client_secret: env.optional(&key("CLIENT_SECRET"))?,
+            scopes: parse_scopes(env.optional(&key("SCOPES"))?.as_deref()),
+            issuer: env.required(&key("ISSUER"))?,
+            redirect_uri: env.required(&key("REDIRECT_URI"))?,
+            userinfo_fallback: parse_flag(env.optional(&key("USERINFO_FALLBACK"))?.as_deref()),
+            endpoint_overrides: EndpointOverrides::default(),
+        })
+    }
 
-        let redirect_uri = read_required_env(variant.redirect_uri_key)?;
+    fn google_from_variant(
+        env: &EnvLookup<impl Fn(&str) -> Result<String, VarError>>,
+        variant: &GoogleEnvVariant,
+    ) -> Result<Option<Self>, OAuthConfigError> {
+        let Some(client_id) = env.optional(variant.client_id_key)? else {
+            return Ok(None);
+        };
 
-        let auth_url =
-            env::var("GOOGLE_AUTH_URL").unwrap_or_else(|_| DEFAULT_GOOGLE_AUTH_URL.to_string());
-        let token_url =
-            env::var("GOOGLE_TOKEN_URL").unwrap_or_else(|_| DEFAULT_GOOGLE_TOKEN_URL.to_string());
-        let userinfo_url = env::var("GOOGLE_USERINFO_URL")
-            .unwrap_or_else(|_| DEFAULT_GOOGLE_USERINFO_URL.to_string());
+        let redirect_uri = env.required(variant.redirect_uri_key)?;
+        let issuer = env
+            .optional("GOOGLE_ISSUER")?
+            .unwrap_or_else(|| DEFAULT_GOOGLE_ISSUER.to_string());
+        let userinfo_fallback = parse_flag(env.optional("GOOGLE_USERINFO_FALLBACK")?.as_deref());
+        let endpoint_overrides = EndpointOverrides {
+            authorization_endpoint: env.optional("GOOGLE_AUTH_URL")?,
+            token_endpoint: env.optional("GOOGLE_TOKEN_URL")?,
+            userinfo_endpoint: env.optional("GOOGLE_USERINFO_URL")?,
+        };
 
-        let provider_id = variant
-            .provider_name_key
-            .and_then(|key| env::var(key).ok())
-            .unwrap_or_else(|| variant.default_provider_id.to_string());
+        let provider_id = match variant.provider_name_key {
+            Some(key) => env.optional(key)?,
+            None => None,
+        }
+        .unwrap_or_else(|| variant.default_provider_id.to_string());
 
         Ok(Some(Self {
             provider_id,
             client_id,
-            auth_url,
-            token_url,
-            userinfo_url,
+            client_secret: None,
+            scopes: parse_scopes(None),
+            issuer,
             redirect_uri,
+            userinfo_fallback,
+            endpoint_overrides,
         }))
     }
 }
 
-fn read_required_env(key: &'static str) -> Result<String, OAuthConfigError> {
-    match env::var(key) {
-        Ok(value) => Ok(value),
-        Err(VarError::NotPresent) => Err(OAuthConfigError::MissingEnv(key)),
-        Err(VarError::NotUnicode(_)) => Err(OAuthConfigError::InvalidUnicode(key)),
+struct EnvLookup<F>(F);
+
+impl<F: Fn(&str) -> Result<String, VarError>> EnvLookup<F> {
+    fn optional(&self, key: &str) -> Result<Option<String>, OAuthConfigError> {
+        match (self.0)(key) {
+            Ok(value) if value.trim().is_empty() => Ok(None),
+            Ok(value) => Ok(Some(value)),
+            Err(VarError::NotPresent) => Ok(None),
+            Err(VarError::NotUnicode(_)) => Err(OAuthConfigError::InvalidUnicode(key.to_string())),
+        }
+    }
+
+    fn required(&self, key: &str) -> Result<String, OAuthConfigError> {
+        self.optional(key)?
+            .ok_or_else(|| OAuthConfigError::MissingEnv(key.to_string()))
+    }
+}
+
+/// Accepts space or comma separated scopes. `openid` is always requested because login relies on
+/// the `id_token`.
+fn parse_scopes(value: Option<&str>) -> Vec<String> {
+    let mut scopes: Vec<String> = match value {
+        Some(value) => value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_string)
+            .collect(),
+        None => DEFAULT_SCOPES
+            .iter()
+            .map(|scope| scope.to_string())
+            .collect(),
+    };
+    if !scopes.iter().any(|scope| scope == "openid") {
+        scopes.insert(0, "openid".to_string());
     }
+    scopes
 }
 
+fn parse_flag(value: Option<&str>) -> bool {
+    value.is_some_and(|value| value.eq_ignore_ascii_case("true") || value == "1")
+}
+
+/// Env layout used before providers could be declared in `OAUTH_PROVIDERS`. Still read so
+/// existing deployments keep working.
 struct GoogleEnvVariant {
     client_id_key: &'static str,
     redirect_uri_key: &'static str,
@@ -118,3 +238,112 @@ const GOOGLE_ENV_VARIANTS: &[GoogleEnvVariant] = &[
         "google-android",
     ),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Vec<OAuthProviderConfig>, OAuthConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        OAuthProviderConfig::load_with(|key| vars.get(key).cloned().ok_or(VarError::NotPresent))
+    }
+
+    #[test]
+    fn loads_declared_providers() {
+        let providers = load(&[
+            ("OAUTH_PROVIDERS", "keycloak, microsoft-work"),
+            ("OAUTH_KEYCLOAK_CLIENT_ID", "local-guide"),
+            ("OAUTH_KEYCLOAK_CLIENT_SECRET", "s3cret"),
+            ("OAUTH_KEYCLOAK_SCOPES", "email profile groups"),
+            (
+                "OAUTH_KEYCLOAK_ISSUER",
+                "https://sso.example.com/realms/staff",
+            ),
+            ("OAUTH_KEYCLOAK_REDIRECT_URI", "localguide:/oauthredirect"),
+            ("OAUTH_MICROSOFT_WORK_CLIENT_ID", "ms-client"),
+            (
+                "OAUTH_MICROSOFT_WORK_ISSUER",
+                "https://login.microsoftonline.com/tenant/v2.0",
+            ),
+            ("OAUTH_MICROSOFT_WORK_REDIRECT_URI", "localguide:/ms"),
+            ("OAUTH_MICROSOFT_WORK_USERINFO_FALLBACK", "true"),
+        ])
+        .expect("valid config");
+
+        assert_eq!(providers.len(), 2);
+        let keycloak = &providers[0];
+        assert_eq!(keycloak.provider_id, "keycloak");
+        assert_eq!(keycloak.client_secret.as_deref(), Some("s3cret"));
+        assert_eq!(keycloak.scopes, ["openid", "email", "profile", "groups"]);
+        assert!(!keycloak.userinfo_fallback);
+
+        let microsoft = &providers[1];
+        assert_eq!(microsoft.provider_id, "microsoft-work");
+        assert_eq!(microsoft.client_secret, None);
+        assert_eq!(microsoft.scopes, ["openid", "email", "profile"]);
+        assert!(microsoft.userinfo_fallback);
+    }
+
+    #[test]
+    fn keeps_reading_legacy_google_variables() {
+        let providers = load(&[
+            ("GOOGLE_IOS_CLIENT_ID", "ios-client"),
+            ("GOOGLE_IOS_REDIRECT_URI", "localguide:/ios"),
+        ])
+        .expect("valid config");
+
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0].provider_id, "google-ios");
+        assert_eq!(providers[0].issuer, DEFAULT_GOOGLE_ISSUER);
+        assert!(providers[0].endpoint_overrides.token_endpoint.is_none());
+
+        let providers = load(&[
+            ("GOOGLE_ANDROID_CLIENT_ID", "android-client"),
+            ("GOOGLE_ANDROID_REDIRECT_URI", "localguide:/android"),
+            ("GOOGLE_TOKEN_URL", "http://localhost:9000/token"),
+            ("GOOGLE_USERINFO_URL", "http://localhost:9000/userinfo"),
+        ])
+        .expect("valid config");
+        let overrides = &providers[0].endpoint_overrides;
+        assert_eq!(overrides.authorization_endpoint, None);
+        assert_eq!(
+            overrides.token_endpoint.as_deref(),
+            Some("http://localhost:9000/token")
+        );
+        assert_eq!(
+            overrides.userinfo_endpoint.as_deref(),
+            Some("http://localhost:9000/userinfo")
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_or_duplicate_providers() {
+        let missing = load(&[
+            ("OAUTH_PROVIDERS", "keycloak"),
+            ("OAUTH_KEYCLOAK_CLIENT_ID", "local-guide"),
+            ("OAUTH_KEYCLOAK_REDIRECT_URI", "localguide:/oauthredirect"),
+        ]);
+        assert!(
+            matches!(missing, Err(OAuthConfigError::MissingEnv(key)) if key == "OAUTH_KEYCLOAK_ISSUER")
+        );
+
+        let duplicate = load(&[
+            ("OAUTH_PROVIDERS", "google-ios"),
+            ("OAUTH_GOOGLE_IOS_CLIENT_ID", "ios-client"),
+            ("OAUTH_GOOGLE_IOS_ISSUER", DEFAULT_GOOGLE_ISSUER),
+            ("OAUTH_GOOGLE_IOS_REDIRECT_URI", "localguide:/ios"),
+            ("GOOGLE_IOS_CLIENT_ID", "ios-client"),
+            ("GOOGLE_IOS_REDIRECT_URI", "localguide:/ios"),
+        ]);
+        assert!(matches!(
+            duplicate,
+            Err(OAuthConfigError::DuplicateProvider(id)) if id == "google-ios"
+        ));
+
+        assert!(matches!(load(&[]), Err(OAuthConfigError::NoProviders)));
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::oauth_config::EndpointOverrides;
+
+const DISCOVERY_PATH: &str = ".well-known/openid-configuration";
+/// How long startup waits for a provider's discovery document before giving up on it.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The subset of an OpenID provider's discovery document the backend relies on.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: String,
+}
+
+#[derive(Debug, Error)]
+pub enum DiscoveryError {
+    #[error("failed to fetch discovery document for {issuer}: {source}")]
+    Request {
+        issuer: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("discovery document for {expected} reports issuer {actual}")]
+    IssuerMismatch { expected: String, actual: String },
+}
+
+impl ProviderMetadata {
+    pub fn http_client() -> reqwest::Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .timeout(DISCOVERY_TIMEOUT)
+            .build()
+    }
+
+    pub async fn discover(
+        http_client: &reqwest::Client,
+        issuer: &str,
+    ) -> Result<Self, DiscoveryError> {
+        let url = format!("{}/{DISCOVERY_PATH}", issuer.trim_end_matches('/'));
+        let request_error = |source| DiscoveryError::Request {
+            issuer: issuer.to_string(),
+            source,
+        };
+
+        let metadata = http_client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(request_error)?
+            .json::<Self>()
+            .await
+            .map_err(request_error)?;
+
+        // The spec requires an exact match, otherwise id_tokens from this provider would never
+        // pass the issuer check.
+        if metadata.issuer != issuer {
+            return Err(DiscoveryError::IssuerMismatch {
+                expected: issuer.to_string(),
+                actual: metadata.issuer,
+            });
+        }
+
+        Ok(metadata)
+    }
+
+    pub fn with_overrides(mut self, overrides: &EndpointOverrides) -> Self {
+        if let Some(endpoint) = &overrides.authorization_endpoint {
+            self.authorization_endpoint = endpoint.clone();
+        }
+        if let Some(endpoint) = &overrides.token_endpoint {
+            self.token_endpoint = endpoint.clone();
+        }
+        if let Some(endpoint) = &overrides.userinfo_endpoint {
+            self.userinfo_endpoint = Some(endpoint.clone());
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mount_document(mock_server: &MockServer, issuer: &str) {
+        Mock::given(method("GET"))
+            .and(path("/realms/staff/.well-known/openid-configuration"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{issuer}/protocol/openid-connect/auth"),
+                "token_endpoint": format!("{issuer}/protocol/openid-connect/token"),
+                "jwks_uri": format!("{issuer}/protocol/openid-connect/certs"),
+                "response_types_supported": ["code"]
+            })))
+            .mount(mock_server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn reads_endpoints_from_discovery_document() {
+        let mock_server = MockServer::start().await;
+        let issuer = format!("{}/realms/staff", mock_server.uri());
+        mount_document(&mock_server, &issuer).await;
+
+        let metadata = ProviderMetadata::discover(&reqwest::Client::new(), &issuer)
+            .await
+            .expect("discover provider");
+
+        assert_eq!(
+            metadata.token_endpoint,
+            format!("{issuer}/protocol/openid-connect/token")
+        );
+        assert_eq!(
+            metadata.jwks_uri,
+            format!("{issuer}/protocol/openid-connect/certs")
+        );
+        assert_eq!(metadata.userinfo_endpoint, None);
+
+        let metadata = metadata.with_overrides(&EndpointOverrides {
+            token_endpoint: Some("http://localhost:9000/token".to_string()),
+            ..EndpointOverrides::default()
+        });
+        assert_eq!(metadata.token_endpoint, "http://localhost:9000/token");
+        assert_eq!(
+            metadata.authorization_endpoint,
+            format!("{issuer}/protocol/openid-connect/auth")
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_document_for_a_different_issuer() {
+        let mock_server = MockServer::start().await;
+        let issuer = format!("{}/realms/staff", mock_server.uri());
+        mount_document(&mock_server, "https://elsewhere.example.com").await;
+
+        let result = ProviderMetadata::discover(&reqwest::Client::new(), &issuer).await;
+        assert!(matches!(result, Err(DiscoveryError::IssuerMismatch { .. })));
+
+        let missing = ProviderMetadata::discover(
+            &reqwest::Client::new(),
+            &format!("{}/realms/unknown", mock_server.uri()),
+        )
+        .await;
+        assert!(matches!(missing, Err(DiscoveryError::Request { .. })));
+    }
+}
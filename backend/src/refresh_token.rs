@@ -0,0 +1,123 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::repository::token::{
+    NewRefreshToken, RefreshTokenRotation, TokenRepository, TokenRepositoryError,
+};
+
+const REFRESH_TOKEN_BYTES: usize = 32;
+
+/// Issues and rotates opaque refresh tokens. Only the SHA-256 hash of a token is persisted.
+#[derive(Clone)]
+pub struct RefreshTokenManager {
+    repository: TokenRepository,
+    expiration: Duration,
+}
+
+#[derive(Debug)]
+pub struct RotatedRefreshToken {
+    pub user_id: Uuid,
+    pub session_id: Option<Uuid>,
+    pub token: String,
+}
+
+#[derive(Debug, Error)]
+pub enum RefreshTokenError {
+    #[error("storage failure: {0}")]
+    Storage(#[from] TokenRepositoryError),
+    #[error("refresh token is unknown or revoked")]
+    Invalid,
+    #[error("refresh token has expired")]
+    Expired,
+    #[error("refresh token was already used; token family {family_id} revoked")]
+    Reused { family_id: Uuid },
+}
+
+impl RefreshTokenManager {
+    pub fn new(repository: TokenRepository, expiration_seconds: u64) -> Self {
+        Self {
+            repository,
+            expiration: Duration::seconds(expiration_seconds as i64),
+        }
+    }
+
+    /// Starts a new token family for a fresh login session.
+    pub async fn issue(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<String, RefreshTokenError> {
+        let token = generate_token();
+        let token_hash = hash_token(&token);
+
+        self.repository
+            .insert_refresh_token(NewRefreshToken {
+                id: Uuid::new_v4(),
+                user_id,
+                family_id: Uuid::new_v4(),
+                session_id: Some(session_id),
+                token_hash: &token_hash,
+                expires_at: Utc::now() + self.expiration,
+            })
+            .await?;
+
+        Ok(token)
+    }
+
+    /// Exchanges a refresh token for a new one in the same family.
+    pub async fn rotate(&self, token: &str) -> Result<RotatedRefreshToken, RefreshTokenError> {
+        let replacement = generate_token();
+
+        let rotation = self
+            .repository
+            .rotate_refresh_token(
+                &hash_token(token),
+                &hash_token(&replacement),
+                Utc::now() + self.expiration,
+            )
+            .await?;
+
+        match rotation {
+            RefreshTokenRotation::Rotated(record) => Ok(RotatedRefreshToken {
+                user_id: record.user_id,
+                session_id: record.session_id,
+                token: replacement,
+            }),
+            RefreshTokenRotation::Reused { user_id, family_id } => {
+                warn!(%user_id, %family_id, "refresh token reuse detected; revoked token family");
+                Err(RefreshTokenError::Reused { family_id })
+            }
+            RefreshTokenRotation::Expired => Err(RefreshTokenError::Expired),
+            RefreshTokenRotation::Revoked | RefreshTokenRotation::NotFound => {
+                Err(RefreshTokenError::Invalid)
+            }
+        }
+    }
+
+    /// Revokes the family the given token belongs to, e.g. when a device logs out.
+    pub async fn revoke(&self, user_id: Uuid, token: &str) -> Result<(), RefreshTokenError> {
+        self.repository
+            .revoke_refresh_token_family(user_id, &hash_token(token))
+            .await?;
+        Ok(())
+    }
+
+    pub fn repository(&self) -> TokenRepository {
+        self.repository.clone()
+    }
+}
+
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; REFRESH_TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
@@ -1,7 +1,10 @@
+use chrono::{DateTime, Utc};
 use sqlx::{Error as SqlxError, FromRow, PgPool, Postgres, Transaction};
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::repository::collection::{leave_all_collections, remove_places_from_collections};
+
 #[derive(Debug, Error)]
 pub enum AuthRepositoryError {
     #[error("database error: {0}")]
@@ -21,6 +24,44 @@ pub struct UserRecord {
     pub email: Option<String>,
     pub name: Option<String>,
     pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub home_city: Option<String>,
+    /// Profile fields the user set themselves, see `ProfileField`.
+    pub user_owned_fields: Vec<String>,
+}
+
+/// Provider-supplied profile fields a user can take ownership of. Owned fields are left alone
+/// when the user signs in again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileField {
+    Name,
+    Avatar,
+}
+
+impl ProfileField {
+    pub const ALL: [ProfileField; 2] = [ProfileField::Name, ProfileField::Avatar];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProfileField::Name => "name",
+            ProfileField::Avatar => "avatar",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.as_str() == value)
+    }
+}
+
+/// Changes made through `PATCH /usr`. `None` keeps the current value and an empty string clears
+/// it.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileUpdate<'a> {
+    pub name: Option<&'a str>,
+    pub bio: Option<&'a str>,
+    pub home_city: Option<&'a str>,
+    /// Replaces the set of user-owned fields. When `None`, setting `name` marks it as owned.
+    pub user_owned_fields: Option<&'a [String]>,
 }
 
 #[derive(Debug, Clone)]
@@ -32,17 +73,115 @@ pub struct IdentityProfile<'a> {
     pub avatar_url: Option<&'a str>,
 }
 
+#[derive(Debug, Clone, FromRow)]
+pub struct IdentityRecord {
+    pub id: Uuid,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub linked_at: DateTime<Utc>,
+}
+
+/// Outcome of linking a provider identity to a signed-in user.
+#[derive(Debug)]
+pub enum IdentityLink {
+    Linked(IdentityRecord),
+    /// The identity was already linked to this user.
+    AlreadyLinked(IdentityRecord),
+    /// The identity signs in to a different account and is left untouched.
+    OwnedByAnotherUser,
+}
+
+#[derive(Debug)]
+pub enum IdentityUnlink {
+    Unlinked,
+    NotFound,
+    /// Removing the identity would leave the user with no way to sign in.
+    LastIdentity,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewLoginState<'a> {
+    pub state: &'a str,
+    pub provider: &'a str,
+    pub code_verifier: &'a str,
+    pub nonce: &'a str,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct LoginStateRecord {
+    pub code_verifier: String,
+    pub nonce: String,
+}
+
 impl AuthRepository {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
 
+    pub async fn insert_login_state(&self, payload: NewLoginState<'_>) -> RepoResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // Abandoned logins are never consumed, so clear them out as new ones start.
+        sqlx::query("DELETE FROM oauth_login_states WHERE expires_at < NOW()")
+            .execute(tx.as_mut())
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO oauth_login_states (state, provider, code_verifier, nonce, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(payload.state)
+        .bind(payload.provider)
+        .bind(payload.code_verifier)
+        .bind(payload.nonce)
+        .bind(payload.expires_at)
+        .execute(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Consumes the pending login for `state` at `provider`. Each state can be used once, and
+    /// expired states are treated as unknown. A state sent to another provider is left in place.
+    pub async fn take_login_state(
+        &self,
+        state: &str,
+        provider: &str,
+    ) -> RepoResult<Option<LoginStateRecord>> {
+        let record = sqlx::query_as::<_, LoginStateRecord>(
+            r#"
+            DELETE FROM oauth_login_states
+            WHERE state = $1 AND provider = $2 AND expires_at > NOW()
+            RETURNING code_verifier, nonce
+            "#,
+        )
+        .bind(state)
+        .bind(provider)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
     pub async fn upsert_user_with_identity(
         &self,
         payload: IdentityProfile<'_>,
     ) -> RepoResult<UserRecord> {
         let mut tx = self.pool.begin().await?;
 
+        // Serialize first logins of the same identity so a concurrent caller
+        // waits here and then takes the existing-identity branch instead of
+        // inserting a second user row.
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1 || ':' || $2, 0))")
+            .bind(payload.provider)
+            .bind(payload.provider_user_id)
+            .execute(tx.as_mut())
+            .await?;
+
         if let Some(existing) = self
             .find_user_by_identity_tx(&mut tx, payload.provider, payload.provider_user_id)
             .await?
@@ -87,7 +226,7 @@ impl AuthRepository {
                 INSERT INTO oauth_identities (id, provider, provider_user_id, user_id)
                 SELECT gen_random_uuid(), $1, $2, user_id FROM resolved_user
                 ON CONFLICT (provider, provider_user_id)
-                DO UPDATE SET user_id = EXCLUDED.user_id
+                DO UPDATE SET user_id = oauth_identities.user_id
                 RETURNING user_id
             )
             SELECT user_id FROM identity_upsert
@@ -131,11 +270,17 @@ impl AuthRepository {
             r#"
             UPDATE users
             SET email = COALESCE($2, email),
-                name = COALESCE($3, name),
-                avatar_url = COALESCE($4, avatar_url),
+                name = CASE
+                    WHEN 'name' = ANY(user_owned_fields) THEN name
+                    ELSE COALESCE($3, name)
+                END,
+                avatar_url = CASE
+                    WHEN 'avatar' = ANY(user_owned_fields) THEN avatar_url
+                    ELSE COALESCE($4, avatar_url)
+                END,
                 updated_at = NOW()
             WHERE id = $1
-            RETURNING id, email, name, avatar_url
+            RETURNING id, email, name, avatar_url, bio, home_city, user_owned_fields
             "#,
         )
         .bind(user_id)
@@ -156,7 +301,7 @@ impl AuthRepository {
     ) -> RepoResult<Option<UserRecord>> {
         let record = sqlx::query_as::<_, UserRecord>(
             r#"
-            SELECT u.id, u.email, u.name, u.avatar_url
+            SELECT u.id, u.email, u.name, u.avatar_url, u.bio, u.home_city, u.user_owned_fields
             FROM oauth_identities oi
             JOIN users u ON u.id = oi.user_id
             WHERE oi.provider = $1 AND oi.provider_user_id = $2
@@ -173,7 +318,7 @@ impl AuthRepository {
     pub async fn find_user_by_id(&self, user_id: Uuid) -> RepoResult<Option<UserRecord>> {
         let record = sqlx::query_as::<_, UserRecord>(
             r#"
-            SELECT id, email, name, avatar_url
+            SELECT id, email, name, avatar_url, bio, home_city, user_owned_fields
             FROM users
             WHERE id = $1
             "#,
@@ -185,6 +330,208 @@ impl AuthRepository {
         Ok(record)
     }
 
+    /// Applies a profile edit. Returns `None` when the user does not exist.
+    pub async fn update_profile(
+        &self,
+        user_id: Uuid,
+        update: ProfileUpdate<'_>,
+    ) -> RepoResult<Option<UserRecord>> {
+        let record = sqlx::query_as::<_, UserRecord>(
+            r#"
+            UPDATE users
+            SET name = NULLIF(COALESCE($2, name), ''),
+                bio = NULLIF(COALESCE($3, bio), ''),
+                home_city = NULLIF(COALESCE($4, home_city), ''),
+                user_owned_fields = COALESCE(
+                    $5,
+                    CASE
+                        WHEN $2 IS NOT NULL AND NOT ('name' = ANY(user_owned_fields))
+                            THEN array_append(user_owned_fields, 'name')
+                        ELSE user_owned_fields
+                    END
+                ),
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, email, name, avatar_url, bio, home_city, user_owned_fields
+            "#,
+        )
+        .bind(user_id)
+        .bind(update.name)
+        .bind(update.bio)
+        .bind(update.home_city)
+        .bind(update.user_owned_fields)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Points the user's avatar at an uploaded file and marks the avatar as user-owned. Returns
+    /// the updated user and the file of the avatar it replaced, or `None` when the user does not
+    /// exist.
+    pub async fn set_uploaded_avatar(
+        &self,
+        user_id: Uuid,
+        avatar_url: &str,
+        file_name: &str,
+    ) -> RepoResult<Option<(UserRecord, Option<String>)>> {
+        let mut tx = self.pool.begin().await?;
+
+        let previous = sqlx::query_scalar::<_, Option<String>>(
+            r#"
+            SELECT avatar_file_name
+            FROM users
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(tx.as_mut())
+        .await?;
+
+        let Some(previous_file_name) = previous else {
+            tx.commit().await?;
+            return Ok(None);
+        };
+
+        let user = sqlx::query_as::<_, UserRecord>(
+            r#"
+            UPDATE users
+            SET avatar_url = $2,
+                avatar_file_name = $3,
+                user_owned_fields = CASE
+                    WHEN 'avatar' = ANY(user_owned_fields) THEN user_owned_fields
+                    ELSE array_append(user_owned_fields, 'avatar')
+                END,
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, email, name, avatar_url, bio, home_city, user_owned_fields
+            "#,
+        )
+        .bind(user_id)
+        .bind(avatar_url)
+        .bind(file_name)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some((user, previous_file_name)))
+    }
+
+    pub async fn find_avatar_file_name(&self, user_id: Uuid) -> RepoResult<Option<String>> {
+        let file_name = sqlx::query_scalar::<_, Option<String>>(
+            r#"
+            SELECT avatar_file_name
+            FROM users
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(file_name.flatten())
+    }
+
+    pub async fn list_identities(&self, user_id: Uuid) -> RepoResult<Vec<IdentityRecord>> {
+        let records = sqlx::query_as::<_, IdentityRecord>(
+            r#"
+            SELECT id, provider, provider_user_id, linked_at
+            FROM oauth_identities
+            WHERE user_id = $1
+            ORDER BY linked_at, id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    pub async fn link_identity(
+        &self,
+        user_id: Uuid,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> RepoResult<IdentityLink> {
+        let inserted = sqlx::query_as::<_, IdentityRecord>(
+            r#"
+            INSERT INTO oauth_identities (id, provider, provider_user_id, user_id)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (provider, provider_user_id) DO NOTHING
+            RETURNING id, provider, provider_user_id, linked_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(provider)
+        .bind(provider_user_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(record) = inserted {
+            return Ok(IdentityLink::Linked(record));
+        }
+
+        let existing = sqlx::query_as::<_, IdentityRecord>(
+            r#"
+            SELECT id, provider, provider_user_id, linked_at
+            FROM oauth_identities
+            WHERE provider = $1 AND provider_user_id = $2 AND user_id = $3
+            "#,
+        )
+        .bind(provider)
+        .bind(provider_user_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match existing {
+            Some(record) => IdentityLink::AlreadyLinked(record),
+            None => IdentityLink::OwnedByAnotherUser,
+        })
+    }
+
+    pub async fn unlink_identity(
+        &self,
+        user_id: Uuid,
+        identity_id: Uuid,
+    ) -> RepoResult<IdentityUnlink> {
+        let mut tx = self.pool.begin().await?;
+
+        // Lock all of the user's identities so two concurrent unlinks cannot both see a spare one.
+        let identity_ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT id
+            FROM oauth_identities
+            WHERE user_id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        if !identity_ids.contains(&identity_id) {
+            tx.commit().await?;
+            return Ok(IdentityUnlink::NotFound);
+        }
+        if identity_ids.len() == 1 {
+            tx.commit().await?;
+            return Ok(IdentityUnlink::LastIdentity);
+        }
+
+        sqlx::query("DELETE FROM oauth_identities WHERE id = $1")
+            .bind(identity_id)
+            .execute(tx.as_mut())
+            .await?;
+
+        tx.commit().await?;
+        Ok(IdentityUnlink::Unlinked)
+    }
+
     pub async fn delete_user_with_places(&self, user_id: Uuid) -> RepoResult<Option<Vec<Uuid>>> {
         let mut tx = self.pool.begin().await?;
 
@@ -206,17 +553,31 @@ impl AuthRepository {
             return Ok(None);
         };
 
-        // Delete places first to return their IDs for filesystem cleanup.
+        leave_all_collections(tx.as_mut(), user_id).await?;
+
+        // Delete places first to return their IDs for filesystem cleanup. They may sit in
+        // collections shared with other users, whose order must not keep gaps.
         let place_ids = sqlx::query_scalar::<_, Uuid>(
             r#"
-            DELETE FROM places
+            SELECT id
+            FROM places
             WHERE user_id = $1
-            RETURNING id
             "#,
         )
         .bind(user_id)
         .fetch_all(tx.as_mut())
         .await?;
+        remove_places_from_collections(tx.as_mut(), &place_ids).await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM places
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(tx.as_mut())
+        .await?;
 
         sqlx::query(
             r#"
@@ -254,7 +615,7 @@ mod tests {
         run_initialization(&pool).await.expect("apply schema");
 
         sqlx::query(
-            "TRUNCATE TABLE place_images, places, oauth_identities, users RESTART IDENTITY",
+            "TRUNCATE TABLE place_images, places, oauth_identities, users RESTART IDENTITY CASCADE",
         )
         .execute(&pool)
         .await
@@ -0,0 +1,342 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Error as SqlxError, FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum CategoryRepositoryError {
+    #[error("database error: {0}")]
+    Database(#[from] SqlxError),
+}
+
+type RepoResult<T> = Result<T, CategoryRepositoryError>;
+
+#[derive(Clone)]
+pub struct CategoryRepository {
+    pool: PgPool,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct CategoryRecord {
+    pub id: Uuid,
+    pub name: String,
+    pub color: Option<String>,
+    pub icon_key: Option<String>,
+    pub sort_order: i32,
+    /// Number of places filed under the category.
+    pub place_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewCategory<'a> {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: &'a str,
+    pub color: Option<&'a str>,
+    pub icon_key: Option<&'a str>,
+    /// Appended after the user's other categories when `None`.
+    pub sort_order: Option<i32>,
+}
+
+/// Changes to a category. `None` keeps the current value and an empty `color` or `icon_key`
+/// clears it.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateCategory {
+    pub name: Option<String>,
+    pub color: Option<String>,
+    pub icon_key: Option<String>,
+    pub sort_order: Option<i32>,
+}
+
+#[derive(Debug)]
+pub enum CategoryWrite {
+    Written(CategoryRecord),
+    NotFound,
+    /// Another category of the user already has this name, ignoring case.
+    NameTaken,
+}
+
+#[derive(Debug)]
+pub enum CategoryDelete {
+    Deleted,
+    NotFound,
+    /// Places still use the category. They have to be moved, e.g. by merging, first.
+    InUse,
+}
+
+impl CategoryRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_for_user(&self, user_id: Uuid) -> RepoResult<Vec<CategoryRecord>> {
+        let records = sqlx::query_as::<_, CategoryRecord>(
+            r#"
+            SELECT c.id, c.name, c.color, c.icon_key, c.sort_order,
+                   (SELECT count(*) FROM places p WHERE p.category_id = c.id) AS place_count,
+                   c.created_at, c.updated_at
+            FROM categories c
+            WHERE c.user_id = $1
+            ORDER BY c.sort_order, lower(c.name), c.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    pub async fn find_for_user(
+        &self,
+        user_id: Uuid,
+        category_id: Uuid,
+    ) -> RepoResult<Option<CategoryRecord>> {
+        let record = sqlx::query_as::<_, CategoryRecord>(
+            r#"
+            SELECT c.id, c.name, c.color, c.icon_key, c.sort_order,
+                   (SELECT count(*) FROM places p WHERE p.category_id = c.id) AS place_count,
+                   c.created_at, c.updated_at
+            FROM categories c
+            WHERE c.id = $1 AND c.user_id = $2
+            "#,
+        )
+        .bind(category_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn create(&self, payload: NewCategory<'_>) -> RepoResult<CategoryWrite> {
+        let record = sqlx::query_as::<_, CategoryRecord>(
+            r#"
+            INSERT INTO categories (id, user_id, name, color, icon_key, sort_order)
+            VALUES (
+                $1, $2, $3, $4, $5,
+                COALESCE($6, (SELECT max(sort_order) + 1 FROM categories WHERE user_id = $2), 0)
+            )
+            ON CONFLICT (user_id, lower(name)) DO NOTHING
+            RETURNING id, name, color, icon_key, sort_order, 0::BIGINT AS place_count,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(payload.id)
+        .bind(payload.user_id)
+        .bind(payload.name)
+        .bind(payload.color)
+        .bind(payload.icon_key)
+        .bind(payload.sort_order)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match record {
+            Some(record) => CategoryWrite::Written(record),
+            None => CategoryWrite::NameTaken,
+        })
+    }
+
+    /// Returns the user's category named `name`, ignoring case, and creates it when missing.
+    /// This is how places created with a plain category string are filed.
+    pub async fn find_or_create_by_name(
+        &self,
+        user_id: Uuid,
+        name: &str,
+    ) -> RepoResult<CategoryRecord> {
+        // The no-op update makes RETURNING yield the existing row on conflict.
+        let record = sqlx::query_as::<_, CategoryRecord>(
+            r#"
+            INSERT INTO categories (id, user_id, name, sort_order)
+            VALUES (
+                $1, $2, $3,
+                COALESCE((SELECT max(sort_order) + 1 FROM categories WHERE user_id = $2), 0)
+            )
+            ON CONFLICT (user_id, lower(name)) DO UPDATE SET name = categories.name
+            RETURNING id, name, color, icon_key, sort_order,
+                      (SELECT count(*) FROM places p WHERE p.category_id = categories.id)
+                          AS place_count,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Applies `update` and copies a new name onto the category's places.
+    pub async fn update(
+        &self,
+        user_id: Uuid,
+        category_id: Uuid,
+        update: UpdateCategory,
+    ) -> RepoResult<CategoryWrite> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated = sqlx::query_as::<_, CategoryRecord>(
+            r#"
+            UPDATE categories
+            SET name = COALESCE($3, name),
+                color = NULLIF(COALESCE($4, color), ''),
+                icon_key = NULLIF(COALESCE($5, icon_key), ''),
+                sort_order = COALESCE($6, sort_order),
+                updated_at = NOW()
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, name, color, icon_key, sort_order,
+                      (SELECT count(*) FROM places p WHERE p.category_id = categories.id)
+                          AS place_count,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(category_id)
+        .bind(user_id)
+        .bind(update.name.as_deref())
+        .bind(update.color.as_deref())
+        .bind(update.icon_key.as_deref())
+        .bind(update.sort_order)
+        .fetch_optional(tx.as_mut())
+        .await;
+
+        let record = match updated {
+            Ok(Some(record)) => record,
+            Ok(None) => return Ok(CategoryWrite::NotFound),
+            Err(err) if is_unique_violation(&err) => return Ok(CategoryWrite::NameTaken),
+            Err(err) => return Err(err.into()),
+        };
+
+        if update.name.is_some() {
+            sqlx::query(
+                r#"
+                UPDATE places
+                SET category = $2, updated_at = NOW()
+                WHERE category_id = $1 AND category <> $2
+                "#,
+            )
+            .bind(record.id)
+            .bind(&record.name)
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(CategoryWrite::Written(record))
+    }
+
+    pub async fn delete(&self, user_id: Uuid, category_id: Uuid) -> RepoResult<CategoryDelete> {
+        let mut tx = self.pool.begin().await?;
+
+        // Locking the row keeps places from being filed under it while it is deleted.
+        let exists = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT id
+            FROM categories
+            WHERE id = $1 AND user_id = $2
+            FOR UPDATE
+            "#,
+        )
+        .bind(category_id)
+        .bind(user_id)
+        .fetch_optional(tx.as_mut())
+        .await?;
+
+        if exists.is_none() {
+            return Ok(CategoryDelete::NotFound);
+        }
+
+        let in_use = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM places WHERE category_id = $1)",
+        )
+        .bind(category_id)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        if in_use {
+            return Ok(CategoryDelete::InUse);
+        }
+
+        sqlx::query("DELETE FROM categories WHERE id = $1")
+            .bind(category_id)
+            .execute(tx.as_mut())
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(CategoryDelete::Deleted)
+    }
+
+    /// Moves every place of `source_id` to `target_id` and deletes the source. Returns the target
+    /// with its new place count, or `None` when either category does not belong to the user.
+    pub async fn merge(
+        &self,
+        user_id: Uuid,
+        source_id: Uuid,
+        target_id: Uuid,
+    ) -> RepoResult<Option<CategoryRecord>> {
+        let mut tx = self.pool.begin().await?;
+
+        let locked = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT id
+            FROM categories
+            WHERE id = ANY($1) AND user_id = $2
+            ORDER BY id
+            FOR UPDATE
+            "#,
+        )
+        .bind([source_id, target_id])
+        .bind(user_id)
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        if locked.len() != 2 {
+            return Ok(None);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE places p
+            SET category_id = c.id, category = c.name, updated_at = NOW()
+            FROM categories c
+            WHERE p.category_id = $1 AND c.id = $2
+            "#,
+        )
+        .bind(source_id)
+        .bind(target_id)
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query("DELETE FROM categories WHERE id = $1")
+            .bind(source_id)
+            .execute(tx.as_mut())
+            .await?;
+
+        let target = sqlx::query_as::<_, CategoryRecord>(
+            r#"
+            SELECT c.id, c.name, c.color, c.icon_key, c.sort_order,
+                   (SELECT count(*) FROM places p WHERE p.category_id = c.id) AS place_count,
+                   c.created_at, c.updated_at
+            FROM categories c
+            WHERE c.id = $1
+            "#,
+        )
+        .bind(target_id)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(target))
+    }
+}
+
+fn is_unique_violation(err: &SqlxError) -> bool {
+    err.as_database_error()
+        .is_some_and(|db_err| db_err.is_unique_violation())
+}
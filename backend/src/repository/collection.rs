@@ -0,0 +1,892 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Error as SqlxError, FromRow, PgConnection, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::repository::place::PlaceRecord;
+
+#[derive(Debug, Error)]
+pub enum CollectionRepositoryError {
+    #[error("database error: {0}")]
+    Database(#[from] SqlxError),
+}
+
+type RepoResult<T> = Result<T, CollectionRepositoryError>;
+
+#[derive(Clone)]
+pub struct CollectionRepository {
+    pool: PgPool,
+}
+
+text_enum! {
+    /// What a member may do with a collection. Each role includes the rights of the ones before
+    /// it. Stored by name in `collection_members.role` and `collection_invites.role`.
+    #[derive(PartialOrd, Ord)]
+    pub enum CollectionRole {
+        Viewer => "viewer",
+        Editor => "editor",
+        Owner => "owner",
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct CollectionRecord {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Role of the user the collection was loaded for.
+    pub role: CollectionRole,
+    pub place_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewCollection<'a> {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: &'a str,
+    pub description: Option<&'a str>,
+}
+
+/// Changes to a collection. `None` keeps the current value and an empty `description` clears it.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateCollection {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CollectionPlaceChange {
+    Changed,
+    /// The collection does not exist or the user may not edit it.
+    CollectionNotFound,
+    /// The place is not one of the user's places, or not in the collection when removing.
+    PlaceNotFound,
+    /// A reorder did not list exactly the places of the collection.
+    PlacesMismatch,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct CollectionMemberRecord {
+    pub user_id: Uuid,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub role: CollectionRole,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MemberChange {
+    Changed,
+    /// The user is not a member of the collection.
+    NotFound,
+    /// The change would leave the collection without an owner.
+    LastOwner,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct CollectionInviteRecord {
+    pub id: Uuid,
+    pub collection_id: Uuid,
+    pub collection_name: String,
+    pub role: CollectionRole,
+    /// `None` for invites that are redeemed with a code.
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewCollectionInvite<'a> {
+    pub collection_id: Uuid,
+    pub invited_by: Uuid,
+    pub role: CollectionRole,
+    /// Lower-cased address of the invitee. Exactly one of `email` and `code_hash` is set.
+    pub email: Option<&'a str>,
+    pub code_hash: Option<&'a str>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl CollectionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Collections the user is a member of, with any role.
+    pub async fn list_for_user(&self, user_id: Uuid) -> RepoResult<Vec<CollectionRecord>> {
+        let records = sqlx::query_as::<_, CollectionRecord>(
+            r#"
+            SELECT c.id, c.name, c.description, m.role,
+                   (SELECT count(*) FROM collection_places cp WHERE cp.collection_id = c.id)
+                       AS place_count,
+                   c.created_at, c.updated_at
+            FROM collections c
+            JOIN collection_members m ON m.collection_id = c.id
+            WHERE m.user_id = $1
+            ORDER BY c.updated_at DESC, c.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Finds a collection the user is a member of.
+    pub async fn find_for_user(
+        &self,
+        user_id: Uuid,
+        collection_id: Uuid,
+    ) -> RepoResult<Option<CollectionRecord>> {
+        let record = sqlx::query_as::<_, CollectionRecord>(
+            r#"
+            SELECT c.id, c.name, c.description, m.role,
+                   (SELECT count(*) FROM collection_places cp WHERE cp.collection_id = c.id)
+                       AS place_count,
+                   c.created_at, c.updated_at
+            FROM collections c
+            JOIN collection_members m ON m.collection_id = c.id
+            WHERE c.id = $1 AND m.user_id = $2
+            "#,
+        )
+        .bind(collection_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Places of a collection in their manual order.
+    pub async fn list_places(&self, collection_id: Uuid) -> RepoResult<Vec<PlaceRecord>> {
+        let records = sqlx::query_as::<_, PlaceRecord>(
+            r#"
+            SELECT p.id, p.user_id, p.name, p.category_id, p.category, p.location, p.note,
+                   p.latitude, p.longitude, p.address, p.place_provider_id, p.status,
+                   p.planned_on, p.priority, p.visibility, p.created_at, p.updated_at
+            FROM collection_places cp
+            JOIN places p ON p.id = cp.place_id
+            WHERE cp.collection_id = $1
+            ORDER BY cp.position
+            "#,
+        )
+        .bind(collection_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Creates a collection owned by `payload.user_id`.
+    pub async fn create(&self, payload: NewCollection<'_>) -> RepoResult<CollectionRecord> {
+        let mut tx = self.pool.begin().await?;
+
+        let record = sqlx::query_as::<_, CollectionRecord>(
+            r#"
+            INSERT INTO collections (id, user_id, name, description)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, name, description, 'owner' AS role, 0::BIGINT AS place_count,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(payload.id)
+        .bind(payload.user_id)
+        .bind(payload.name)
+        .bind(payload.description)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO collection_members (collection_id, user_id, role)
+            VALUES ($1, $2, 'owner')
+            "#,
+        )
+        .bind(payload.id)
+        .bind(payload.user_id)
+        .execute(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(record)
+    }
+
+    /// Renames a collection or changes its description. Only owners may do this.
+    pub async fn update(
+        &self,
+        user_id: Uuid,
+        collection_id: Uuid,
+        update: UpdateCollection,
+    ) -> RepoResult<Option<CollectionRecord>> {
+        let record = sqlx::query_as::<_, CollectionRecord>(
+            r#"
+            UPDATE collections
+            SET name = COALESCE($3, name),
+                description = NULLIF(COALESCE($4, description), ''),
+                updated_at = NOW()
+            WHERE id = $1
+              AND EXISTS (
+                  SELECT 1 FROM collection_members m
+                  WHERE m.collection_id = collections.id AND m.user_id = $2 AND m.role = 'owner'
+              )
+            RETURNING id, name, description, 'owner' AS role,
+                      (SELECT count(*) FROM collection_places cp
+                       WHERE cp.collection_id = collections.id) AS place_count,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(collection_id)
+        .bind(user_id)
+        .bind(update.name.as_deref())
+        .bind(update.description.as_deref())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Deletes a collection if the user owns it. Its places are not affected.
+    pub async fn delete(&self, user_id: Uuid, collection_id: Uuid) -> RepoResult<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM collections c
+            USING collection_members m
+            WHERE c.id = $1 AND m.collection_id = c.id AND m.user_id = $2 AND m.role = 'owner'
+            "#,
+        )
+        .bind(collection_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Adds one of the user's places at `position`, or at the end when `None` or past the end.
+    /// Adding a place that is already in the collection moves it instead. Members can only add
+    /// their own places, so joining a collection never exposes other users' places to it.
+    pub async fn add_place(
+        &self,
+        user_id: Uuid,
+        collection_id: Uuid,
+        place_id: Uuid,
+        position: Option<i32>,
+    ) -> RepoResult<CollectionPlaceChange> {
+        let mut tx = self.pool.begin().await?;
+
+        if !lock_collection(tx.as_mut(), user_id, collection_id).await? {
+            return Ok(CollectionPlaceChange::CollectionNotFound);
+        }
+
+        let may_add = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM places WHERE id = $1 AND user_id = $2)
+                OR EXISTS (
+                    SELECT 1 FROM collection_places WHERE collection_id = $3 AND place_id = $1
+                )
+            "#,
+        )
+        .bind(place_id)
+        .bind(user_id)
+        .bind(collection_id)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        if !may_add {
+            return Ok(CollectionPlaceChange::PlaceNotFound);
+        }
+
+        let mut place_ids = place_ids_in_order(tx.as_mut(), collection_id).await?;
+        place_ids.retain(|id| *id != place_id);
+        let index = position
+            .and_then(|position| usize::try_from(position).ok())
+            .map_or(place_ids.len(), |position| position.min(place_ids.len()));
+        place_ids.insert(index, place_id);
+
+        sqlx::query(
+            r#"
+            INSERT INTO collection_places (collection_id, place_id, position)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (collection_id, place_id) DO NOTHING
+            "#,
+        )
+        .bind(collection_id)
+        .bind(place_id)
+        .bind(index as i32)
+        .execute(tx.as_mut())
+        .await?;
+
+        write_positions(tx.as_mut(), collection_id, &place_ids).await?;
+        touch_collections(tx.as_mut(), &[collection_id]).await?;
+
+        tx.commit().await?;
+
+        Ok(CollectionPlaceChange::Changed)
+    }
+
+    pub async fn remove_place(
+        &self,
+        user_id: Uuid,
+        collection_id: Uuid,
+        place_id: Uuid,
+    ) -> RepoResult<CollectionPlaceChange> {
+        let mut tx = self.pool.begin().await?;
+
+        if !lock_collection(tx.as_mut(), user_id, collection_id).await? {
+            return Ok(CollectionPlaceChange::CollectionNotFound);
+        }
+
+        let removed =
+            sqlx::query("DELETE FROM collection_places WHERE collection_id = $1 AND place_id = $2")
+                .bind(collection_id)
+                .bind(place_id)
+                .execute(tx.as_mut())
+                .await?;
+
+        if removed.rows_affected() == 0 {
+            return Ok(CollectionPlaceChange::PlaceNotFound);
+        }
+
+        let place_ids = place_ids_in_order(tx.as_mut(), collection_id).await?;
+        write_positions(tx.as_mut(), collection_id, &place_ids).await?;
+        touch_collections(tx.as_mut(), &[collection_id]).await?;
+
+        tx.commit().await?;
+
+        Ok(CollectionPlaceChange::Changed)
+    }
+
+    /// Puts the places of a collection in the order of `place_ids`, which must list every place
+    /// of the collection exactly once.
+    pub async fn reorder(
+        &self,
+        user_id: Uuid,
+        collection_id: Uuid,
+        place_ids: &[Uuid],
+    ) -> RepoResult<CollectionPlaceChange> {
+        let mut tx = self.pool.begin().await?;
+
+        if !lock_collection(tx.as_mut(), user_id, collection_id).await? {
+            return Ok(CollectionPlaceChange::CollectionNotFound);
+        }
+
+        let mut current = place_ids_in_order(tx.as_mut(), collection_id).await?;
+        let mut requested = place_ids.to_vec();
+        current.sort_unstable();
+        requested.sort_unstable();
+        requested.dedup();
+        if current != requested || requested.len() != place_ids.len() {
+            return Ok(CollectionPlaceChange::PlacesMismatch);
+        }
+
+        write_positions(tx.as_mut(), collection_id, place_ids).await?;
+        touch_collections(tx.as_mut(), &[collection_id]).await?;
+
+        tx.commit().await?;
+
+        Ok(CollectionPlaceChange::Changed)
+    }
+
+    pub async fn list_members(
+        &self,
+        collection_id: Uuid,
+    ) -> RepoResult<Vec<CollectionMemberRecord>> {
+        let records = sqlx::query_as::<_, CollectionMemberRecord>(
+            r#"
+            SELECT m.user_id, u.name, u.avatar_url, m.role, m.joined_at
+            FROM collection_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.collection_id = $1
+            ORDER BY m.joined_at, m.user_id
+            "#,
+        )
+        .bind(collection_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    pub async fn set_member_role(
+        &self,
+        collection_id: Uuid,
+        member_id: Uuid,
+        role: CollectionRole,
+    ) -> RepoResult<MemberChange> {
+        let mut tx = self.pool.begin().await?;
+        lock_collection_members(tx.as_mut(), collection_id).await?;
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE collection_members
+            SET role = $3
+            WHERE collection_id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(collection_id)
+        .bind(member_id)
+        .bind(role)
+        .execute(tx.as_mut())
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(MemberChange::NotFound);
+        }
+        if !has_owner(tx.as_mut(), collection_id).await? {
+            return Ok(MemberChange::LastOwner);
+        }
+
+        tx.commit().await?;
+
+        Ok(MemberChange::Changed)
+    }
+
+    /// Removes a member, or lets a member leave. The member's own places are taken out of the
+    /// collection, since the remaining members would otherwise keep seeing them.
+    pub async fn remove_member(
+        &self,
+        collection_id: Uuid,
+        member_id: Uuid,
+    ) -> RepoResult<MemberChange> {
+        let mut tx = self.pool.begin().await?;
+        lock_collection_members(tx.as_mut(), collection_id).await?;
+
+        let removed =
+            sqlx::query("DELETE FROM collection_members WHERE collection_id = $1 AND user_id = $2")
+                .bind(collection_id)
+                .bind(member_id)
+                .execute(tx.as_mut())
+                .await?;
+
+        if removed.rows_affected() == 0 {
+            return Ok(MemberChange::NotFound);
+        }
+        if !has_owner(tx.as_mut(), collection_id).await? {
+            return Ok(MemberChange::LastOwner);
+        }
+
+        let removed_places = sqlx::query(
+            r#"
+            DELETE FROM collection_places cp
+            USING places p
+            WHERE cp.collection_id = $1 AND p.id = cp.place_id AND p.user_id = $2
+            "#,
+        )
+        .bind(collection_id)
+        .bind(member_id)
+        .execute(tx.as_mut())
+        .await?;
+
+        if removed_places.rows_affected() > 0 {
+            let place_ids = place_ids_in_order(tx.as_mut(), collection_id).await?;
+            write_positions(tx.as_mut(), collection_id, &place_ids).await?;
+            touch_collections(tx.as_mut(), &[collection_id]).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(MemberChange::Changed)
+    }
+
+    pub async fn create_invite(
+        &self,
+        payload: NewCollectionInvite<'_>,
+    ) -> RepoResult<CollectionInviteRecord> {
+        let record = sqlx::query_as::<_, CollectionInviteRecord>(
+            r#"
+            INSERT INTO collection_invites (
+                id, collection_id, invited_by, role, email, code_hash, expires_at
+            )
+            SELECT $1, c.id, $3, $4, $5, $6, $7
+            FROM collections c
+            WHERE c.id = $2
+            RETURNING id, collection_id,
+                      (SELECT name FROM collections WHERE id = collection_id) AS collection_name,
+                      role, email, created_at, expires_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(payload.collection_id)
+        .bind(payload.invited_by)
+        .bind(payload.role)
+        .bind(payload.email)
+        .bind(payload.code_hash)
+        .bind(payload.expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Invites of a collection that have not expired, newest first.
+    pub async fn list_invites(
+        &self,
+        collection_id: Uuid,
+    ) -> RepoResult<Vec<CollectionInviteRecord>> {
+        let records = sqlx::query_as::<_, CollectionInviteRecord>(
+            r#"
+            SELECT i.id, i.collection_id, c.name AS collection_name, i.role, i.email,
+                   i.created_at, i.expires_at
+            FROM collection_invites i
+            JOIN collections c ON c.id = i.collection_id
+            WHERE i.collection_id = $1 AND i.expires_at > NOW()
+            ORDER BY i.created_at DESC, i.id
+            "#,
+        )
+        .bind(collection_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    pub async fn delete_invite(&self, collection_id: Uuid, invite_id: Uuid) -> RepoResult<bool> {
+        let result =
+            sqlx::query("DELETE FROM collection_invites WHERE id = $1 AND collection_id = $2")
+                .bind(invite_id)
+                .bind(collection_id)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Pending invites addressed to the user's email address, newest first.
+    pub async fn list_invites_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> RepoResult<Vec<CollectionInviteRecord>> {
+        let records = sqlx::query_as::<_, CollectionInviteRecord>(
+            r#"
+            SELECT i.id, i.collection_id, c.name AS collection_name, i.role, i.email,
+                   i.created_at, i.expires_at
+            FROM collection_invites i
+            JOIN collections c ON c.id = i.collection_id
+            JOIN users u ON lower(u.email) = i.email
+            WHERE u.id = $1 AND i.expires_at > NOW()
+            ORDER BY i.created_at DESC, i.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Accepts an invite addressed to the user's email address, which uses it up. Returns the id
+    /// of the joined collection, or `None` when there is no such pending invite.
+    pub async fn accept_invite(&self, user_id: Uuid, invite_id: Uuid) -> RepoResult<Option<Uuid>> {
+        let mut tx = self.pool.begin().await?;
+
+        let invite = sqlx::query_as::<_, (Uuid, CollectionRole)>(
+            r#"
+            DELETE FROM collection_invites i
+            USING users u
+            WHERE i.id = $1 AND u.id = $2 AND lower(u.email) = i.email AND i.expires_at > NOW()
+            RETURNING i.collection_id, i.role
+            "#,
+        )
+        .bind(invite_id)
+        .bind(user_id)
+        .fetch_optional(tx.as_mut())
+        .await?;
+
+        let Some((collection_id, role)) = invite else {
+            return Ok(None);
+        };
+        add_member(tx.as_mut(), collection_id, user_id, role).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(collection_id))
+    }
+
+    /// Declines an invite addressed to the user's email address.
+    pub async fn decline_invite(&self, user_id: Uuid, invite_id: Uuid) -> RepoResult<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM collection_invites i
+            USING users u
+            WHERE i.id = $1 AND u.id = $2 AND lower(u.email) = i.email
+            "#,
+        )
+        .bind(invite_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Joins the collection of a code invite. Code invites stay valid for other users until they
+    /// expire or are revoked. Returns `None` for unknown or expired codes.
+    pub async fn redeem_invite_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &str,
+    ) -> RepoResult<Option<Uuid>> {
+        let mut tx = self.pool.begin().await?;
+
+        let invite = sqlx::query_as::<_, (Uuid, CollectionRole)>(
+            r#"
+            SELECT collection_id, role
+            FROM collection_invites
+            WHERE code_hash = $1 AND expires_at > NOW()
+            "#,
+        )
+        .bind(code_hash)
+        .fetch_optional(tx.as_mut())
+        .await?;
+
+        let Some((collection_id, role)) = invite else {
+            return Ok(None);
+        };
+        add_member(tx.as_mut(), collection_id, user_id, role).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(collection_id))
+    }
+}
+
+/// Takes places out of every collection, closing the gaps they leave in their order. Called
+/// inside the transaction that deletes the places. The collections are locked in id order
+/// first, like any other change to their order.
+pub async fn remove_places_from_collections(
+    conn: &mut PgConnection,
+    place_ids: &[Uuid],
+) -> Result<(), SqlxError> {
+    if place_ids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        SELECT id
+        FROM collections
+        WHERE id IN (SELECT collection_id FROM collection_places WHERE place_id = ANY($1))
+        ORDER BY id
+        FOR UPDATE
+        "#,
+    )
+    .bind(place_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    let mut collection_ids = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        DELETE FROM collection_places
+        WHERE place_id = ANY($1)
+        RETURNING collection_id
+        "#,
+    )
+    .bind(place_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+    collection_ids.sort();
+    collection_ids.dedup();
+
+    for collection_id in &collection_ids {
+        let place_ids = place_ids_in_order(&mut *conn, *collection_id).await?;
+        write_positions(&mut *conn, *collection_id, &place_ids).await?;
+    }
+    touch_collections(conn, &collection_ids).await?;
+
+    Ok(())
+}
+
+/// Takes a user out of their collections ahead of deleting their account. Collections only
+/// they own pass to the member who joined first, and collections nobody else belongs to are
+/// deleted. Called inside the transaction that deletes the user.
+pub async fn leave_all_collections(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+) -> Result<(), SqlxError> {
+    sqlx::query(
+        r#"
+        SELECT c.id
+        FROM collections c
+        JOIN collection_members m ON m.collection_id = c.id
+        WHERE m.user_id = $1
+        ORDER BY c.id
+        FOR UPDATE OF c
+        "#,
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE collection_members m
+        SET role = 'owner'
+        FROM (
+            SELECT DISTINCT ON (cm.collection_id) cm.collection_id, cm.user_id
+            FROM collection_members cm
+            WHERE cm.user_id <> $1
+              AND cm.collection_id IN (
+                  SELECT collection_id FROM collection_members
+                  WHERE user_id = $1 AND role = 'owner'
+              )
+              AND NOT EXISTS (
+                  SELECT 1 FROM collection_members o
+                  WHERE o.collection_id = cm.collection_id AND o.user_id <> $1
+                    AND o.role = 'owner'
+              )
+            ORDER BY cm.collection_id, cm.joined_at, cm.user_id
+        ) heir
+        WHERE m.collection_id = heir.collection_id AND m.user_id = heir.user_id
+        "#,
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM collections c
+        WHERE c.id IN (SELECT collection_id FROM collection_members WHERE user_id = $1)
+          AND NOT EXISTS (
+              SELECT 1 FROM collection_members o
+              WHERE o.collection_id = c.id AND o.user_id <> $1
+          )
+        "#,
+    )
+    .bind(user_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Locks the collection row so concurrent changes to its order are applied one at a time.
+/// Returns `false` when the user is not an editor or owner of the collection.
+async fn lock_collection(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    collection_id: Uuid,
+) -> Result<bool, SqlxError> {
+    let locked = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT c.id
+        FROM collections c
+        JOIN collection_members m ON m.collection_id = c.id
+        WHERE c.id = $1 AND m.user_id = $2 AND m.role IN ('editor', 'owner')
+        FOR UPDATE OF c
+        "#,
+    )
+    .bind(collection_id)
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(locked.is_some())
+}
+
+/// Joins a collection. Users who already are members keep their role.
+async fn add_member(
+    conn: &mut PgConnection,
+    collection_id: Uuid,
+    user_id: Uuid,
+    role: CollectionRole,
+) -> Result<(), SqlxError> {
+    sqlx::query(
+        r#"
+        INSERT INTO collection_members (collection_id, user_id, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (collection_id, user_id) DO NOTHING
+        "#,
+    )
+    .bind(collection_id)
+    .bind(user_id)
+    .bind(role)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Locks the collection row so that concurrent member changes cannot both remove the last owner.
+async fn lock_collection_members(
+    conn: &mut PgConnection,
+    collection_id: Uuid,
+) -> Result<(), SqlxError> {
+    sqlx::query("SELECT id FROM collections WHERE id = $1 FOR UPDATE")
+        .bind(collection_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn has_owner(conn: &mut PgConnection, collection_id: Uuid) -> Result<bool, SqlxError> {
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM collection_members WHERE collection_id = $1 AND role = 'owner'
+        )
+        "#,
+    )
+    .bind(collection_id)
+    .fetch_one(conn)
+    .await
+}
+
+async fn place_ids_in_order(
+    conn: &mut PgConnection,
+    collection_id: Uuid,
+) -> Result<Vec<Uuid>, SqlxError> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT place_id
+        FROM collection_places
+        WHERE collection_id = $1
+        ORDER BY position, added_at
+        "#,
+    )
+    .bind(collection_id)
+    .fetch_all(conn)
+    .await
+}
+
+/// Numbers the places of a collection 0, 1, 2, ... in the order of `place_ids`.
+async fn write_positions(
+    conn: &mut PgConnection,
+    collection_id: Uuid,
+    place_ids: &[Uuid],
+) -> Result<(), SqlxError> {
+    sqlx::query(
+        r#"
+        UPDATE collection_places cp
+        SET position = ordered.position - 1
+        FROM unnest($2::UUID[]) WITH ORDINALITY AS ordered (place_id, position)
+        WHERE cp.collection_id = $1 AND cp.place_id = ordered.place_id
+        "#,
+    )
+    .bind(collection_id)
+    .bind(place_ids)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn touch_collections(
+    conn: &mut PgConnection,
+    collection_ids: &[Uuid],
+) -> Result<(), SqlxError> {
+    if collection_ids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE collections SET updated_at = NOW() WHERE id = ANY($1)")
+        .bind(collection_ids)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::{Error as SqlxError, FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::repository::social::can_view_place;
+
+#[derive(Debug, Error)]
+pub enum CommentRepositoryError {
+    #[error("database error: {0}")]
+    Database(#[from] SqlxError),
+}
+
+type RepoResult<T> = Result<T, CommentRepositoryError>;
+
+/// Comments and reactions on places. Everything here requires that the user can see the place,
+/// see `can_view_place`.
+#[derive(Clone)]
+pub struct CommentRepository {
+    pool: PgPool,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct CommentRecord {
+    pub id: Uuid,
+    pub place_id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: Option<String>,
+    pub user_avatar_url: Option<String>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ReactionRecord {
+    pub place_id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: Option<String>,
+    pub user_avatar_url: Option<String>,
+    pub emoji: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReactionCountRecord {
+    pub emoji: String,
+    pub count: i64,
+}
+
+/// Comment and reaction counts of a place. Reactions are ordered by count, most used first.
+#[derive(Debug, Clone, Default)]
+pub struct PlaceFeedbackCounts {
+    pub comment_count: i64,
+    pub reactions: Vec<ReactionCountRecord>,
+}
+
+#[derive(Debug)]
+pub enum CommentWrite {
+    Written(CommentRecord),
+    /// The place is not visible to the user or has no such comment.
+    NotFound,
+    /// Someone else wrote the comment.
+    Forbidden,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FeedbackRemoval {
+    Removed,
+    /// The place is not visible to the user or has no such comment or reaction.
+    NotFound,
+    /// Neither the author nor the owner of the place.
+    Forbidden,
+}
+
+impl CommentRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Lists the comments of a place, oldest first. Returns `None` when the user cannot see it.
+    pub async fn list_comments(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+    ) -> RepoResult<Option<Vec<CommentRecord>>> {
+        let mut conn = self.pool.acquire().await?;
+        if !can_view_place(&mut conn, user_id, place_id).await? {
+            return Ok(None);
+        }
+
+        let records = sqlx::query_as::<_, CommentRecord>(
+            r#"
+            SELECT c.id, c.place_id, c.user_id, u.name AS user_name,
+                   u.avatar_url AS user_avatar_url, c.body, c.created_at, c.updated_at
+            FROM place_comments c
+            JOIN users u ON u.id = c.user_id
+            WHERE c.place_id = $1
+            ORDER BY c.created_at, c.id
+            "#,
+        )
+        .bind(place_id)
+        .fetch_all(conn.as_mut())
+        .await?;
+
+        Ok(Some(records))
+    }
+
+    /// Returns `None` when the user cannot see the place.
+    pub async fn create_comment(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+        body: &str,
+    ) -> RepoResult<Option<CommentRecord>> {
+        let mut tx = self.pool.begin().await?;
+        if !can_view_place(tx.as_mut(), user_id, place_id).await? {
+            return Ok(None);
+        }
+
+        let record = sqlx::query_as::<_, CommentRecord>(
+            r#"
+            WITH inserted AS (
+                INSERT INTO place_comments (id, place_id, user_id, body)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, place_id, user_id, body, created_at, updated_at
+            )
+            SELECT c.id, c.place_id, c.user_id, u.name AS user_name,
+                   u.avatar_url AS user_avatar_url, c.body, c.created_at, c.updated_at
+            FROM inserted c
+            JOIN users u ON u.id = c.user_id
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(place_id)
+        .bind(user_id)
+        .bind(body)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(record))
+    }
+
+    /// Changes the text of a comment. Only its author may.
+    pub async fn update_comment(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+        comment_id: Uuid,
+        body: &str,
+    ) -> RepoResult<CommentWrite> {
+        let mut tx = self.pool.begin().await?;
+        if !can_view_place(tx.as_mut(), user_id, place_id).await? {
+            return Ok(CommentWrite::NotFound);
+        }
+
+        let author_id = sqlx::query_scalar::<_, Uuid>(
+            "SELECT user_id FROM place_comments WHERE id = $1 AND place_id = $2 FOR UPDATE",
+        )
+        .bind(comment_id)
+        .bind(place_id)
+        .fetch_optional(tx.as_mut())
+        .await?;
+        match author_id {
+            None => return Ok(CommentWrite::NotFound),
+            Some(author_id) if author_id != user_id => return Ok(CommentWrite::Forbidden),
+            Some(_) => {}
+        }
+
+        let record = sqlx::query_as::<_, CommentRecord>(
+            r#"
+            WITH updated AS (
+                UPDATE place_comments
+                SET body = $2, updated_at = NOW()
+                WHERE id = $1
+                RETURNING id, place_id, user_id, body, created_at, updated_at
+            )
+            SELECT c.id, c.place_id, c.user_id, u.name AS user_name,
+                   u.avatar_url AS user_avatar_url, c.body, c.created_at, c.updated_at
+            FROM updated c
+            JOIN users u ON u.id = c.user_id
+            "#,
+        )
+        .bind(comment_id)
+        .bind(body)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(CommentWrite::Written(record))
+    }
+
+    /// Deletes a comment on behalf of its author or of the owner of the place.
+    pub async fn delete_comment(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+        comment_id: Uuid,
+    ) -> RepoResult<FeedbackRemoval> {
+        let mut tx = self.pool.begin().await?;
+        if !can_view_place(tx.as_mut(), user_id, place_id).await? {
+            return Ok(FeedbackRemoval::NotFound);
+        }
+
+        let allowed = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT c.user_id = $3 OR p.user_id = $3
+            FROM place_comments c
+            JOIN places p ON p.id = c.place_id
+            WHERE c.id = $1 AND c.place_id = $2
+            "#,
+        )
+        .bind(comment_id)
+        .bind(place_id)
+        .bind(user_id)
+        .fetch_optional(tx.as_mut())
+        .await?;
+        match allowed {
+            None => return Ok(FeedbackRemoval::NotFound),
+            Some(false) => return Ok(FeedbackRemoval::Forbidden),
+            Some(true) => {}
+        }
+
+        sqlx::query("DELETE FROM place_comments WHERE id = $1")
+            .bind(comment_id)
+            .execute(tx.as_mut())
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(FeedbackRemoval::Removed)
+    }
+
+    /// Lists the reactions on a place, oldest first. Returns `None` when the user cannot see it.
+    pub async fn list_reactions(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+    ) -> RepoResult<Option<Vec<ReactionRecord>>> {
+        let mut conn = self.pool.acquire().await?;
+        if !can_view_place(&mut conn, user_id, place_id).await? {
+            return Ok(None);
+        }
+
+        let records = sqlx::query_as::<_, ReactionRecord>(
+            r#"
+            SELECT r.place_id, r.user_id, u.name AS user_name, u.avatar_url AS user_avatar_url,
+                   r.emoji, r.created_at
+            FROM place_reactions r
+            JOIN users u ON u.id = r.user_id
+            WHERE r.place_id = $1
+            ORDER BY r.created_at, r.user_id
+            "#,
+        )
+        .bind(place_id)
+        .fetch_all(conn.as_mut())
+        .await?;
+
+        Ok(Some(records))
+    }
+
+    /// Sets the user's reaction, replacing an earlier one. Returns `None` when the user cannot see
+    /// the place.
+    pub async fn set_reaction(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+        emoji: &str,
+    ) -> RepoResult<Option<ReactionRecord>> {
+        let mut tx = self.pool.begin().await?;
+        if !can_view_place(tx.as_mut(), user_id, place_id).await? {
+            return Ok(None);
+        }
+
+        let record = sqlx::query_as::<_, ReactionRecord>(
+            r#"
+            WITH upserted AS (
+                INSERT INTO place_reactions (place_id, user_id, emoji)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (place_id, user_id)
+                DO UPDATE SET emoji = EXCLUDED.emoji, created_at = NOW()
+                RETURNING place_id, user_id, emoji, created_at
+            )
+            SELECT r.place_id, r.user_id, u.name AS user_name, u.avatar_url AS user_avatar_url,
+                   r.emoji, r.created_at
+            FROM upserted r
+            JOIN users u ON u.id = r.user_id
+            "#,
+        )
+        .bind(place_id)
+        .bind(user_id)
+        .bind(emoji)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(record))
+    }
+
+    /// Removes the reaction of `reactor_id`, on behalf of that user or of the owner of the place.
+    pub async fn remove_reaction(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+        reactor_id: Uuid,
+    ) -> RepoResult<FeedbackRemoval> {
+        let mut tx = self.pool.begin().await?;
+        if !can_view_place(tx.as_mut(), user_id, place_id).await? {
+            return Ok(FeedbackRemoval::NotFound);
+        }
+
+        if reactor_id != user_id {
+            let is_owner = sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS (SELECT 1 FROM places WHERE id = $1 AND user_id = $2)",
+            )
+            .bind(place_id)
+            .bind(user_id)
+            .fetch_one(tx.as_mut())
+            .await?;
+            if !is_owner {
+                return Ok(FeedbackRemoval::Forbidden);
+            }
+        }
+
+        let result =
+            sqlx::query("DELETE FROM place_reactions WHERE place_id = $1 AND user_id = $2")
+                .bind(place_id)
+                .bind(reactor_id)
+                .execute(tx.as_mut())
+                .await?;
+
+        tx.commit().await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(FeedbackRemoval::NotFound);
+        }
+        Ok(FeedbackRemoval::Removed)
+    }
+
+    /// Counts the comments and reactions of the places the user has access to.
+    pub async fn list_counts_for_places(
+        &self,
+        user_id: Uuid,
+        place_ids: &[Uuid],
+    ) -> RepoResult<HashMap<Uuid, PlaceFeedbackCounts>> {
+        if place_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let comment_counts = sqlx::query_as::<_, (Uuid, i64)>(
+            r#"
+            SELECT c.place_id, count(*)
+            FROM place_comments c
+            WHERE c.place_id = ANY($1)
+              AND EXISTS (
+                  SELECT 1 FROM place_access a WHERE a.place_id = c.place_id AND a.user_id = $2
+              )
+            GROUP BY c.place_id
+            "#,
+        )
+        .bind(place_ids)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let reaction_counts = sqlx::query_as::<_, (Uuid, String, i64)>(
+            r#"
+            SELECT r.place_id, r.emoji, count(*) AS count
+            FROM place_reactions r
+            WHERE r.place_id = ANY($1)
+              AND EXISTS (
+                  SELECT 1 FROM place_access a WHERE a.place_id = r.place_id AND a.user_id = $2
+              )
+            GROUP BY r.place_id, r.emoji
+            ORDER BY r.place_id, count DESC, r.emoji
+            "#,
+        )
+        .bind(place_ids)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut counts: HashMap<Uuid, PlaceFeedbackCounts> = HashMap::new();
+        for (place_id, comment_count) in comment_counts {
+            counts.entry(place_id).or_default().comment_count = comment_count;
+        }
+        for (place_id, emoji, count) in reaction_counts {
+            counts
+                .entry(place_id)
+                .or_default()
+                .reactions
+                .push(ReactionCountRecord { emoji, count });
+        }
+
+        Ok(counts)
+    }
+}
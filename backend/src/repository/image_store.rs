@@ -5,6 +5,9 @@ use tokio::fs;
 use tracing::error;
 use uuid::Uuid;
 
+/// Avatars live next to the per-place directories, which are named by place id.
+const AVATAR_DIR: &str = "avatars";
+
 #[derive(Clone)]
 pub struct ImageStore {
     base_dir: Arc<PathBuf>,
@@ -44,7 +47,7 @@ impl ImageStore {
         for upload in uploads {
             let file_name = self
                 .write_image(
-                    place_id,
+                    &self.base_dir.join(place_id.to_string()),
                     upload.id,
                     upload.file_name.as_deref(),
                     &upload.bytes,
@@ -94,15 +97,65 @@ impl ImageStore {
         fs::read(path).await
     }
 
+    pub fn avatar_path_for(&self, user_id: Uuid, file_name: &str) -> PathBuf {
+        self.avatar_dir(user_id).join(file_name)
+    }
+
+    pub async fn save_avatar(
+        &self,
+        user_id: Uuid,
+        upload: ImageUpload,
+    ) -> Result<StoredImage, std::io::Error> {
+        let file_name = self
+            .write_image(
+                &self.avatar_dir(user_id),
+                upload.id,
+                upload.file_name.as_deref(),
+                &upload.bytes,
+            )
+            .await?;
+        Ok(StoredImage {
+            id: upload.id,
+            file_name,
+        })
+    }
+
+    pub async fn get_avatar(
+        &self,
+        user_id: Uuid,
+        file_name: &str,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        fs::read(self.avatar_path_for(user_id, file_name)).await
+    }
+
+    pub async fn remove_avatar(&self, user_id: Uuid, file_name: &str) {
+        let path = self.avatar_path_for(user_id, file_name);
+        if let Err(err) = fs::remove_file(&path).await {
+            error!(?err, ?path, "failed to delete avatar file");
+        }
+    }
+
+    pub async fn remove_avatar_dir(&self, user_id: Uuid) {
+        let dir = self.avatar_dir(user_id);
+        if let Err(err) = fs::remove_dir_all(&dir).await {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                error!(?err, ?dir, "failed to delete avatar directory");
+            }
+        }
+    }
+
+    fn avatar_dir(&self, user_id: Uuid) -> PathBuf {
+        self.base_dir.join(AVATAR_DIR).join(user_id.to_string())
+    }
+
     async fn write_image(
         &self,
-        place_id: Uuid,
+        dir: &Path,
         image_id: Uuid,
         file_name: Option<&str>,
         bytes: &[u8],
     ) -> Result<String, std::io::Error> {
-        let place_dir = self.base_dir.join(place_id.to_string());
-        fs::create_dir_all(&place_dir).await?;
+        fs::create_dir_all(dir).await?;
 
         let extension = file_name
             .and_then(|name| Path::new(name).extension())
@@ -115,7 +168,7 @@ impl ImageStore {
             extension.unwrap_or_else(|| String::from(""))
         );
 
-        let full_path = place_dir.join(&stored_file_name);
+        let full_path = dir.join(&stored_file_name);
         fs::write(full_path, bytes).await?;
 
         Ok(stored_file_name)
@@ -1,8 +1,14 @@
-use chrono::{DateTime, Utc};
-use sqlx::{Error as SqlxError, FromRow, PgPool};
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Error as SqlxError, FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::geo::{BoundingBox, EARTH_RADIUS_M};
+use crate::repository::collection::remove_places_from_collections;
+use crate::repository::social::{can_view_place, record_activity, ActivityKind};
+
 #[derive(Debug, Error)]
 pub enum PlaceRepositoryError {
     #[error("database error: {0}")]
@@ -21,17 +27,170 @@ pub struct PlaceRecord {
     pub id: Uuid,
     pub user_id: Uuid,
     pub name: String,
+    pub category_id: Uuid,
+    /// Name of the category, kept in sync with `categories.name`.
     pub category: String,
     pub location: String,
     pub note: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub address: Option<String>,
+    pub place_provider_id: Option<String>,
+    pub status: PlaceStatus,
+    /// Day the user plans to go, mostly useful for wishlist places.
+    pub planned_on: Option<NaiveDate>,
+    /// 1 to 5, higher is more important.
+    pub priority: Option<i16>,
+    pub visibility: PlaceVisibility,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+text_enum! {
+    /// Whether a place is on the user's wishlist or a memory. Stored by name in `places.status`.
+    #[derive(Default)]
+    pub enum PlaceStatus {
+        WantToGo => "want_to_go",
+        #[default]
+        Visited => "visited",
+        Favorite => "favorite",
+        Archived => "archived",
+    }
+}
+
+text_enum! {
+    /// Who sees a place in their feed besides its owner and the members of collections holding
+    /// it. `Friends` means users who follow the owner and are followed back.
+    #[derive(Default)]
+    pub enum PlaceVisibility {
+        #[default]
+        Private => "private",
+        Friends => "friends",
+        Public => "public",
+    }
+}
+
+/// Filters, order and page of a place listing. The default lists every place, newest first.
+#[derive(Debug, Clone, Default)]
+pub struct PlaceListQuery<'a> {
+    pub category_id: Option<Uuid>,
+    pub category: Option<&'a str>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+    pub has_images: Option<bool>,
+    /// Places with any of these statuses. Empty lists every status.
+    pub statuses: &'a [PlaceStatus],
+    /// Inclusive range of `planned_on`. Places without a planned date are left out when set.
+    pub planned_after: Option<NaiveDate>,
+    pub planned_before: Option<NaiveDate>,
+    /// Lower-cased tag names. Places need any of them, or all when `all_tags` is set.
+    pub tags: &'a [String],
+    pub all_tags: bool,
+    pub sort: PlaceSort,
+    pub descending: bool,
+    /// Continue after this position of a previous page.
+    pub after: Option<PlaceCursor>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PlaceSort {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Name,
+    /// Distance from a point. Places without coordinates sort after all others.
+    Distance {
+        latitude: f64,
+        longitude: f64,
+    },
+}
+
+/// Sort value and id of the last place on a page. The id breaks ties between equal sort values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaceCursor {
+    pub key: PlaceSortKey,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaceSortKey {
+    Time(DateTime<Utc>),
+    Name(String),
+    /// `f64::INFINITY` for places without coordinates.
+    Distance(f64),
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct PlaceListRecord {
+    #[sqlx(flatten)]
+    pub place: PlaceRecord,
+    /// Only set when sorting by distance and the place has coordinates.
+    pub distance_m: Option<f64>,
+}
+
+impl PlaceListRecord {
+    /// Cursor pointing just after this place in a listing sorted by `sort`.
+    pub fn cursor(&self, sort: PlaceSort) -> PlaceCursor {
+        let key = match sort {
+            PlaceSort::CreatedAt => PlaceSortKey::Time(self.place.created_at),
+            PlaceSort::UpdatedAt => PlaceSortKey::Time(self.place.updated_at),
+            PlaceSort::Name => PlaceSortKey::Name(self.place.name.clone()),
+            PlaceSort::Distance { .. } => {
+                PlaceSortKey::Distance(self.distance_m.unwrap_or(f64::INFINITY))
+            }
+        };
+        PlaceCursor {
+            key,
+            id: self.place.id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct NearbyPlaceRecord {
+    #[sqlx(flatten)]
+    pub place: PlaceRecord,
+    pub distance_m: f64,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct PlaceSearchRecord {
+    #[sqlx(flatten)]
+    pub place: PlaceRecord,
+    pub rank: f32,
+    /// Note excerpt with matches wrapped in `<mark>` tags, when the note matched.
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct TagRecord {
+    pub id: Uuid,
+    pub name: String,
+    pub place_count: i64,
+}
+
+/// Places of one map grid cell.
+#[derive(Debug, Clone, FromRow)]
+pub struct PlaceClusterRecord {
+    pub count: i64,
+    /// Mean position of the places in the cell.
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Most common categories in the cell, most frequent first.
+    pub top_categories: Vec<String>,
+    /// Set when the cell holds a single place.
+    pub place_id: Option<Uuid>,
+}
+
 #[derive(Debug, Clone, FromRow)]
 pub struct PlaceImageRecord {
     pub id: Uuid,
     pub place_id: Uuid,
+    /// Visit the image was taken on, if it is attached to one.
+    pub visit_id: Option<Uuid>,
     pub file_name: String,
     pub caption: Option<String>,
     pub created_at: DateTime<Utc>,
@@ -42,9 +201,20 @@ pub struct NewPlace<'a> {
     pub id: Uuid,
     pub user_id: Uuid,
     pub name: &'a str,
+    pub category_id: Uuid,
+    /// Name of the category `category_id` refers to.
     pub category: &'a str,
     pub location: &'a str,
     pub note: Option<&'a str>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub address: Option<&'a str>,
+    pub place_provider_id: Option<&'a str>,
+    pub status: PlaceStatus,
+    pub planned_on: Option<NaiveDate>,
+    pub priority: Option<i16>,
+    pub visibility: PlaceVisibility,
+    pub tags: &'a [String],
 }
 
 #[derive(Debug, Clone)]
@@ -58,9 +228,24 @@ pub struct NewPlaceImage<'a> {
 #[derive(Debug, Clone, Default)]
 pub struct UpdatePlace {
     pub name: Option<String>,
+    /// Set together with `category`, the name of the new category.
+    pub category_id: Option<Uuid>,
     pub category: Option<String>,
     pub location: Option<String>,
     pub note: Option<String>,
+    /// `Some(None)` clears the coordinates, the address, the provider id, the planned date and
+    /// the priority.
+    pub latitude: Option<Option<f64>>,
+    pub longitude: Option<Option<f64>>,
+    pub address: Option<Option<String>>,
+    pub place_provider_id: Option<Option<String>>,
+    pub status: Option<PlaceStatus>,
+    pub planned_on: Option<Option<NaiveDate>>,
+    pub priority: Option<Option<i16>>,
+    /// Only the owner may change it.
+    pub visibility: Option<PlaceVisibility>,
+    /// Replaces all tags of the place when set.
+    pub tags: Option<Vec<String>>,
 }
 
 impl PlaceRepository {
@@ -77,27 +262,55 @@ impl PlaceRepository {
 
         let place = sqlx::query_as::<_, PlaceRecord>(
             r#"
-            INSERT INTO places (id, user_id, name, category, location, note)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            RETURNING id, user_id, name, category, location, note, created_at, updated_at
+            INSERT INTO places (
+                id, user_id, name, category_id, category, location, note,
+                latitude, longitude, address, place_provider_id, status, planned_on, priority,
+                visibility
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+            RETURNING id, user_id, name, category_id, category, location, note, latitude, longitude,
+                   address, place_provider_id, status, planned_on, priority, visibility,
+                   created_at, updated_at
             "#,
         )
         .bind(payload.id)
         .bind(payload.user_id)
         .bind(payload.name)
+        .bind(payload.category_id)
         .bind(payload.category)
         .bind(payload.location)
         .bind(payload.note)
+        .bind(payload.latitude)
+        .bind(payload.longitude)
+        .bind(payload.address)
+        .bind(payload.place_provider_id)
+        .bind(payload.status)
+        .bind(payload.planned_on)
+        .bind(payload.priority)
+        .bind(payload.visibility)
         .fetch_one(tx.as_mut())
         .await?;
 
+        record_activity(
+            tx.as_mut(),
+            payload.user_id,
+            place.id,
+            ActivityKind::Added,
+            None,
+        )
+        .await?;
+
+        if !payload.tags.is_empty() {
+            replace_tags(tx.as_mut(), payload.user_id, payload.id, payload.tags).await?;
+        }
+
         let mut inserted_images = Vec::new();
         for img in images {
             let record = sqlx::query_as::<_, PlaceImageRecord>(
                 r#"
                 INSERT INTO place_images (id, place_id, file_name, caption)
                 VALUES ($1, $2, $3, $4)
-                RETURNING id, place_id, file_name, caption, created_at
+                RETURNING id, place_id, visit_id, file_name, caption, created_at
                 "#,
             )
             .bind(img.id)
@@ -114,26 +327,357 @@ impl PlaceRepository {
         Ok((place, inserted_images))
     }
 
-    pub async fn list_for_user(&self, user_id: Uuid) -> RepoResult<Vec<PlaceRecord>> {
-        let mut tx = self.pool.begin().await?;
+    /// Lists the user's places matching `query`. Pages are keyset based: the next page starts
+    /// after the cursor of the last record, so concurrent inserts never shift or repeat results.
+    pub async fn list_for_user(
+        &self,
+        user_id: Uuid,
+        query: &PlaceListQuery<'_>,
+    ) -> RepoResult<Vec<PlaceListRecord>> {
+        let mut builder = QueryBuilder::<Postgres>::new(
+            r#"
+            SELECT *
+            FROM (
+                SELECT id, user_id, name, category_id, category, location, note,
+                       latitude, longitude, address, place_provider_id, status, planned_on,
+                       priority, visibility, created_at, updated_at,
+            "#,
+        );
+        match query.sort {
+            PlaceSort::Distance {
+                latitude,
+                longitude,
+            } => push_distance_m(&mut builder, latitude, longitude),
+            _ => {
+                builder.push("NULL::DOUBLE PRECISION");
+            }
+        }
+        builder.push(" AS distance_m FROM places p WHERE user_id = ");
+        builder.push_bind(user_id);
+
+        if let Some(category_id) = query.category_id {
+            builder.push(" AND category_id = ");
+            builder.push_bind(category_id);
+        }
+        if let Some(category) = query.category {
+            builder.push(" AND lower(category) = lower(");
+            builder.push_bind(category);
+            builder.push(")");
+        }
+        for (column, comparison, value) in [
+            ("created_at", ">=", query.created_after),
+            ("created_at", "<", query.created_before),
+            ("updated_at", ">=", query.updated_after),
+            ("updated_at", "<", query.updated_before),
+        ] {
+            if let Some(value) = value {
+                builder.push(format_args!(" AND {column} {comparison} "));
+                builder.push_bind(value);
+            }
+        }
+        if let Some(has_images) = query.has_images {
+            builder.push(if has_images {
+                " AND EXISTS"
+            } else {
+                " AND NOT EXISTS"
+            });
+            builder.push(" (SELECT 1 FROM place_images i WHERE i.place_id = p.id)");
+        }
+        if !query.statuses.is_empty() {
+            builder.push(" AND status = ANY(");
+            builder.push_bind(query.statuses);
+            builder.push(")");
+        }
+        for (comparison, value) in [(">=", query.planned_after), ("<=", query.planned_before)] {
+            if let Some(value) = value {
+                builder.push(format_args!(" AND planned_on {comparison} "));
+                builder.push_bind(value);
+            }
+        }
+        if !query.tags.is_empty() {
+            let matching_tags = r#"
+                FROM place_tags pt
+                JOIN tags t ON t.id = pt.tag_id
+                WHERE pt.place_id = p.id AND lower(t.name) = ANY("#;
+            if query.all_tags {
+                builder.push(format_args!(" AND (SELECT count(*) {matching_tags}"));
+                builder.push_bind(query.tags);
+                builder.push(")) = ");
+                builder.push_bind(query.tags.len() as i64);
+            } else {
+                builder.push(format_args!(" AND EXISTS (SELECT 1 {matching_tags}"));
+                builder.push_bind(query.tags);
+                builder.push("))");
+            }
+        }
+        builder.push(") listed");
+
+        let sort_column = match query.sort {
+            PlaceSort::CreatedAt => "created_at",
+            PlaceSort::UpdatedAt => "updated_at",
+            PlaceSort::Name => "name",
+            PlaceSort::Distance { .. } => "COALESCE(distance_m, 'Infinity'::DOUBLE PRECISION)",
+        };
+        let direction = if query.descending { "DESC" } else { "ASC" };
+
+        if let Some(cursor) = &query.after {
+            let comparison = if query.descending { "<" } else { ">" };
+            builder.push(format_args!(" WHERE ({sort_column}, id) {comparison} ("));
+            match &cursor.key {
+                PlaceSortKey::Time(value) => builder.push_bind(*value),
+                PlaceSortKey::Name(value) => builder.push_bind(value.clone()),
+                PlaceSortKey::Distance(value) => builder.push_bind(*value),
+            };
+            builder.push(", ");
+            builder.push_bind(cursor.id);
+            builder.push(")");
+        }
+
+        builder.push(format_args!(
+            " ORDER BY {sort_column} {direction}, id {direction}"
+        ));
+        if let Some(limit) = query.limit {
+            builder.push(" LIMIT ");
+            builder.push_bind(limit);
+        }
+
+        let records = builder
+            .build_query_as::<PlaceListRecord>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(records)
+    }
+
+    /// Searches the user's places, best match first. `ts_query` is matched against the
+    /// `search_vector` column and may use prefix terms such as `blue:* & bot:*`. `text` is the raw
+    /// input, compared by trigram word similarity so misspelled words still find places.
+    /// The note is HTML-escaped before it is cut into a snippet, so `<mark>` is the only markup
+    /// in it.
+    pub async fn search_for_user(
+        &self,
+        user_id: Uuid,
+        ts_query: &str,
+        text: &str,
+        limit: i64,
+    ) -> RepoResult<Vec<PlaceSearchRecord>> {
+        let records = sqlx::query_as::<_, PlaceSearchRecord>(
+            r#"
+            SELECT id, user_id, name, category_id, category, location, note, latitude, longitude,
+                   address, place_provider_id, status, planned_on, priority, visibility,
+                   created_at, updated_at,
+                   ts_rank(search_vector, query) + word_similarity($3, search_text) AS rank,
+                   CASE WHEN to_tsvector('simple', coalesce(note, '')) @@ query THEN
+                       ts_headline('simple',
+                                   replace(replace(replace(replace(note, '&', '&amp;'),
+                                       '<', '&lt;'), '>', '&gt;'), '"', '&quot;'),
+                                   query,
+                                   'StartSel=<mark>, StopSel=</mark>, MaxWords=20, MinWords=8')
+                   END AS snippet
+            FROM places, to_tsquery('simple', $2) AS query
+            WHERE user_id = $1
+              AND (search_vector @@ query OR $3 <% search_text)
+            ORDER BY rank DESC, id
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(ts_query)
+        .bind(text)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Places of the user within `radius_m` metres of the given point, nearest first. Places
+    /// without coordinates are skipped.
+    pub async fn list_nearby_for_user(
+        &self,
+        user_id: Uuid,
+        latitude: f64,
+        longitude: f64,
+        radius_m: f64,
+    ) -> RepoResult<Vec<NearbyPlaceRecord>> {
+        let bbox = BoundingBox::around(latitude, longitude, radius_m);
+        let [(west_a, east_a), (west_b, east_b)] = bbox.longitude_ranges;
+
+        // The bounding box narrows the scan through places_user_coordinates_idx; the haversine
+        // distance then drops the corners of the box.
+        let records = sqlx::query_as::<_, NearbyPlaceRecord>(
+            r#"
+            SELECT *
+            FROM (
+                SELECT id, user_id, name, category_id, category, location, note,
+                       latitude, longitude, address, place_provider_id, status, planned_on,
+                       priority, visibility, created_at, updated_at,
+                       2 * $11 * asin(least(1, sqrt(
+                           power(sin(radians(latitude - $2) / 2), 2)
+                           + cos(radians($2)) * cos(radians(latitude))
+                             * power(sin(radians(longitude - $3) / 2), 2)
+                       ))) AS distance_m
+                FROM places
+                WHERE user_id = $1
+                  AND latitude BETWEEN $4 AND $5
+                  AND (longitude BETWEEN $6 AND $7 OR longitude BETWEEN $8 AND $9)
+            ) nearby
+            WHERE distance_m <= $10
+            ORDER BY distance_m, id
+            "#,
+        )
+        .bind(user_id)
+        .bind(latitude)
+        .bind(longitude)
+        .bind(bbox.min_latitude)
+        .bind(bbox.max_latitude)
+        .bind(west_a)
+        .bind(east_a)
+        .bind(west_b)
+        .bind(east_b)
+        .bind(radius_m)
+        .bind(EARTH_RADIUS_M)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Places of the user inside `bbox`, at most `limit` of them.
+    pub async fn list_in_bounds_for_user(
+        &self,
+        user_id: Uuid,
+        bbox: &BoundingBox,
+        limit: i64,
+    ) -> RepoResult<Vec<PlaceRecord>> {
+        let [(west_a, east_a), (west_b, east_b)] = bbox.longitude_ranges;
 
         let records = sqlx::query_as::<_, PlaceRecord>(
             r#"
-            SELECT id, user_id, name, category, location, note, created_at, updated_at
+            SELECT id, user_id, name, category_id, category, location, note, latitude, longitude,
+                   address, place_provider_id, status, planned_on, priority, visibility,
+                   created_at, updated_at
             FROM places
             WHERE user_id = $1
-            ORDER BY created_at DESC
+              AND latitude BETWEEN $2 AND $3
+              AND (longitude BETWEEN $4 AND $5 OR longitude BETWEEN $6 AND $7)
+            ORDER BY latitude DESC, id
+            LIMIT $8
             "#,
         )
         .bind(user_id)
-        .fetch_all(tx.as_mut())
+        .bind(bbox.min_latitude)
+        .bind(bbox.max_latitude)
+        .bind(west_a)
+        .bind(east_a)
+        .bind(west_b)
+        .bind(east_b)
+        .bind(limit)
+        .fetch_all(&self.pool)
         .await?;
 
-        tx.commit().await?;
+        Ok(records)
+    }
+
+    /// Groups the user's places inside `bbox` into square grid cells of `cell_degrees`. Cells are
+    /// aligned to the 0/0 origin so they stay put while the map is panned.
+    pub async fn cluster_in_bounds_for_user(
+        &self,
+        user_id: Uuid,
+        bbox: &BoundingBox,
+        cell_degrees: f64,
+        top_categories: i32,
+    ) -> RepoResult<Vec<PlaceClusterRecord>> {
+        let [(west_a, east_a), (west_b, east_b)] = bbox.longitude_ranges;
+
+        // Aggregating per (cell, category) first gives the category counts and the cell totals in
+        // a single scan of the index.
+        let records = sqlx::query_as::<_, PlaceClusterRecord>(
+            r#"
+            WITH per_category AS (
+                SELECT floor(latitude / $8) AS cell_y,
+                       floor(longitude / $8) AS cell_x,
+                       category,
+                       COUNT(*) AS places,
+                       SUM(latitude) AS latitude_sum,
+                       SUM(longitude) AS longitude_sum,
+                       (array_agg(id))[1] AS sample_id
+                FROM places
+                WHERE user_id = $1
+                  AND latitude BETWEEN $2 AND $3
+                  AND (longitude BETWEEN $4 AND $5 OR longitude BETWEEN $6 AND $7)
+                GROUP BY cell_y, cell_x, category
+            )
+            SELECT SUM(places)::BIGINT AS count,
+                   SUM(latitude_sum) / SUM(places) AS latitude,
+                   SUM(longitude_sum) / SUM(places) AS longitude,
+                   (array_agg(category ORDER BY places DESC, category))[1:$9] AS top_categories,
+                   CASE WHEN SUM(places) = 1 THEN (array_agg(sample_id))[1] END AS place_id
+            FROM per_category
+            GROUP BY cell_y, cell_x
+            ORDER BY count DESC, cell_y, cell_x
+            "#,
+        )
+        .bind(user_id)
+        .bind(bbox.min_latitude)
+        .bind(bbox.max_latitude)
+        .bind(west_a)
+        .bind(east_a)
+        .bind(west_b)
+        .bind(east_b)
+        .bind(cell_degrees)
+        .bind(top_categories)
+        .fetch_all(&self.pool)
+        .await?;
 
         Ok(records)
     }
 
+    /// Finds a place the user can see through its visibility or otherwise, see `can_view_place`,
+    /// together with its images.
+    pub async fn find_visible_for_user(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+    ) -> RepoResult<Option<(PlaceRecord, Vec<PlaceImageRecord>)>> {
+        let mut conn = self.pool.acquire().await?;
+        if !can_view_place(&mut conn, user_id, place_id).await? {
+            return Ok(None);
+        }
+
+        let Some(record) = sqlx::query_as::<_, PlaceRecord>(
+            r#"
+            SELECT p.id, p.user_id, p.name, p.category_id, p.category, p.location, p.note,
+                   p.latitude, p.longitude, p.address, p.place_provider_id, p.status,
+                   p.planned_on, p.priority, p.visibility, p.created_at, p.updated_at
+            FROM places p
+            WHERE p.id = $1
+            "#,
+        )
+        .bind(place_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let images = sqlx::query_as::<_, PlaceImageRecord>(
+            r#"
+            SELECT id, place_id, visit_id, file_name, caption, created_at
+            FROM place_images
+            WHERE place_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(place_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(Some((record, images)))
+    }
+
+    /// Finds a place the user can see: one of their own, or one in a collection they are a
+    /// member of.
     pub async fn find_for_user(
         &self,
         user_id: Uuid,
@@ -143,9 +687,12 @@ impl PlaceRepository {
 
         let record = sqlx::query_as::<_, PlaceRecord>(
             r#"
-            SELECT id, user_id, name, category, location, note, created_at, updated_at
-            FROM places
-            WHERE id = $1 AND user_id = $2
+            SELECT p.id, p.user_id, p.name, p.category_id, p.category, p.location, p.note,
+                   p.latitude, p.longitude, p.address, p.place_provider_id, p.status,
+                   p.planned_on, p.priority, p.visibility, p.created_at, p.updated_at
+            FROM places p
+            WHERE p.id = $1
+              AND EXISTS (SELECT 1 FROM place_access a WHERE a.place_id = p.id AND a.user_id = $2)
             "#,
         )
         .bind(place_id)
@@ -158,6 +705,36 @@ impl PlaceRepository {
         Ok(record)
     }
 
+    /// Finds a place the user may change: one of their own, or one in a collection they are an
+    /// editor or owner of.
+    pub async fn find_editable_for_user(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+    ) -> RepoResult<Option<PlaceRecord>> {
+        let record = sqlx::query_as::<_, PlaceRecord>(
+            r#"
+            SELECT p.id, p.user_id, p.name, p.category_id, p.category, p.location, p.note,
+                   p.latitude, p.longitude, p.address, p.place_provider_id, p.status,
+                   p.planned_on, p.priority, p.visibility, p.created_at, p.updated_at
+            FROM places p
+            WHERE p.id = $1
+              AND EXISTS (
+                  SELECT 1 FROM place_access a
+                  WHERE a.place_id = p.id AND a.user_id = $2 AND a.role IN ('editor', 'owner')
+              )
+            "#,
+        )
+        .bind(place_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Updates a place the user may change, see `find_editable_for_user`. Tags stay those of the
+    /// place's owner, whoever makes the change.
     pub async fn update_place_with_images(
         &self,
         user_id: Uuid,
@@ -168,34 +745,89 @@ impl PlaceRepository {
     ) -> RepoResult<(PlaceRecord, Vec<PlaceImageRecord>, Vec<PlaceImageRecord>)> {
         let mut tx = self.pool.begin().await?;
 
+        let previous_visibility = sqlx::query_scalar::<_, PlaceVisibility>(
+            "SELECT visibility FROM places WHERE id = $1 FOR UPDATE",
+        )
+        .bind(place_id)
+        .fetch_optional(tx.as_mut())
+        .await?;
+
         let place = sqlx::query_as::<_, PlaceRecord>(
             r#"
-            UPDATE places
+            UPDATE places p
             SET name = COALESCE($3, name),
-                category = COALESCE($4, category),
-                location = COALESCE($5, location),
-                note = COALESCE($6, note),
+                category_id = COALESCE($4, category_id),
+                category = COALESCE($5, category),
+                location = COALESCE($6, location),
+                note = COALESCE($7, note),
+                latitude = CASE WHEN $8 THEN $9 ELSE latitude END,
+                longitude = CASE WHEN $10 THEN $11 ELSE longitude END,
+                address = CASE WHEN $12 THEN $13 ELSE address END,
+                place_provider_id = CASE WHEN $14 THEN $15 ELSE place_provider_id END,
+                status = COALESCE($16, status),
+                planned_on = CASE WHEN $17 THEN $18 ELSE planned_on END,
+                priority = CASE WHEN $19 THEN $20 ELSE priority END,
+                visibility = COALESCE($21, visibility),
                 updated_at = NOW()
-            WHERE id = $1 AND user_id = $2
-            RETURNING id, user_id, name, category, location, note, created_at, updated_at
+            WHERE p.id = $1
+              AND EXISTS (
+                  SELECT 1 FROM place_access a
+                  WHERE a.place_id = p.id AND a.user_id = $2 AND a.role IN ('editor', 'owner')
+              )
+            RETURNING id, user_id, name, category_id, category, location, note, latitude, longitude,
+                   address, place_provider_id, status, planned_on, priority, visibility,
+                   created_at, updated_at
             "#,
         )
         .bind(place_id)
         .bind(user_id)
         .bind(update.name.as_deref())
+        .bind(update.category_id)
         .bind(update.category.as_deref())
         .bind(update.location.as_deref())
         .bind(update.note.as_deref())
+        .bind(update.latitude.is_some())
+        .bind(update.latitude.flatten())
+        .bind(update.longitude.is_some())
+        .bind(update.longitude.flatten())
+        .bind(update.address.is_some())
+        .bind(update.address.as_ref().and_then(Option::as_deref))
+        .bind(update.place_provider_id.is_some())
+        .bind(update.place_provider_id.as_ref().and_then(Option::as_deref))
+        .bind(update.status)
+        .bind(update.planned_on.is_some())
+        .bind(update.planned_on.flatten())
+        .bind(update.priority.is_some())
+        .bind(update.priority.flatten())
+        .bind(update.visibility)
         .fetch_one(tx.as_mut())
         .await?;
 
+        // Opening a place up to more people is news for them, narrowing it is not.
+        if previous_visibility.is_some_and(|previous| previous != place.visibility)
+            && place.visibility != PlaceVisibility::Private
+        {
+            record_activity(
+                tx.as_mut(),
+                place.user_id,
+                place.id,
+                ActivityKind::Shared,
+                None,
+            )
+            .await?;
+        }
+
+        if let Some(tags) = &update.tags {
+            replace_tags(tx.as_mut(), place.user_id, place_id, tags).await?;
+        }
+
         let mut deleted_images = Vec::new();
         if !delete_image_ids.is_empty() {
             deleted_images = sqlx::query_as::<_, PlaceImageRecord>(
                 r#"
                 DELETE FROM place_images
                 WHERE id = ANY($1) AND place_id = $2
-                RETURNING id, place_id, file_name, caption, created_at
+                RETURNING id, place_id, visit_id, file_name, caption, created_at
                 "#,
             )
             .bind(delete_image_ids)
@@ -210,7 +842,7 @@ impl PlaceRepository {
                 r#"
                 INSERT INTO place_images (id, place_id, file_name, caption)
                 VALUES ($1, $2, $3, $4)
-                RETURNING id, place_id, file_name, caption, created_at
+                RETURNING id, place_id, visit_id, file_name, caption, created_at
                 "#,
             )
             .bind(img.id)
@@ -236,10 +868,12 @@ impl PlaceRepository {
 
         let records = sqlx::query_as::<_, PlaceImageRecord>(
             r#"
-            SELECT pi.id, pi.place_id, pi.file_name, pi.caption, pi.created_at
+            SELECT pi.id, pi.place_id, pi.visit_id, pi.file_name, pi.caption, pi.created_at
             FROM place_images pi
-            JOIN places p ON p.id = pi.place_id
-            WHERE pi.place_id = $1 AND p.user_id = $2
+            WHERE pi.place_id = $1
+              AND EXISTS (
+                  SELECT 1 FROM place_access a WHERE a.place_id = pi.place_id AND a.user_id = $2
+              )
             ORDER BY pi.created_at DESC
             "#,
         )
@@ -253,29 +887,123 @@ impl PlaceRepository {
         Ok(records)
     }
 
+    /// Images of several places in one query, grouped by place id, newest first like
+    /// `list_images_for_place`. Places without images have no entry.
+    pub async fn list_images_for_places(
+        &self,
+        user_id: Uuid,
+        place_ids: &[Uuid],
+    ) -> RepoResult<HashMap<Uuid, Vec<PlaceImageRecord>>> {
+        if place_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let records = sqlx::query_as::<_, PlaceImageRecord>(
+            r#"
+            SELECT pi.id, pi.place_id, pi.visit_id, pi.file_name, pi.caption, pi.created_at
+            FROM place_images pi
+            WHERE pi.place_id = ANY($1)
+              AND EXISTS (
+                  SELECT 1 FROM place_access a WHERE a.place_id = pi.place_id AND a.user_id = $2
+              )
+            ORDER BY pi.created_at DESC
+            "#,
+        )
+        .bind(place_ids)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut images: HashMap<Uuid, Vec<PlaceImageRecord>> = HashMap::new();
+        for record in records {
+            images.entry(record.place_id).or_default().push(record);
+        }
+
+        Ok(images)
+    }
+
+    /// Tag names of several places in one query, grouped by place id and sorted by name. Places
+    /// without tags have no entry.
+    pub async fn list_tags_for_places(
+        &self,
+        user_id: Uuid,
+        place_ids: &[Uuid],
+    ) -> RepoResult<HashMap<Uuid, Vec<String>>> {
+        if place_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let rows = sqlx::query_as::<_, (Uuid, String)>(
+            r#"
+            SELECT pt.place_id, t.name
+            FROM place_tags pt
+            JOIN tags t ON t.id = pt.tag_id
+            WHERE pt.place_id = ANY($1)
+              AND EXISTS (
+                  SELECT 1 FROM place_access a WHERE a.place_id = pt.place_id AND a.user_id = $2
+              )
+            ORDER BY lower(t.name)
+            "#,
+        )
+        .bind(place_ids)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for (place_id, name) in rows {
+            tags.entry(place_id).or_default().push(name);
+        }
+
+        Ok(tags)
+    }
+
+    /// Tags used on at least one of the user's places, most used first.
+    pub async fn list_tags_for_user(&self, user_id: Uuid) -> RepoResult<Vec<TagRecord>> {
+        let records = sqlx::query_as::<_, TagRecord>(
+            r#"
+            SELECT t.id, t.name, count(*) AS place_count
+            FROM tags t
+            JOIN place_tags pt ON pt.tag_id = t.id
+            WHERE t.user_id = $1
+            GROUP BY t.id
+            ORDER BY place_count DESC, lower(t.name)
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Finds an image of a place the user can see, see `can_view_place`.
     pub async fn find_image_for_user(
         &self,
         user_id: Uuid,
         image_id: Uuid,
     ) -> RepoResult<Option<PlaceImageRecord>> {
-        let mut tx = self.pool.begin().await?;
+        let mut conn = self.pool.acquire().await?;
 
         let record = sqlx::query_as::<_, PlaceImageRecord>(
             r#"
-            SELECT pi.id, pi.place_id, pi.file_name, pi.caption, pi.created_at
-            FROM place_images pi
-            JOIN places p ON p.id = pi.place_id
-            WHERE pi.id = $1 AND p.user_id = $2
+            SELECT id, place_id, visit_id, file_name, caption, created_at
+            FROM place_images
+            WHERE id = $1
             "#,
         )
         .bind(image_id)
-        .bind(user_id)
-        .fetch_optional(tx.as_mut())
+        .fetch_optional(&mut *conn)
         .await?;
 
-        tx.commit().await?;
+        let Some(record) = record else {
+            return Ok(None);
+        };
+        if !can_view_place(&mut conn, user_id, record.place_id).await? {
+            return Ok(None);
+        }
 
-        Ok(record)
+        Ok(Some(record))
     }
 
     pub async fn delete_place_for_user(
@@ -287,7 +1015,9 @@ impl PlaceRepository {
 
         let place = sqlx::query_as::<_, PlaceRecord>(
             r#"
-            SELECT id, user_id, name, category, location, note, created_at, updated_at
+            SELECT id, user_id, name, category_id, category, location, note, latitude, longitude,
+                   address, place_provider_id, status, planned_on, priority, visibility,
+                   created_at, updated_at
             FROM places
             WHERE id = $1 AND user_id = $2
             "#,
@@ -304,7 +1034,7 @@ impl PlaceRepository {
 
         let images = sqlx::query_as::<_, PlaceImageRecord>(
             r#"
-            SELECT id, place_id, file_name, caption, created_at
+            SELECT id, place_id, visit_id, file_name, caption, created_at
             FROM place_images
             WHERE place_id = $1
             "#,
@@ -313,6 +1043,8 @@ impl PlaceRepository {
         .fetch_all(tx.as_mut())
         .await?;
 
+        remove_places_from_collections(tx.as_mut(), &[place_id]).await?;
+
         sqlx::query(
             r#"
             DELETE FROM places
@@ -329,3 +1061,65 @@ impl PlaceRepository {
         Ok(Some((place, images)))
     }
 }
+
+/// Great-circle distance in metres between a place and the given point, `NULL` for places
+/// without coordinates.
+fn push_distance_m(builder: &mut QueryBuilder<'_, Postgres>, latitude: f64, longitude: f64) {
+    builder.push("2 * ");
+    builder.push_bind(EARTH_RADIUS_M);
+    builder.push(" * asin(least(1, sqrt(power(sin(radians(latitude - ");
+    builder.push_bind(latitude);
+    builder.push(") / 2), 2) + cos(radians(");
+    builder.push_bind(latitude);
+    builder.push(")) * cos(radians(latitude)) * power(sin(radians(longitude - ");
+    builder.push_bind(longitude);
+    builder.push(") / 2), 2))))");
+}
+
+/// Sets the tags of a place to `tags`. Tags are shared by all places of a user and matched
+/// case-insensitively, so the first spelling used for a tag is the one that is kept.
+async fn replace_tags(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    place_id: Uuid,
+    tags: &[String],
+) -> Result<(), SqlxError> {
+    sqlx::query("DELETE FROM place_tags WHERE place_id = $1")
+        .bind(place_id)
+        .execute(&mut *conn)
+        .await?;
+
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO tags (id, user_id, name)
+        SELECT gen_random_uuid(), $1, name
+        FROM unnest($2::TEXT[]) AS name
+        ON CONFLICT (user_id, lower(name)) DO NOTHING
+        "#,
+    )
+    .bind(user_id)
+    .bind(tags)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO place_tags (place_id, tag_id)
+        SELECT $1, t.id
+        FROM tags t
+        WHERE t.user_id = $2
+          AND lower(t.name) IN (SELECT lower(name) FROM unnest($3::TEXT[]) AS name)
+        "#,
+    )
+    .bind(place_id)
+    .bind(user_id)
+    .bind(tags)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Error as SqlxError, FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::repository::place::PlaceImageRecord;
+
+#[derive(Debug, Error)]
+pub enum ShareRepositoryError {
+    #[error("database error: {0}")]
+    Database(#[from] SqlxError),
+}
+
+type RepoResult<T> = Result<T, ShareRepositoryError>;
+
+#[derive(Clone)]
+pub struct ShareRepository {
+    pool: PgPool,
+}
+
+/// A share link. Exactly one of `place_id` and `collection_id` is set.
+#[derive(Debug, Clone, FromRow)]
+pub struct ShareLinkRecord {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub place_id: Option<Uuid>,
+    pub collection_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ShareTarget {
+    Place(Uuid),
+    Collection(Uuid),
+}
+
+#[derive(Debug, Clone)]
+pub struct NewShareLink<'a> {
+    pub user_id: Uuid,
+    pub target: ShareTarget,
+    pub token_hash: &'a str,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ShareRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates a link. Returns `None` unless the user owns the place or is an owner of the
+    /// collection.
+    pub async fn create(&self, payload: NewShareLink<'_>) -> RepoResult<Option<ShareLinkRecord>> {
+        let (place_id, collection_id) = match payload.target {
+            ShareTarget::Place(place_id) => (Some(place_id), None),
+            ShareTarget::Collection(collection_id) => (None, Some(collection_id)),
+        };
+
+        let record = sqlx::query_as::<_, ShareLinkRecord>(
+            r#"
+            INSERT INTO share_links (id, user_id, place_id, collection_id, token_hash, expires_at)
+            SELECT $1, $2, $3, $4, $5, $6
+            WHERE EXISTS (SELECT 1 FROM places WHERE id = $3 AND user_id = $2)
+               OR EXISTS (
+                   SELECT 1 FROM collection_members
+                   WHERE collection_id = $4 AND user_id = $2 AND role = 'owner'
+               )
+            RETURNING id, user_id, place_id, collection_id, created_at, expires_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(payload.user_id)
+        .bind(place_id)
+        .bind(collection_id)
+        .bind(payload.token_hash)
+        .bind(payload.expires_at)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Lists links that have not been revoked, including expired ones so the user can see them.
+    pub async fn list_for_user(&self, user_id: Uuid) -> RepoResult<Vec<ShareLinkRecord>> {
+        let records = sqlx::query_as::<_, ShareLinkRecord>(
+            r#"
+            SELECT id, user_id, place_id, collection_id, created_at, expires_at
+            FROM share_links
+            WHERE user_id = $1 AND revoked_at IS NULL
+            ORDER BY created_at DESC, id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Looks up a link that is neither revoked nor expired by the hash of its token.
+    pub async fn find_active(&self, token_hash: &str) -> RepoResult<Option<ShareLinkRecord>> {
+        let record = sqlx::query_as::<_, ShareLinkRecord>(
+            r#"
+            SELECT id, user_id, place_id, collection_id, created_at, expires_at
+            FROM share_links
+            WHERE token_hash = $1
+              AND revoked_at IS NULL
+              AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Returns `false` when the user has no active link with that id.
+    pub async fn revoke(&self, user_id: Uuid, share_id: Uuid) -> RepoResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE share_links
+            SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(share_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Finds an image of the shared place, or of a place currently in the shared collection,
+    /// as long as the creator of the link can still see what it shares.
+    pub async fn find_image(
+        &self,
+        share: &ShareLinkRecord,
+        image_id: Uuid,
+    ) -> RepoResult<Option<PlaceImageRecord>> {
+        let record = sqlx::query_as::<_, PlaceImageRecord>(
+            r#"
+            SELECT pi.id, pi.place_id, pi.visit_id, pi.file_name, pi.caption, pi.created_at
+            FROM place_images pi
+            WHERE pi.id = $1
+              AND ((pi.place_id = $2
+                    AND EXISTS (
+                        SELECT 1 FROM place_access a WHERE a.place_id = $2 AND a.user_id = $4
+                    ))
+                   OR (pi.place_id IN (
+                           SELECT place_id FROM collection_places WHERE collection_id = $3
+                       )
+                       AND EXISTS (
+                           SELECT 1 FROM collection_members m
+                           WHERE m.collection_id = $3 AND m.user_id = $4
+                       )))
+            "#,
+        )
+        .bind(image_id)
+        .bind(share.place_id)
+        .bind(share.collection_id)
+        .bind(share.user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+}
@@ -0,0 +1,400 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Error as SqlxError, FromRow, PgConnection, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum SocialRepositoryError {
+    #[error("database error: {0}")]
+    Database(#[from] SqlxError),
+}
+
+type RepoResult<T> = Result<T, SocialRepositoryError>;
+
+#[derive(Clone)]
+pub struct SocialRepository {
+    pool: PgPool,
+}
+
+text_enum! {
+    /// What an activity event reports. Stored by name in `activity_events.kind`.
+    pub enum ActivityKind {
+        Added => "place_added",
+        Visited => "place_visited",
+        /// The owner made the place visible to friends or to everyone.
+        Shared => "place_shared",
+    }
+}
+
+/// Another user on one of the user's follow or block lists.
+#[derive(Debug, Clone, FromRow)]
+pub struct ConnectionRecord {
+    pub user_id: Uuid,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    /// When the follow or block was made.
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FollowOutcome {
+    Followed,
+    UnknownUser,
+    /// One of the two users blocked the other.
+    Blocked,
+}
+
+/// An event of the feed together with what the feed shows of its user, place and visit.
+#[derive(Debug, Clone, FromRow)]
+pub struct FeedEventRecord {
+    pub id: Uuid,
+    pub kind: ActivityKind,
+    pub created_at: DateTime<Utc>,
+    pub user_id: Uuid,
+    pub user_name: Option<String>,
+    pub user_avatar_url: Option<String>,
+    pub place_id: Uuid,
+    pub place_name: String,
+    pub category: String,
+    pub location: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub address: Option<String>,
+    /// Set for `Visited` events.
+    pub visited_on: Option<NaiveDate>,
+    pub rating: Option<i16>,
+}
+
+impl FeedEventRecord {
+    pub fn cursor(&self) -> FeedCursor {
+        FeedCursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+/// Position in the feed. The next page holds the events before it.
+#[derive(Debug, Clone, Copy)]
+pub struct FeedCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl SocialRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Following someone twice keeps the first follow.
+    pub async fn follow(&self, follower_id: Uuid, followee_id: Uuid) -> RepoResult<FollowOutcome> {
+        let mut tx = self.pool.begin().await?;
+
+        let exists =
+            sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+                .bind(followee_id)
+                .fetch_one(tx.as_mut())
+                .await?;
+        if !exists {
+            return Ok(FollowOutcome::UnknownUser);
+        }
+
+        if is_blocked(tx.as_mut(), follower_id, followee_id).await? {
+            return Ok(FollowOutcome::Blocked);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO follows (follower_id, followee_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(follower_id)
+        .bind(followee_id)
+        .execute(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(FollowOutcome::Followed)
+    }
+
+    /// Returns `false` when the user did not follow them.
+    pub async fn unfollow(&self, follower_id: Uuid, followee_id: Uuid) -> RepoResult<bool> {
+        let result = sqlx::query("DELETE FROM follows WHERE follower_id = $1 AND followee_id = $2")
+            .bind(follower_id)
+            .bind(followee_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_following(&self, user_id: Uuid) -> RepoResult<Vec<ConnectionRecord>> {
+        let records = sqlx::query_as::<_, ConnectionRecord>(
+            r#"
+            SELECT u.id AS user_id, u.name, u.avatar_url, f.created_at
+            FROM follows f
+            JOIN users u ON u.id = f.followee_id
+            WHERE f.follower_id = $1
+            ORDER BY f.created_at DESC, u.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    pub async fn list_followers(&self, user_id: Uuid) -> RepoResult<Vec<ConnectionRecord>> {
+        let records = sqlx::query_as::<_, ConnectionRecord>(
+            r#"
+            SELECT u.id AS user_id, u.name, u.avatar_url, f.created_at
+            FROM follows f
+            JOIN users u ON u.id = f.follower_id
+            WHERE f.followee_id = $1
+            ORDER BY f.created_at DESC, u.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Blocks a user and ends the follows between the two. Returns `false` when there is no such
+    /// user.
+    pub async fn block(&self, blocker_id: Uuid, blocked_id: Uuid) -> RepoResult<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO user_blocks (blocker_id, blocked_id)
+            SELECT $1, id FROM users WHERE id = $2
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(blocker_id)
+        .bind(blocked_id)
+        .execute(tx.as_mut())
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            let exists =
+                sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+                    .bind(blocked_id)
+                    .fetch_one(tx.as_mut())
+                    .await?;
+            if !exists {
+                return Ok(false);
+            }
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM follows
+            WHERE (follower_id = $1 AND followee_id = $2)
+               OR (follower_id = $2 AND followee_id = $1)
+            "#,
+        )
+        .bind(blocker_id)
+        .bind(blocked_id)
+        .execute(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Returns `false` when the user had not blocked them. Follows ended by the block stay ended.
+    pub async fn unblock(&self, blocker_id: Uuid, blocked_id: Uuid) -> RepoResult<bool> {
+        let result =
+            sqlx::query("DELETE FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2")
+                .bind(blocker_id)
+                .bind(blocked_id)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_blocks(&self, user_id: Uuid) -> RepoResult<Vec<ConnectionRecord>> {
+        let records = sqlx::query_as::<_, ConnectionRecord>(
+            r#"
+            SELECT u.id AS user_id, u.name, u.avatar_url, b.created_at
+            FROM user_blocks b
+            JOIN users u ON u.id = b.blocked_id
+            WHERE b.blocker_id = $1
+            ORDER BY b.created_at DESC, u.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// The file name of another user's uploaded avatar. `None` when they have none, or when
+    /// either user blocked the other.
+    pub async fn find_avatar_file_name(
+        &self,
+        viewer_id: Uuid,
+        user_id: Uuid,
+    ) -> RepoResult<Option<String>> {
+        let file_name = sqlx::query_scalar::<_, Option<String>>(
+            r#"
+            SELECT avatar_file_name
+            FROM users
+            WHERE id = $2
+              AND NOT EXISTS (
+                  SELECT 1 FROM user_blocks
+                  WHERE (blocker_id = $1 AND blocked_id = $2)
+                     OR (blocker_id = $2 AND blocked_id = $1)
+              )
+            "#,
+        )
+        .bind(viewer_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(file_name.flatten())
+    }
+
+    /// Lists events of the users the user follows, newest first, on places they can currently
+    /// see: public places, and friends-only places of users who follow the user back. Users
+    /// blocked in either direction are left out.
+    pub async fn feed(
+        &self,
+        user_id: Uuid,
+        before: Option<FeedCursor>,
+        limit: i64,
+    ) -> RepoResult<Vec<FeedEventRecord>> {
+        let records = sqlx::query_as::<_, FeedEventRecord>(
+            r#"
+            SELECT e.id, e.kind, e.created_at,
+                   u.id AS user_id, u.name AS user_name, u.avatar_url AS user_avatar_url,
+                   p.id AS place_id, p.name AS place_name, p.category, p.location,
+                   p.latitude, p.longitude, p.address,
+                   v.visited_on, v.rating
+            FROM follows f
+            JOIN activity_events e ON e.user_id = f.followee_id
+            JOIN users u ON u.id = e.user_id
+            JOIN places p ON p.id = e.place_id
+            LEFT JOIN place_visits v ON v.id = e.visit_id
+            WHERE f.follower_id = $1
+              AND (p.visibility = 'public'
+                   OR (p.visibility = 'friends'
+                       AND EXISTS (
+                           SELECT 1 FROM follows back
+                           WHERE back.follower_id = e.user_id AND back.followee_id = $1
+                       )))
+              AND NOT EXISTS (
+                  SELECT 1 FROM user_blocks b
+                  WHERE (b.blocker_id = $1 AND b.blocked_id = e.user_id)
+                     OR (b.blocker_id = e.user_id AND b.blocked_id = $1)
+              )
+              AND ($2::TIMESTAMPTZ IS NULL OR (e.created_at, e.id) < ($2, $3))
+            ORDER BY e.created_at DESC, e.id DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(before.map(|cursor| cursor.created_at))
+        .bind(before.map(|cursor| cursor.id))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+}
+
+/// Records an event of the user's on the place, as part of the change that caused it.
+pub async fn record_activity(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    place_id: Uuid,
+    kind: ActivityKind,
+    visit_id: Option<Uuid>,
+) -> Result<(), SqlxError> {
+    sqlx::query(
+        r#"
+        INSERT INTO activity_events (id, user_id, place_id, visit_id, kind)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(place_id)
+    .bind(visit_id)
+    .bind(kind)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Whether the user can see the place: through `place_access`, or as allowed by its visibility
+/// when neither the user nor the owner blocked the other.
+pub async fn can_view_place(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    place_id: Uuid,
+) -> Result<bool, SqlxError> {
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM places p
+            WHERE p.id = $1
+              AND (EXISTS (
+                       SELECT 1 FROM place_access a WHERE a.place_id = p.id AND a.user_id = $2
+                   )
+                   OR ((p.visibility = 'public'
+                        OR (p.visibility = 'friends'
+                            AND EXISTS (
+                                SELECT 1 FROM follows
+                                WHERE follower_id = $2 AND followee_id = p.user_id
+                            )
+                            AND EXISTS (
+                                SELECT 1 FROM follows
+                                WHERE follower_id = p.user_id AND followee_id = $2
+                            )))
+                       AND NOT EXISTS (
+                           SELECT 1 FROM user_blocks b
+                           WHERE (b.blocker_id = p.user_id AND b.blocked_id = $2)
+                              OR (b.blocker_id = $2 AND b.blocked_id = p.user_id)
+                       )))
+        )
+        "#,
+    )
+    .bind(place_id)
+    .bind(user_id)
+    .fetch_one(conn)
+    .await
+}
+
+async fn is_blocked(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    other_id: Uuid,
+) -> Result<bool, SqlxError> {
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM user_blocks
+            WHERE (blocker_id = $1 AND blocked_id = $2)
+               OR (blocker_id = $2 AND blocked_id = $1)
+        )
+        "#,
+    )
+    .bind(user_id)
+    .bind(other_id)
+    .fetch_one(conn)
+    .await
+}
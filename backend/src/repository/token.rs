@@ -0,0 +1,547 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Error as SqlxError, FromRow, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum TokenRepositoryError {
+    #[error("database error: {0}")]
+    Database(#[from] SqlxError),
+}
+
+type RepoResult<T> = Result<T, TokenRepositoryError>;
+
+#[derive(Clone)]
+pub struct TokenRepository {
+    pool: PgPool,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct RefreshTokenRecord {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub family_id: Uuid,
+    pub session_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewRefreshToken<'a> {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub family_id: Uuid,
+    pub session_id: Option<Uuid>,
+    pub token_hash: &'a str,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct SessionRecord {
+    pub id: Uuid,
+    pub provider: String,
+    pub device_label: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewSession<'a> {
+    pub user_id: Uuid,
+    pub provider: &'a str,
+    pub device_label: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct PersonalTokenRecord {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewPersonalToken<'a> {
+    pub user_id: Uuid,
+    pub name: &'a str,
+    pub token_hash: &'a str,
+    pub scopes: &'a [String],
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of presenting a refresh token for rotation.
+#[derive(Debug)]
+pub enum RefreshTokenRotation {
+    /// The token was valid; it is now spent and `RefreshTokenRecord` is its replacement.
+    Rotated(RefreshTokenRecord),
+    /// The token had already been rotated, so the whole family has been revoked.
+    Reused {
+        user_id: Uuid,
+        family_id: Uuid,
+    },
+    Expired,
+    Revoked,
+    NotFound,
+}
+
+impl TokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert_refresh_token(
+        &self,
+        payload: NewRefreshToken<'_>,
+    ) -> RepoResult<RefreshTokenRecord> {
+        let record = sqlx::query_as::<_, RefreshTokenRecord>(
+            r#"
+            INSERT INTO refresh_tokens (id, user_id, family_id, session_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, family_id, session_id, expires_at, rotated_at, revoked_at
+            "#,
+        )
+        .bind(payload.id)
+        .bind(payload.user_id)
+        .bind(payload.family_id)
+        .bind(payload.session_id)
+        .bind(payload.token_hash)
+        .bind(payload.expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        replacement_hash: &str,
+        replacement_expires_at: DateTime<Utc>,
+    ) -> RepoResult<RefreshTokenRotation> {
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the presented token so two concurrent refreshes cannot both rotate it.
+        let current = sqlx::query_as::<_, RefreshTokenRecord>(
+            r#"
+            SELECT id, user_id, family_id, session_id, expires_at, rotated_at, revoked_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(tx.as_mut())
+        .await?;
+
+        let Some(current) = current else {
+            tx.commit().await?;
+            return Ok(RefreshTokenRotation::NotFound);
+        };
+
+        if current.revoked_at.is_some() {
+            tx.commit().await?;
+            return Ok(RefreshTokenRotation::Revoked);
+        }
+
+        if current.rotated_at.is_some() {
+            sqlx::query(
+                r#"
+                UPDATE refresh_tokens
+                SET revoked_at = NOW()
+                WHERE family_id = $1 AND revoked_at IS NULL
+                "#,
+            )
+            .bind(current.family_id)
+            .execute(tx.as_mut())
+            .await?;
+            tx.commit().await?;
+            return Ok(RefreshTokenRotation::Reused {
+                user_id: current.user_id,
+                family_id: current.family_id,
+            });
+        }
+
+        if current.expires_at <= Utc::now() {
+            tx.commit().await?;
+            return Ok(RefreshTokenRotation::Expired);
+        }
+
+        let replacement = sqlx::query_as::<_, RefreshTokenRecord>(
+            r#"
+            INSERT INTO refresh_tokens (id, user_id, family_id, session_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, family_id, session_id, expires_at, rotated_at, revoked_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(current.user_id)
+        .bind(current.family_id)
+        .bind(current.session_id)
+        .bind(replacement_hash)
+        .bind(replacement_expires_at)
+        .fetch_one(tx.as_mut())
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET rotated_at = NOW(),
+                replaced_by = $2
+            WHERE id = $1
+            "#,
+        )
+        .bind(current.id)
+        .bind(replacement.id)
+        .execute(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(RefreshTokenRotation::Rotated(replacement))
+    }
+
+    pub async fn revoke_refresh_token_family(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+    ) -> RepoResult<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW()
+            WHERE family_id = (
+                SELECT family_id
+                FROM refresh_tokens
+                WHERE token_hash = $2 AND user_id = $1
+            )
+            AND revoked_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn revoke_access_token(
+        &self,
+        jti: Uuid,
+        user_id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> RepoResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // Entries for tokens that have expired on their own are no longer needed.
+        sqlx::query("DELETE FROM revoked_access_tokens WHERE expires_at < NOW()")
+            .execute(tx.as_mut())
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO revoked_access_tokens (jti, user_id, expires_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (jti) DO NOTHING
+            "#,
+        )
+        .bind(jti)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Rejects every access token issued to the user at or before `before` and revokes the
+    /// sessions and refresh token families that were started before it.
+    pub async fn revoke_all_for_user(
+        &self,
+        user_id: Uuid,
+        before: DateTime<Utc>,
+    ) -> RepoResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // The cutoff only ever moves forward so an older request cannot resurrect tokens.
+        sqlx::query(
+            r#"
+            INSERT INTO access_token_cutoffs (user_id, revoked_before)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET revoked_before = GREATEST(access_token_cutoffs.revoked_before, EXCLUDED.revoked_before),
+                updated_at = NOW()
+            "#,
+        )
+        .bind(user_id)
+        .bind(before)
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW()
+            WHERE revoked_at IS NULL
+              AND family_id IN (
+                SELECT family_id
+                FROM refresh_tokens
+                WHERE user_id = $1
+                GROUP BY family_id
+                HAVING MIN(created_at) <= $2
+              )
+            "#,
+        )
+        .bind(user_id)
+        .bind(before)
+        .execute(tx.as_mut())
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE user_id = $1 AND created_at <= $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(before)
+        .execute(tx.as_mut())
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// `issued_at` is the JWT `iat` claim, which only has second precision, so a token issued in
+    /// the same second as the cutoff is treated as issued before it. Tokens of a session started
+    /// after the cutoff are left to the session's own revocation instead, so logging in again
+    /// right after logging out everywhere works.
+    pub async fn is_access_token_revoked(
+        &self,
+        user_id: Uuid,
+        jti: Uuid,
+        issued_at: i64,
+        session_id: Option<Uuid>,
+    ) -> RepoResult<bool> {
+        let revoked = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM revoked_access_tokens WHERE jti = $1
+            ) OR EXISTS (
+                SELECT 1
+                FROM access_token_cutoffs c
+                WHERE c.user_id = $2
+                  AND c.revoked_before >= to_timestamp($3)
+                  AND NOT EXISTS (
+                      SELECT 1 FROM sessions s WHERE s.id = $4 AND s.created_at > c.revoked_before
+                  )
+            ) OR EXISTS (
+                SELECT 1 FROM sessions WHERE id = $4 AND revoked_at IS NOT NULL
+            )
+            "#,
+        )
+        .bind(jti)
+        .bind(user_id)
+        .bind(issued_at as f64)
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(revoked)
+    }
+
+    pub async fn create_session(&self, payload: NewSession<'_>) -> RepoResult<SessionRecord> {
+        let record = sqlx::query_as::<_, SessionRecord>(
+            r#"
+            INSERT INTO sessions (id, user_id, provider, device_label, user_agent, ip_address)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, provider, device_label, user_agent, ip_address, created_at, last_seen_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(payload.user_id)
+        .bind(payload.provider)
+        .bind(payload.device_label)
+        .bind(payload.user_agent)
+        .bind(payload.ip_address)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn list_sessions(&self, user_id: Uuid) -> RepoResult<Vec<SessionRecord>> {
+        let records = sqlx::query_as::<_, SessionRecord>(
+            r#"
+            SELECT id, provider, device_label, user_agent, ip_address, created_at, last_seen_at
+            FROM sessions
+            WHERE user_id = $1 AND revoked_at IS NULL
+            ORDER BY last_seen_at DESC, id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Revokes the session and its refresh tokens. Returns `false` when the user has no active
+    /// session with that id.
+    pub async fn revoke_session(&self, user_id: Uuid, session_id: Uuid) -> RepoResult<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let revoked = sqlx::query(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .execute(tx.as_mut())
+        .await?
+        .rows_affected()
+            > 0;
+
+        if revoked {
+            sqlx::query(
+                r#"
+                UPDATE refresh_tokens
+                SET revoked_at = NOW()
+                WHERE session_id = $1 AND revoked_at IS NULL
+                "#,
+            )
+            .bind(session_id)
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(revoked)
+    }
+
+    /// Records activity on a session. Skips the write when it was already marked recently.
+    pub async fn touch_session(&self, session_id: Uuid) -> RepoResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE sessions
+            SET last_seen_at = NOW()
+            WHERE id = $1 AND last_seen_at < NOW() - INTERVAL '1 minute'
+            "#,
+        )
+        .bind(session_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn create_personal_token(
+        &self,
+        payload: NewPersonalToken<'_>,
+    ) -> RepoResult<PersonalTokenRecord> {
+        let record = sqlx::query_as::<_, PersonalTokenRecord>(
+            r#"
+            INSERT INTO personal_access_tokens (id, user_id, name, token_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, name, scopes, created_at, expires_at, last_used_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(payload.user_id)
+        .bind(payload.name)
+        .bind(payload.token_hash)
+        .bind(payload.scopes)
+        .bind(payload.expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Lists tokens that have not been revoked, including expired ones so the user can see them.
+    pub async fn list_personal_tokens(
+        &self,
+        user_id: Uuid,
+    ) -> RepoResult<Vec<PersonalTokenRecord>> {
+        let records = sqlx::query_as::<_, PersonalTokenRecord>(
+            r#"
+            SELECT id, user_id, name, scopes, created_at, expires_at, last_used_at
+            FROM personal_access_tokens
+            WHERE user_id = $1 AND revoked_at IS NULL
+            ORDER BY created_at DESC, id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Looks up a usable token by hash and records its use, at most once a minute.
+    pub async fn find_active_personal_token(
+        &self,
+        token_hash: &str,
+    ) -> RepoResult<Option<PersonalTokenRecord>> {
+        let record = sqlx::query_as::<_, PersonalTokenRecord>(
+            r#"
+            SELECT id, user_id, name, scopes, created_at, expires_at, last_used_at
+            FROM personal_access_tokens
+            WHERE token_hash = $1
+              AND revoked_at IS NULL
+              AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some(record) = &record {
+            sqlx::query(
+                r#"
+                UPDATE personal_access_tokens
+                SET last_used_at = NOW()
+                WHERE id = $1
+                  AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+                "#,
+            )
+            .bind(record.id)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(record)
+    }
+
+    /// Returns `false` when the user has no active token with that id.
+    pub async fn revoke_personal_token(&self, user_id: Uuid, token_id: Uuid) -> RepoResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(token_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
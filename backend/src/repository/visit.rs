@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Error as SqlxError, FromRow, PgConnection, PgPool};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::repository::place::PlaceImageRecord;
+use crate::repository::social::{record_activity, ActivityKind};
+
+#[derive(Debug, Error)]
+pub enum VisitRepositoryError {
+    #[error("database error: {0}")]
+    Database(#[from] SqlxError),
+}
+
+type RepoResult<T> = Result<T, VisitRepositoryError>;
+
+#[derive(Clone)]
+pub struct VisitRepository {
+    pool: PgPool,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct VisitRecord {
+    pub id: Uuid,
+    pub place_id: Uuid,
+    pub visited_on: NaiveDate,
+    pub rating: Option<i16>,
+    /// Amount spent in minor units of `currency`.
+    pub spend_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub companions: Vec<String>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Visit aggregates of one place.
+#[derive(Debug, Clone, FromRow)]
+pub struct VisitStatsRecord {
+    pub place_id: Uuid,
+    pub visit_count: i64,
+    pub last_visited_on: Option<NaiveDate>,
+    /// Mean of the rated visits, `None` when no visit has a rating.
+    pub average_rating: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewVisit<'a> {
+    pub id: Uuid,
+    pub place_id: Uuid,
+    pub visited_on: NaiveDate,
+    pub rating: Option<i16>,
+    pub spend_cents: Option<i64>,
+    pub currency: Option<&'a str>,
+    pub companions: &'a [String],
+    pub note: Option<&'a str>,
+    /// Images of the place to attach to the visit.
+    pub image_ids: &'a [Uuid],
+}
+
+/// Changes to a visit. `None` keeps the current value and `Some(None)` clears it.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateVisit {
+    pub visited_on: Option<NaiveDate>,
+    pub rating: Option<Option<i16>>,
+    pub spend_cents: Option<Option<i64>>,
+    pub currency: Option<Option<String>>,
+    pub companions: Option<Vec<String>>,
+    pub note: Option<Option<String>>,
+    /// Replaces the images attached to the visit when set.
+    pub image_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug)]
+pub enum VisitWrite {
+    Written(VisitRecord),
+    /// The place or visit does not exist or belongs to another user.
+    NotFound,
+    /// An image id does not refer to an image of the visited place.
+    UnknownImage,
+}
+
+impl VisitRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Visits of a place, most recent first. Empty when the user cannot see the place, as its
+    /// owner or as a member of a collection it is in.
+    pub async fn list_for_place(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+    ) -> RepoResult<Vec<VisitRecord>> {
+        let records = sqlx::query_as::<_, VisitRecord>(
+            r#"
+            SELECT v.id, v.place_id, v.visited_on, v.rating, v.spend_cents, v.currency,
+                   v.companions, v.note, v.created_at, v.updated_at
+            FROM place_visits v
+            WHERE v.place_id = $1
+              AND EXISTS (
+                  SELECT 1 FROM place_access a WHERE a.place_id = v.place_id AND a.user_id = $2
+              )
+            ORDER BY v.visited_on DESC, v.created_at DESC
+            "#,
+        )
+        .bind(place_id)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    pub async fn find_for_user(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+        visit_id: Uuid,
+    ) -> RepoResult<Option<VisitRecord>> {
+        let record = sqlx::query_as::<_, VisitRecord>(
+            r#"
+            SELECT v.id, v.place_id, v.visited_on, v.rating, v.spend_cents, v.currency,
+                   v.companions, v.note, v.created_at, v.updated_at
+            FROM place_visits v
+            WHERE v.id = $1 AND v.place_id = $2
+              AND EXISTS (
+                  SELECT 1 FROM place_access a WHERE a.place_id = v.place_id AND a.user_id = $3
+              )
+            "#,
+        )
+        .bind(visit_id)
+        .bind(place_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn create(&self, user_id: Uuid, payload: NewVisit<'_>) -> RepoResult<VisitWrite> {
+        let mut tx = self.pool.begin().await?;
+
+        let record = sqlx::query_as::<_, VisitRecord>(
+            r#"
+            INSERT INTO place_visits (
+                id, place_id, visited_on, rating, spend_cents, currency, companions, note
+            )
+            SELECT $1, p.id, $3, $4, $5, $6, $7, $8
+            FROM places p
+            WHERE p.id = $2 AND p.user_id = $9
+            RETURNING id, place_id, visited_on, rating, spend_cents, currency, companions, note,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(payload.id)
+        .bind(payload.place_id)
+        .bind(payload.visited_on)
+        .bind(payload.rating)
+        .bind(payload.spend_cents)
+        .bind(payload.currency)
+        .bind(payload.companions)
+        .bind(payload.note)
+        .bind(user_id)
+        .fetch_optional(tx.as_mut())
+        .await?;
+
+        let Some(record) = record else {
+            return Ok(VisitWrite::NotFound);
+        };
+
+        if !attach_images(tx.as_mut(), &record, payload.image_ids).await? {
+            return Ok(VisitWrite::UnknownImage);
+        }
+
+        // The first visit logged for a place takes it off the wishlist. Favorites and archived
+        // places keep their status, and so do places put back on the wishlist after a visit.
+        sqlx::query(
+            r#"
+            UPDATE places
+            SET status = 'visited', updated_at = NOW()
+            WHERE id = $1 AND status = 'want_to_go'
+              AND NOT EXISTS (SELECT 1 FROM place_visits WHERE place_id = $1 AND id <> $2)
+            "#,
+        )
+        .bind(record.place_id)
+        .bind(record.id)
+        .execute(tx.as_mut())
+        .await?;
+
+        record_activity(
+            tx.as_mut(),
+            user_id,
+            record.place_id,
+            ActivityKind::Visited,
+            Some(record.id),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(VisitWrite::Written(record))
+    }
+
+    pub async fn update(
+        &self,
+        user_id: Uuid,
+        place_id: Uuid,
+        visit_id: Uuid,
+        update: UpdateVisit,
+    ) -> RepoResult<VisitWrite> {
+        let mut tx = self.pool.begin().await?;
+
+        let record = sqlx::query_as::<_, VisitRecord>(
+            r#"
+            UPDATE place_visits v
+            SET visited_on = COALESCE($4, v.visited_on),
+                rating = CASE WHEN $5 THEN $6 ELSE v.rating END,
+                spend_cents = CASE WHEN $7 THEN $8 ELSE v.spend_cents END,
+                currency = CASE WHEN $9 THEN $10 ELSE v.currency END,
+                companions = COALESCE($11, v.companions),
+                note = CASE WHEN $12 THEN $13 ELSE v.note END,
+                updated_at = NOW()
+            FROM places p
+            WHERE v.id = $1 AND v.place_id = $2 AND p.id = v.place_id AND p.user_id = $3
+            RETURNING v.id, v.place_id, v.visited_on, v.rating, v.spend_cents, v.currency,
+                      v.companions, v.note, v.created_at, v.updated_at
+            "#,
+        )
+        .bind(visit_id)
+        .bind(place_id)
+        .bind(user_id)
+        .bind(update.visited_on)
+        .bind(update.rating.is_some())
+        .bind(update.rating.flatten())
+        .bind(update.spend_cents.is_some())
+        .bind(update.spend_cents.flatten())
+        .bind(update.currency.is_some())
+        .bind(update.currency.flatten())
+        .bind(update.companions)
+        .bind(update.note.is_some())
+        .bind(update.note.flatten())
+        .fetch_optional(tx.as_mut())
+        .await?;
+
+        let Some(record) = record else {
+            return Ok(VisitWrite::NotFound);
+        };
+
+        if let Some(image_ids) = &update.image_ids {
+            sqlx::query("UPDATE place_images SET visit_id = NULL WHERE visit_id = $1")
+                .bind(visit_id)
+                .execute(tx.as_mut())
+                .await?;
+
+            if !attach_images(tx.as_mut(), &record, image_ids).await? {
+                return Ok(VisitWrite::UnknownImage);
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(VisitWrite::Written(record))
+    }
+
+    /// Deletes a visit. Its images stay on the place.
+    pub async fn delete(&self, user_id: Uuid, place_id: Uuid, visit_id: Uuid) -> RepoResult<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM place_visits v
+            USING places p
+            WHERE v.id = $1 AND v.place_id = $2 AND p.id = v.place_id AND p.user_id = $3
+            "#,
+        )
+        .bind(visit_id)
+        .bind(place_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Images of several visits in one query, grouped by visit id, oldest first.
+    pub async fn list_images_for_visits(
+        &self,
+        visit_ids: &[Uuid],
+    ) -> RepoResult<HashMap<Uuid, Vec<PlaceImageRecord>>> {
+        if visit_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let records = sqlx::query_as::<_, PlaceImageRecord>(
+            r#"
+            SELECT id, place_id, visit_id, file_name, caption, created_at
+            FROM place_images
+            WHERE visit_id = ANY($1)
+            ORDER BY created_at
+            "#,
+        )
+        .bind(visit_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut images: HashMap<Uuid, Vec<PlaceImageRecord>> = HashMap::new();
+        for record in records {
+            if let Some(visit_id) = record.visit_id {
+                images.entry(visit_id).or_default().push(record);
+            }
+        }
+
+        Ok(images)
+    }
+
+    /// Visit aggregates of several places in one query. Places without visits have no entry.
+    pub async fn list_stats_for_places(
+        &self,
+        user_id: Uuid,
+        place_ids: &[Uuid],
+    ) -> RepoResult<HashMap<Uuid, VisitStatsRecord>> {
+        if place_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let records = sqlx::query_as::<_, VisitStatsRecord>(
+            r#"
+            SELECT v.place_id,
+                   count(*) AS visit_count,
+                   max(v.visited_on) AS last_visited_on,
+                   avg(v.rating)::DOUBLE PRECISION AS average_rating
+            FROM place_visits v
+            WHERE v.place_id = ANY($1)
+              AND EXISTS (
+                  SELECT 1 FROM place_access a WHERE a.place_id = v.place_id AND a.user_id = $2
+              )
+            GROUP BY v.place_id
+            "#,
+        )
+        .bind(place_ids)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| (record.place_id, record))
+            .collect())
+    }
+}
+
+/// Attaches images of the visited place to `visit`. Returns `false`, leaving the transaction to be
+/// rolled back, when an id does not belong to an image of that place.
+async fn attach_images(
+    conn: &mut PgConnection,
+    visit: &VisitRecord,
+    image_ids: &[Uuid],
+) -> Result<bool, SqlxError> {
+    if image_ids.is_empty() {
+        return Ok(true);
+    }
+
+    let attached = sqlx::query(
+        r#"
+        UPDATE place_images
+        SET visit_id = $1
+        WHERE id = ANY($2) AND place_id = $3
+        "#,
+    )
+    .bind(visit.id)
+    .bind(image_ids)
+    .bind(visit.place_id)
+    .execute(conn)
+    .await?;
+
+    Ok(attached.rows_affected() == image_ids.len() as u64)
+}
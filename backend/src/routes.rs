@@ -2,15 +2,31 @@ use axum::Router;
 
 use crate::app_state::AppState;
 
+mod categories;
+mod collections;
+mod comments;
+mod jwks;
+mod members;
 mod middleware;
-mod models;
+pub(crate) mod models;
 mod oauth;
 mod places;
+mod shares;
+mod social;
 mod users;
+mod visits;
 
 pub fn router(state: AppState) -> Router {
     Router::new()
+        .merge(jwks::router(state.clone()))
         .merge(oauth::router(state.clone()))
         .merge(users::router(state.clone()))
+        .merge(categories::router(state.clone()))
+        .merge(collections::router(state.clone()))
+        .merge(comments::router(state.clone()))
+        .merge(members::router(state.clone()))
+        .merge(visits::router(state.clone()))
+        .merge(shares::router(state.clone()))
+        .merge(social::router(state.clone()))
         .merge(places::router(state))
 }
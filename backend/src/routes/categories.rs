@@ -0,0 +1,496 @@
+use axum::{
+    extract::{Extension, Path as AxumPath, State},
+    http::StatusCode,
+    middleware,
+    routing::{get, patch, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::personal_token::Scope;
+use crate::repository::category::{CategoryDelete, CategoryWrite, NewCategory, UpdateCategory};
+
+use super::middleware::{api_auth, with_scope, AuthUser};
+use super::models::{CategoryResponse, ErrorResponse};
+
+pub const MAX_CATEGORY_NAME_CHARS: usize = 100;
+/// Where places without a category go, as blank categories did when categories were introduced.
+pub const UNCATEGORIZED: &str = "Uncategorized";
+const MAX_ICON_KEY_CHARS: usize = 50;
+
+/// Categories organise places, so they are readable and writable with the places scopes.
+pub fn router(state: AppState) -> Router {
+    let middleware_state = state.clone();
+
+    let read = Router::new().route("/categories", get(list_categories));
+    let write = Router::new()
+        .route("/categories", post(create_category))
+        .route(
+            "/categories/:id",
+            patch(update_category).delete(delete_category),
+        )
+        .route("/categories/:id/merge", post(merge_category));
+
+    with_scope(read, Scope::PlacesRead)
+        .merge(with_scope(write, Scope::PlacesWrite))
+        .route_layer(middleware::from_fn_with_state(middleware_state, api_auth))
+        .with_state(state)
+}
+
+async fn list_categories(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<Vec<CategoryResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let categories = state
+        .category_repository()
+        .list_for_user(user.id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list categories");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        categories.into_iter().map(CategoryResponse::from).collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct CreateCategoryRequest {
+    name: String,
+    color: Option<String>,
+    icon_key: Option<String>,
+    sort_order: Option<i32>,
+}
+
+async fn create_category(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Json(payload): Json<CreateCategoryRequest>,
+) -> Result<(StatusCode, Json<CategoryResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let name = validate_name(&payload.name)?;
+    let color = validate_color(payload.color.as_deref())?.filter(|color| !color.is_empty());
+    let icon_key =
+        validate_icon_key(payload.icon_key.as_deref())?.filter(|icon_key| !icon_key.is_empty());
+
+    let created = state
+        .category_repository()
+        .create(NewCategory {
+            id: Uuid::new_v4(),
+            user_id: user.id,
+            name,
+            color,
+            icon_key,
+            sort_order: payload.sort_order,
+        })
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to create category");
+            internal_error()
+        })?;
+
+    match created {
+        CategoryWrite::Written(category) => {
+            Ok((StatusCode::CREATED, Json(CategoryResponse::from(category))))
+        }
+        CategoryWrite::NameTaken => Err(category_exists()),
+        CategoryWrite::NotFound => Err(category_not_found()),
+    }
+}
+
+#[derive(Deserialize)]
+struct UpdateCategoryRequest {
+    name: Option<String>,
+    color: Option<String>,
+    icon_key: Option<String>,
+    sort_order: Option<i32>,
+}
+
+/// Edits a category. Omitted fields are kept and an empty `color` or `icon_key` clears it.
+/// Renaming also renames the category on all of its places.
+async fn update_category(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(category_id): AxumPath<Uuid>,
+    Json(payload): Json<UpdateCategoryRequest>,
+) -> Result<Json<CategoryResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let name = payload.name.as_deref().map(validate_name).transpose()?;
+    let color = validate_color(payload.color.as_deref())?;
+    let icon_key = validate_icon_key(payload.icon_key.as_deref())?;
+
+    let updated = state
+        .category_repository()
+        .update(
+            user.id,
+            category_id,
+            UpdateCategory {
+                name: name.map(str::to_string),
+                color: color.map(str::to_string),
+                icon_key: icon_key.map(str::to_string),
+                sort_order: payload.sort_order,
+            },
+        )
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to update category");
+            internal_error()
+        })?;
+
+    match updated {
+        CategoryWrite::Written(category) => Ok(Json(CategoryResponse::from(category))),
+        CategoryWrite::NameTaken => Err(category_exists()),
+        CategoryWrite::NotFound => Err(category_not_found()),
+    }
+}
+
+async fn delete_category(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(category_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let deleted = state
+        .category_repository()
+        .delete(user.id, category_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to delete category");
+            internal_error()
+        })?;
+
+    match deleted {
+        CategoryDelete::Deleted => Ok(StatusCode::NO_CONTENT),
+        CategoryDelete::NotFound => Err(category_not_found()),
+        CategoryDelete::InUse => Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new(
+                "category_in_use",
+                "category still has places; merge it into another category instead",
+            )),
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+struct MergeCategoryRequest {
+    into: Uuid,
+}
+
+/// Moves all places of the category in the path to `into` and deletes it.
+async fn merge_category(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(category_id): AxumPath<Uuid>,
+    Json(payload): Json<MergeCategoryRequest>,
+) -> Result<Json<CategoryResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if payload.into == category_id {
+        return Err(invalid_request("a category cannot be merged into itself"));
+    }
+
+    let target = state
+        .category_repository()
+        .merge(user.id, category_id, payload.into)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to merge categories");
+            internal_error()
+        })?
+        .ok_or_else(category_not_found)?;
+
+    Ok(Json(CategoryResponse::from(target)))
+}
+
+fn validate_name(name: &str) -> Result<&str, (StatusCode, Json<ErrorResponse>)> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_CATEGORY_NAME_CHARS {
+        return Err(invalid_request(format!(
+            "name must be between 1 and {MAX_CATEGORY_NAME_CHARS} characters"
+        )));
+    }
+    Ok(name)
+}
+
+/// Accepts `#RRGGBB` or an empty string.
+fn validate_color(color: Option<&str>) -> Result<Option<&str>, (StatusCode, Json<ErrorResponse>)> {
+    let color = color.map(str::trim);
+    let valid = color.is_none_or(|color| {
+        color.is_empty()
+            || (color.len() == 7
+                && color.starts_with('#')
+                && color[1..].chars().all(|c| c.is_ascii_hexdigit()))
+    });
+    if !valid {
+        return Err(invalid_request("color must have the form #RRGGBB"));
+    }
+    Ok(color)
+}
+
+/// Icon keys name an icon bundled with the clients, e.g. `coffee` or `local-bar`.
+fn validate_icon_key(
+    icon_key: Option<&str>,
+) -> Result<Option<&str>, (StatusCode, Json<ErrorResponse>)> {
+    let icon_key = icon_key.map(str::trim);
+    let valid = icon_key.is_none_or(|icon_key| {
+        icon_key.len() <= MAX_ICON_KEY_CHARS
+            && icon_key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    });
+    if !valid {
+        return Err(invalid_request(format!(
+            "icon_key must be at most {MAX_ICON_KEY_CHARS} lowercase letters, digits, '-' or '_'"
+        )));
+    }
+    Ok(icon_key)
+}
+
+fn invalid_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+fn category_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new(
+            "category_not_found",
+            "category does not exist",
+        )),
+    )
+}
+
+fn category_exists() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::CONFLICT,
+        Json(ErrorResponse::new(
+            "category_exists",
+            "a category with this name already exists",
+        )),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "internal_error",
+            "unexpected server error",
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Method, Request};
+    use serde_json::json;
+
+    use crate::routes::models::PlaceResponse;
+    use crate::test_utils::router::{
+        create_place, parse_json, send_json, send_multipart, Part, TestContext,
+    };
+
+    #[tokio::test]
+    async fn categories_fold_case_rename_and_merge_across_places() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone()).merge(super::router(state))
+        })
+        .await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+        let other = ctx.insert_user().await;
+        let other_token = ctx.jwt.generate(&other, None).expect("jwt");
+
+        // Plain category strings are filed under one category regardless of case.
+        let first = create_place(&ctx, &token, "Place", vec![Part::text("category", "Cafe")]).await;
+        let second = create_place(
+            &ctx,
+            &token,
+            "Place",
+            vec![Part::text("category", " cafe ")],
+        )
+        .await;
+        assert_eq!(first.category_id, second.category_id);
+        assert_eq!(second.category, "Cafe");
+        let cafe_id = first.category_id;
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/categories",
+            &token,
+            json!({
+                "name": "Coffee", "color": "#6f4e37", "icon_key": "coffee"
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let coffee: CategoryResponse = parse_json(response).await;
+        assert_eq!(coffee.sort_order, 1);
+        assert_eq!(coffee.icon_key.as_deref(), Some("coffee"));
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/categories",
+            &token,
+            json!({
+                "name": "COFFEE"
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let third = create_place(
+            &ctx,
+            &token,
+            "Place",
+            vec![Part::text("category_id", coffee.id.to_string())],
+        )
+        .await;
+        assert_eq!(third.category, "Coffee");
+
+        let categories = list(&ctx, &token).await;
+        let counts: Vec<(&str, i64)> = categories
+            .iter()
+            .map(|category| (category.name.as_str(), category.place_count))
+            .collect();
+        assert_eq!(counts, [("Cafe", 2), ("Coffee", 1)]);
+
+        // Renaming rewrites the category name on the category's places.
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &format!("/categories/{cafe_id}"),
+            &token,
+            json!({ "name": "Cafés", "color": "#FFAA00" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let place: PlaceResponse = get_place(&ctx, &token, first.id).await;
+        assert_eq!(place.category, "Cafés");
+
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &format!("/categories/{cafe_id}"),
+            &token,
+            json!({ "name": "coffee" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/categories/{}", coffee.id),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("/categories/{}/merge", coffee.id),
+            &token,
+            json!({ "into": cafe_id }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let merged: CategoryResponse = parse_json(response).await;
+        assert_eq!(merged.place_count, 3);
+        let place = get_place(&ctx, &token, third.id).await;
+        assert_eq!(
+            (place.category_id, place.category.as_str()),
+            (cafe_id, "Cafés")
+        );
+        assert_eq!(list(&ctx, &token).await.len(), 1);
+
+        // Other users can neither see nor use the category.
+        assert!(list(&ctx, &other_token).await.is_empty());
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &format!("/categories/{cafe_id}"),
+            &other_token,
+            json!({ "name": "Mine" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let parts = vec![
+            Part::text("id", Uuid::new_v4().to_string()),
+            Part::text("name", "Sneaky"),
+            Part::text("category_id", cafe_id.to_string()),
+            Part::text("location", "Somewhere"),
+        ];
+        let response = send_multipart(&ctx, Request::post("/places"), &other_token, parts).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/categories",
+            &token,
+            json!({
+                "name": "Empty"
+            }),
+        )
+        .await;
+        let empty: CategoryResponse = parse_json(response).await;
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/categories/{}", empty.id),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        for payload in [
+            json!({ "name": "" }),
+            json!({ "name": "Bars", "color": "orange" }),
+            json!({ "name": "Bars", "icon_key": "Local Bar" }),
+        ] {
+            let response = send_json(&ctx, Method::POST, "/categories", &token, payload).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        // Clients that send no category file the place under the one blank categories migrated to.
+        let blank = create_place(&ctx, &token, "Place", vec![Part::text("category", " ")]).await;
+        assert_eq!(blank.category, UNCATEGORIZED);
+        let parts = vec![
+            Part::text("id", Uuid::new_v4().to_string()),
+            Part::text("name", "Place"),
+            Part::text("location", "Somewhere"),
+        ];
+        let response = send_multipart(&ctx, Request::post("/places"), &token, parts).await;
+        let missing: PlaceResponse = parse_json(response).await;
+        assert_eq!(missing.category_id, blank.category_id);
+    }
+
+    async fn get_place(ctx: &TestContext, token: &str, place_id: Uuid) -> PlaceResponse {
+        let response = send_json(
+            ctx,
+            Method::GET,
+            &format!("/places/{place_id}"),
+            token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        parse_json(response).await
+    }
+
+    async fn list(ctx: &TestContext, token: &str) -> Vec<CategoryResponse> {
+        let response = send_json(ctx, Method::GET, "/categories", token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        parse_json(response).await
+    }
+}
@@ -0,0 +1,575 @@
+use axum::{
+    extract::{Extension, Path as AxumPath, State},
+    http::StatusCode,
+    middleware,
+    routing::{delete, get, patch, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::personal_token::Scope;
+use crate::repository::collection::{
+    CollectionPlaceChange, CollectionRecord, CollectionRole, NewCollection, UpdateCollection,
+};
+
+use super::middleware::{api_auth, with_scope, AuthUser};
+use super::models::{CollectionDetailResponse, CollectionResponse, ErrorResponse};
+use super::places::place_responses;
+
+const MAX_COLLECTION_NAME_CHARS: usize = 100;
+const MAX_DESCRIPTION_CHARS: usize = 1000;
+
+/// Collections group places, so they are readable and writable with the places scopes.
+pub fn router(state: AppState) -> Router {
+    let middleware_state = state.clone();
+
+    let read = Router::new()
+        .route("/collections", get(list_collections))
+        .route("/collections/:id", get(get_collection));
+    let write = Router::new()
+        .route("/collections", post(create_collection))
+        .route(
+            "/collections/:id",
+            patch(update_collection).delete(delete_collection),
+        )
+        .route(
+            "/collections/:id/places",
+            post(add_place).put(reorder_places),
+        )
+        .route("/collections/:id/places/:place_id", delete(remove_place));
+
+    with_scope(read, Scope::PlacesRead)
+        .merge(with_scope(write, Scope::PlacesWrite))
+        .route_layer(middleware::from_fn_with_state(middleware_state, api_auth))
+        .with_state(state)
+}
+
+async fn list_collections(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<Vec<CollectionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let collections = state
+        .collection_repository()
+        .list_for_user(user.id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list collections");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        collections
+            .into_iter()
+            .map(CollectionResponse::from)
+            .collect(),
+    ))
+}
+
+async fn get_collection(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+) -> Result<Json<CollectionDetailResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let collection =
+        find_collection(&state, user.id, collection_id, CollectionRole::Viewer).await?;
+    Ok(Json(collection_detail(&state, user.id, collection).await?))
+}
+
+#[derive(Deserialize)]
+struct CreateCollectionRequest {
+    name: String,
+    description: Option<String>,
+}
+
+async fn create_collection(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Json(payload): Json<CreateCollectionRequest>,
+) -> Result<(StatusCode, Json<CollectionResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let name = validate_name(&payload.name)?;
+    let description = validate_description(payload.description.as_deref())?
+        .filter(|description| !description.is_empty());
+
+    let collection = state
+        .collection_repository()
+        .create(NewCollection {
+            id: Uuid::new_v4(),
+            user_id: user.id,
+            name,
+            description,
+        })
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to create collection");
+            internal_error()
+        })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CollectionResponse::from(collection)),
+    ))
+}
+
+#[derive(Deserialize)]
+struct UpdateCollectionRequest {
+    name: Option<String>,
+    description: Option<String>,
+}
+
+/// Edits a collection. Omitted fields are kept and an empty `description` clears it.
+async fn update_collection(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+    Json(payload): Json<UpdateCollectionRequest>,
+) -> Result<Json<CollectionResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let name = payload.name.as_deref().map(validate_name).transpose()?;
+    let description = validate_description(payload.description.as_deref())?;
+    find_collection(&state, user.id, collection_id, CollectionRole::Owner).await?;
+
+    let collection = state
+        .collection_repository()
+        .update(
+            user.id,
+            collection_id,
+            UpdateCollection {
+                name: name.map(str::to_string),
+                description: description.map(str::to_string),
+            },
+        )
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to update collection");
+            internal_error()
+        })?
+        .ok_or_else(collection_not_found)?;
+
+    Ok(Json(CollectionResponse::from(collection)))
+}
+
+/// Deletes a collection for all of its members. Their places stay untouched.
+async fn delete_collection(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    find_collection(&state, user.id, collection_id, CollectionRole::Owner).await?;
+
+    let deleted = state
+        .collection_repository()
+        .delete(user.id, collection_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to delete collection");
+            internal_error()
+        })?;
+
+    if !deleted {
+        return Err(collection_not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct AddPlaceRequest {
+    place_id: Uuid,
+    /// Zero-based index to insert at. Appends when omitted.
+    position: Option<i32>,
+}
+
+/// Adds a place to a collection, or moves it when it is already part of it.
+async fn add_place(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+    Json(payload): Json<AddPlaceRequest>,
+) -> Result<Json<CollectionDetailResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if payload.position.is_some_and(|position| position < 0) {
+        return Err(invalid_request("position must not be negative"));
+    }
+
+    find_collection(&state, user.id, collection_id, CollectionRole::Editor).await?;
+    let change = state
+        .collection_repository()
+        .add_place(user.id, collection_id, payload.place_id, payload.position)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to add place to collection");
+            internal_error()
+        })?;
+
+    changed_collection(&state, user.id, collection_id, change).await
+}
+
+#[derive(Deserialize)]
+struct ReorderPlacesRequest {
+    place_ids: Vec<Uuid>,
+}
+
+/// Sets the order of a collection's places. `place_ids` must list each of them exactly once.
+async fn reorder_places(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+    Json(payload): Json<ReorderPlacesRequest>,
+) -> Result<Json<CollectionDetailResponse>, (StatusCode, Json<ErrorResponse>)> {
+    find_collection(&state, user.id, collection_id, CollectionRole::Editor).await?;
+    let change = state
+        .collection_repository()
+        .reorder(user.id, collection_id, &payload.place_ids)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to reorder collection");
+            internal_error()
+        })?;
+
+    changed_collection(&state, user.id, collection_id, change).await
+}
+
+async fn remove_place(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((collection_id, place_id)): AxumPath<(Uuid, Uuid)>,
+) -> Result<Json<CollectionDetailResponse>, (StatusCode, Json<ErrorResponse>)> {
+    find_collection(&state, user.id, collection_id, CollectionRole::Editor).await?;
+    let change = state
+        .collection_repository()
+        .remove_place(user.id, collection_id, place_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to remove place from collection");
+            internal_error()
+        })?;
+
+    changed_collection(&state, user.id, collection_id, change).await
+}
+
+/// Maps the outcome of a change to the collection's places to the updated collection or an
+/// error.
+async fn changed_collection(
+    state: &AppState,
+    user_id: Uuid,
+    collection_id: Uuid,
+    change: CollectionPlaceChange,
+) -> Result<Json<CollectionDetailResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match change {
+        CollectionPlaceChange::Changed => {
+            let collection =
+                find_collection(state, user_id, collection_id, CollectionRole::Viewer).await?;
+            Ok(Json(collection_detail(state, user_id, collection).await?))
+        }
+        CollectionPlaceChange::CollectionNotFound => Err(collection_not_found()),
+        CollectionPlaceChange::PlaceNotFound => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new("not_found", "place not found")),
+        )),
+        CollectionPlaceChange::PlacesMismatch => Err(invalid_request(
+            "place_ids must list every place of the collection exactly once",
+        )),
+    }
+}
+
+/// Loads a collection the user is a member of with at least `role`. Non-members get a `404` and
+/// members with a lesser role a `403`.
+pub(super) async fn find_collection(
+    state: &AppState,
+    user_id: Uuid,
+    collection_id: Uuid,
+    role: CollectionRole,
+) -> Result<CollectionRecord, (StatusCode, Json<ErrorResponse>)> {
+    let collection = state
+        .collection_repository()
+        .find_for_user(user_id, collection_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load collection");
+            internal_error()
+        })?
+        .ok_or_else(collection_not_found)?;
+
+    if collection.role < role {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(
+                "forbidden",
+                format!("this requires the {} role in the collection", role.as_str()),
+            )),
+        ));
+    }
+
+    Ok(collection)
+}
+
+async fn collection_detail(
+    state: &AppState,
+    user_id: Uuid,
+    collection: CollectionRecord,
+) -> Result<CollectionDetailResponse, (StatusCode, Json<ErrorResponse>)> {
+    let places = state
+        .collection_repository()
+        .list_places(collection.id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load collection places");
+            internal_error()
+        })?;
+
+    Ok(CollectionDetailResponse {
+        collection: CollectionResponse::from(collection),
+        places: place_responses(state, user_id, places).await?,
+    })
+}
+
+fn validate_name(name: &str) -> Result<&str, (StatusCode, Json<ErrorResponse>)> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_COLLECTION_NAME_CHARS {
+        return Err(invalid_request(format!(
+            "name must be between 1 and {MAX_COLLECTION_NAME_CHARS} characters"
+        )));
+    }
+    Ok(name)
+}
+
+fn validate_description(
+    description: Option<&str>,
+) -> Result<Option<&str>, (StatusCode, Json<ErrorResponse>)> {
+    let description = description.map(str::trim);
+    if description.is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_CHARS) {
+        return Err(invalid_request(format!(
+            "description must be at most {MAX_DESCRIPTION_CHARS} characters"
+        )));
+    }
+    Ok(description)
+}
+
+fn invalid_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+fn collection_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new(
+            "collection_not_found",
+            "collection does not exist",
+        )),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "internal_error",
+            "unexpected server error",
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+    use serde_json::json;
+
+    use crate::test_utils::router::{create_place, parse_json, send_json, TestContext};
+
+    #[tokio::test]
+    async fn collections_keep_a_manual_order_and_drop_deleted_places() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone()).merge(super::router(state))
+        })
+        .await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+        let other = ctx.insert_user().await;
+        let other_token = ctx.jwt.generate(&other, None).expect("jwt");
+
+        let mut places = Vec::new();
+        for name in ["Ramen Bar", "Noodle House", "Udon Shop", "Tea Room"] {
+            places.push(create_place(&ctx, &token, name, vec![]).await.id);
+        }
+        let [ramen, noodle, udon, tea] = places[..] else {
+            unreachable!()
+        };
+        let foreign = create_place(&ctx, &other_token, "Elsewhere", vec![])
+            .await
+            .id;
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/collections",
+            &token,
+            json!({ "name": " Best ramen downtown ", "description": "Weeknight picks" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let collection: CollectionResponse = parse_json(response).await;
+        assert_eq!(collection.name, "Best ramen downtown");
+        let places_uri = format!("/collections/{}/places", collection.id);
+
+        for place_id in [ramen, noodle, udon] {
+            let response = send_json(
+                &ctx,
+                Method::POST,
+                &places_uri,
+                &token,
+                json!({ "place_id": place_id }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &places_uri,
+            &token,
+            json!({ "place_id": tea, "position": 1 }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let detail: CollectionDetailResponse = parse_json(response).await;
+        assert_eq!(place_ids(&detail), [ramen, tea, noodle, udon]);
+        assert_eq!(detail.collection.place_count, 4);
+
+        // Adding a place again moves it.
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &places_uri,
+            &token,
+            json!({ "place_id": ramen }),
+        )
+        .await;
+        let detail: CollectionDetailResponse = parse_json(response).await;
+        assert_eq!(place_ids(&detail), [tea, noodle, udon, ramen]);
+
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &places_uri,
+            &token,
+            json!({ "place_ids": [udon, ramen, tea, noodle] }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let detail: CollectionDetailResponse = parse_json(response).await;
+        assert_eq!(place_ids(&detail), [udon, ramen, tea, noodle]);
+
+        for place_ids in [json!([udon, ramen, tea]), json!([udon, ramen, tea, tea])] {
+            let response = send_json(
+                &ctx,
+                Method::PUT,
+                &places_uri,
+                &token,
+                json!({ "place_ids": place_ids }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("{places_uri}/{tea}"),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let detail: CollectionDetailResponse = parse_json(response).await;
+        assert_eq!(place_ids(&detail), [udon, ramen, noodle]);
+
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/places/{ramen}"),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let detail = get_collection(&ctx, &token, collection.id).await;
+        assert_eq!(place_ids(&detail), [udon, noodle]);
+        let positions = sqlx::query_scalar::<_, i32>(
+            "SELECT position FROM collection_places WHERE collection_id = $1 ORDER BY position",
+        )
+        .bind(collection.id)
+        .fetch_all(&ctx.pool)
+        .await
+        .expect("positions");
+        assert_eq!(positions, [0, 1]);
+
+        // Other users' places and collections are out of reach.
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &places_uri,
+            &token,
+            json!({ "place_id": foreign }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/collections/{}", collection.id),
+            &other_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(&ctx, Method::GET, "/collections", &other_token, json!({})).await;
+        let collections: Vec<CollectionResponse> = parse_json(response).await;
+        assert!(collections.is_empty());
+
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/collections/{}", collection.id),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/places/{udon}"),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    fn place_ids(detail: &CollectionDetailResponse) -> Vec<Uuid> {
+        detail.places.iter().map(|place| place.id).collect()
+    }
+
+    async fn get_collection(
+        ctx: &TestContext,
+        token: &str,
+        collection_id: Uuid,
+    ) -> CollectionDetailResponse {
+        let response = send_json(
+            ctx,
+            Method::GET,
+            &format!("/collections/{collection_id}"),
+            token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        parse_json(response).await
+    }
+}
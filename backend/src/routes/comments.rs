@@ -0,0 +1,491 @@
+use axum::{
+    extract::{Extension, Path as AxumPath, State},
+    http::StatusCode,
+    middleware,
+    routing::{delete, get, patch, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::personal_token::Scope;
+use crate::repository::comment::{CommentWrite, FeedbackRemoval};
+
+use super::middleware::{api_auth, with_scope, AuthUser};
+use super::models::{CommentResponse, ErrorResponse, ReactionResponse};
+
+const MAX_COMMENT_CHARS: usize = 2000;
+/// Enough for emoji built from several code points, such as flags and families.
+const MAX_EMOJI_CHARS: usize = 8;
+
+/// Comments and reactions on any place the user can see, not only their own, so the
+/// visibility checks live in the repository rather than in `find_for_user`.
+pub fn router(state: AppState) -> Router {
+    let middleware_state = state.clone();
+
+    let read = Router::new()
+        .route("/places/:id/comments", get(list_comments))
+        .route("/places/:id/reactions", get(list_reactions));
+    let write = Router::new()
+        .route("/places/:id/comments", post(create_comment))
+        .route(
+            "/places/:id/comments/:comment_id",
+            patch(update_comment).delete(delete_comment),
+        )
+        .route("/places/:id/reactions", put(set_reaction))
+        .route("/places/:id/reactions/:user_id", delete(remove_reaction));
+
+    with_scope(read, Scope::PlacesRead)
+        .merge(with_scope(write, Scope::PlacesWrite))
+        .route_layer(middleware::from_fn_with_state(middleware_state, api_auth))
+        .with_state(state)
+}
+
+async fn list_comments(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(place_id): AxumPath<Uuid>,
+) -> Result<Json<Vec<CommentResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let comments = state
+        .comment_repository()
+        .list_comments(user.id, place_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list comments");
+            internal_error()
+        })?
+        .ok_or_else(place_not_found)?;
+
+    Ok(Json(
+        comments.into_iter().map(CommentResponse::from).collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct CommentRequest {
+    body: String,
+}
+
+async fn create_comment(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(place_id): AxumPath<Uuid>,
+    Json(payload): Json<CommentRequest>,
+) -> Result<(StatusCode, Json<CommentResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let body = validate_body(&payload.body)?;
+
+    let comment = state
+        .comment_repository()
+        .create_comment(user.id, place_id, body)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to create comment");
+            internal_error()
+        })?
+        .ok_or_else(place_not_found)?;
+
+    Ok((StatusCode::CREATED, Json(CommentResponse::from(comment))))
+}
+
+async fn update_comment(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((place_id, comment_id)): AxumPath<(Uuid, Uuid)>,
+    Json(payload): Json<CommentRequest>,
+) -> Result<Json<CommentResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let body = validate_body(&payload.body)?;
+
+    let write = state
+        .comment_repository()
+        .update_comment(user.id, place_id, comment_id, body)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to update comment");
+            internal_error()
+        })?;
+
+    match write {
+        CommentWrite::Written(comment) => Ok(Json(CommentResponse::from(comment))),
+        CommentWrite::NotFound => Err(comment_not_found()),
+        CommentWrite::Forbidden => Err(forbidden("only the author can edit a comment")),
+    }
+}
+
+async fn delete_comment(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((place_id, comment_id)): AxumPath<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let removal = state
+        .comment_repository()
+        .delete_comment(user.id, place_id, comment_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to delete comment");
+            internal_error()
+        })?;
+
+    match removal {
+        FeedbackRemoval::Removed => Ok(StatusCode::NO_CONTENT),
+        FeedbackRemoval::NotFound => Err(comment_not_found()),
+        FeedbackRemoval::Forbidden => Err(forbidden(
+            "only the author or the owner of the place can delete a comment",
+        )),
+    }
+}
+
+async fn list_reactions(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(place_id): AxumPath<Uuid>,
+) -> Result<Json<Vec<ReactionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let reactions = state
+        .comment_repository()
+        .list_reactions(user.id, place_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list reactions");
+            internal_error()
+        })?
+        .ok_or_else(place_not_found)?;
+
+    Ok(Json(
+        reactions.into_iter().map(ReactionResponse::from).collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ReactionRequest {
+    emoji: String,
+}
+
+async fn set_reaction(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(place_id): AxumPath<Uuid>,
+    Json(payload): Json<ReactionRequest>,
+) -> Result<Json<ReactionResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let emoji = validate_emoji(&payload.emoji)?;
+
+    let reaction = state
+        .comment_repository()
+        .set_reaction(user.id, place_id, emoji)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to set reaction");
+            internal_error()
+        })?
+        .ok_or_else(place_not_found)?;
+
+    Ok(Json(ReactionResponse::from(reaction)))
+}
+
+async fn remove_reaction(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((place_id, reactor_id)): AxumPath<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let removal = state
+        .comment_repository()
+        .remove_reaction(user.id, place_id, reactor_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to remove reaction");
+            internal_error()
+        })?;
+
+    match removal {
+        FeedbackRemoval::Removed => Ok(StatusCode::NO_CONTENT),
+        FeedbackRemoval::NotFound => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "reaction_not_found",
+                "reaction not found",
+            )),
+        )),
+        FeedbackRemoval::Forbidden => Err(forbidden(
+            "only the user who reacted or the owner of the place can remove a reaction",
+        )),
+    }
+}
+
+fn validate_body(body: &str) -> Result<&str, (StatusCode, Json<ErrorResponse>)> {
+    let body = body.trim();
+    if body.is_empty() || body.chars().count() > MAX_COMMENT_CHARS {
+        return Err(invalid_request(
+            "body must be between 1 and 2000 characters",
+        ));
+    }
+    Ok(body)
+}
+
+/// Accepts a single emoji, loosely: a short run of non-ASCII characters without whitespace.
+fn validate_emoji(emoji: &str) -> Result<&str, (StatusCode, Json<ErrorResponse>)> {
+    let emoji = emoji.trim();
+    let count = emoji.chars().count();
+    if count == 0
+        || count > MAX_EMOJI_CHARS
+        || emoji.chars().any(|c| c.is_ascii() || c.is_whitespace())
+    {
+        return Err(invalid_request("emoji must be a single emoji"));
+    }
+    Ok(emoji)
+}
+
+fn invalid_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+fn forbidden(message: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse::new("forbidden", message)),
+    )
+}
+
+fn place_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new("not_found", "place not found")),
+    )
+}
+
+fn comment_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new("comment_not_found", "comment not found")),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "internal_error",
+            "unexpected server error",
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+    use serde_json::json;
+
+    use crate::routes::models::PlaceResponse;
+    use crate::test_utils::router::{create_place, parse_json, send_json, Part, TestContext};
+
+    #[tokio::test]
+    async fn visible_places_collect_comments_and_reactions_until_deleted() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone())
+                .merge(crate::routes::users::router(state.clone()))
+                .merge(super::router(state))
+        })
+        .await;
+        let owner = ctx.insert_user().await;
+        let owner_token = ctx.jwt.generate(&owner, None).expect("jwt");
+        let guest = ctx.insert_user().await;
+        let guest_token = ctx.jwt.generate(&guest, None).expect("jwt");
+        let other = ctx.insert_user().await;
+        let other_token = ctx.jwt.generate(&other, None).expect("jwt");
+
+        let public = create_place(
+            &ctx,
+            &owner_token,
+            "Matcha Bar",
+            vec![
+                Part::text("category", "Cafe"),
+                Part::text("visibility", "public"),
+            ],
+        )
+        .await;
+        let private = create_place(
+            &ctx,
+            &owner_token,
+            "Secret Spot",
+            vec![
+                Part::text("category", "Cafe"),
+                Part::text("visibility", "private"),
+            ],
+        )
+        .await;
+        let comments_uri = format!("/places/{}/comments", public.id);
+        let reactions_uri = format!("/places/{}/reactions", public.id);
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("/places/{}/comments", private.id),
+            &guest_token,
+            json!({ "body": "Looks nice" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &comments_uri,
+            &guest_token,
+            json!({ "body": "   " }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &comments_uri,
+            &guest_token,
+            json!({ "body": "The matcha latte is great" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let comment: CommentResponse = parse_json(response).await;
+        assert_eq!(comment.user.id, guest.id);
+        let comment_uri = format!("{comments_uri}/{}", comment.id);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &comments_uri,
+            &other_token,
+            json!({ "body": "Too sweet for me" }),
+        )
+        .await;
+        let other_comment: CommentResponse = parse_json(response).await;
+
+        // Authors edit their own comments, owners may only delete them.
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &comment_uri,
+            &owner_token,
+            json!({ "body": "Edited" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &comment_uri,
+            &guest_token,
+            json!({ "body": "The matcha latte is great, get it iced" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let edited: CommentResponse = parse_json(response).await;
+        assert_eq!(edited.body, "The matcha latte is great, get it iced");
+        let response = send_json(&ctx, Method::DELETE, &comment_uri, &other_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("{comments_uri}/{}", other_comment.id),
+            &owner_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        for (token, emoji) in [
+            (&guest_token, "😍"),
+            (&other_token, "😍"),
+            (&owner_token, "👍"),
+        ] {
+            let response = send_json(
+                &ctx,
+                Method::PUT,
+                &reactions_uri,
+                token,
+                json!({ "emoji": emoji }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &reactions_uri,
+            &guest_token,
+            json!({ "emoji": "nice" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("{reactions_uri}/{}", guest.id),
+            &other_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/places/{}", public.id),
+            &owner_token,
+            json!({}),
+        )
+        .await;
+        let place: PlaceResponse = parse_json(response).await;
+        assert_eq!(place.comment_count, 1);
+        let counts: Vec<(&str, i64)> = place
+            .reaction_counts
+            .iter()
+            .map(|count| (count.emoji.as_str(), count.count))
+            .collect();
+        assert_eq!(counts, [("😍", 2), ("👍", 1)]);
+
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("{reactions_uri}/{}", other.id),
+            &owner_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send_json(&ctx, Method::GET, &reactions_uri, &guest_token, json!({})).await;
+        let reactions: Vec<ReactionResponse> = parse_json(response).await;
+        assert_eq!(reactions.len(), 2);
+
+        // Deleting an account removes the comments it wrote, deleting a place all of its own.
+        let response = send_json(&ctx, Method::DELETE, "/usr", &guest_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(comment_count(&ctx, public.id).await, 0);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &comments_uri,
+            &other_token,
+            json!({ "body": "Still great" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/places/{}", public.id),
+            &owner_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(comment_count(&ctx, public.id).await, 0);
+    }
+
+    async fn comment_count(ctx: &TestContext, place_id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT count(*) FROM place_comments WHERE place_id = $1")
+            .bind(place_id)
+            .fetch_one(&ctx.pool)
+            .await
+            .expect("count comments")
+    }
+}
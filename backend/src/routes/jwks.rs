@@ -0,0 +1,28 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderValue},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+
+use crate::app_state::AppState;
+
+// Verifiers may cache the key set, so new keys must be published before they become active.
+const JWKS_CACHE_CONTROL: &str = "public, max-age=300";
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/.well-known/jwks.json", get(jwks))
+        .with_state(state)
+}
+
+async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(JWKS_CACHE_CONTROL),
+        )],
+        Json(state.jwt_manager().jwks()),
+    )
+}
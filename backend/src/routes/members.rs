@@ -0,0 +1,899 @@
+use axum::{
+    extract::{Extension, Path as AxumPath, State},
+    http::StatusCode,
+    middleware,
+    routing::{delete, get, patch, post},
+    Json, Router,
+};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::personal_token::Scope;
+use crate::refresh_token::{generate_token, hash_token};
+use crate::repository::collection::{CollectionRole, MemberChange, NewCollectionInvite};
+
+use super::collections::find_collection;
+use super::middleware::{api_auth, with_scope, AuthUser};
+use super::models::{
+    CollectionInviteResponse, CollectionMemberResponse, CollectionResponse, ErrorResponse,
+};
+
+const INVITE_TTL_DAYS: i64 = 14;
+const MAX_EMAIL_CHARS: usize = 254;
+
+/// Members and invites of collections. Like the collections themselves they are read and
+/// written with the places scopes.
+pub fn router(state: AppState) -> Router {
+    let middleware_state = state.clone();
+
+    let read = Router::new()
+        .route("/collections/:id/members", get(list_members))
+        .route("/collections/:id/invites", get(list_invites))
+        .route("/invites", get(list_my_invites));
+    let write = Router::new()
+        .route(
+            "/collections/:id/members/:user_id",
+            patch(update_member).delete(remove_member),
+        )
+        .route("/collections/:id/leave", post(leave_collection))
+        .route("/collections/:id/invites", post(create_invite))
+        .route("/collections/:id/invites/:invite_id", delete(revoke_invite))
+        .route("/invites/redeem", post(redeem_invite))
+        .route("/invites/:id/accept", post(accept_invite))
+        .route("/invites/:id", delete(decline_invite));
+
+    with_scope(read, Scope::PlacesRead)
+        .merge(with_scope(write, Scope::PlacesWrite))
+        .route_layer(middleware::from_fn_with_state(middleware_state, api_auth))
+        .with_state(state)
+}
+
+async fn list_members(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+) -> Result<Json<Vec<CollectionMemberResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    find_collection(&state, user.id, collection_id, CollectionRole::Viewer).await?;
+
+    let members = state
+        .collection_repository()
+        .list_members(collection_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list collection members");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        members
+            .into_iter()
+            .map(CollectionMemberResponse::from)
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct UpdateMemberRequest {
+    role: String,
+}
+
+async fn update_member(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((collection_id, member_id)): AxumPath<(Uuid, Uuid)>,
+    Json(payload): Json<UpdateMemberRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let role = parse_role(&payload.role)?;
+    find_collection(&state, user.id, collection_id, CollectionRole::Owner).await?;
+
+    let change = state
+        .collection_repository()
+        .set_member_role(collection_id, member_id, role)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to change collection member role");
+            internal_error()
+        })?;
+
+    member_changed(change)
+}
+
+/// Removes a member. Owners can remove anyone and every member can remove themselves, which is
+/// the same as leaving.
+async fn remove_member(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((collection_id, member_id)): AxumPath<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let required = if member_id == user.id {
+        CollectionRole::Viewer
+    } else {
+        CollectionRole::Owner
+    };
+    find_collection(&state, user.id, collection_id, required).await?;
+
+    leave(&state, collection_id, member_id).await
+}
+
+async fn leave_collection(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    find_collection(&state, user.id, collection_id, CollectionRole::Viewer).await?;
+
+    leave(&state, collection_id, user.id).await
+}
+
+async fn leave(
+    state: &AppState,
+    collection_id: Uuid,
+    member_id: Uuid,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let change = state
+        .collection_repository()
+        .remove_member(collection_id, member_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to remove collection member");
+            internal_error()
+        })?;
+
+    member_changed(change)?;
+    info!(%collection_id, user_id = %member_id, "collection member removed");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_invites(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+) -> Result<Json<Vec<CollectionInviteResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    find_collection(&state, user.id, collection_id, CollectionRole::Owner).await?;
+
+    let invites = state
+        .collection_repository()
+        .list_invites(collection_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list collection invites");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        invites
+            .into_iter()
+            .map(CollectionInviteResponse::from)
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct CreateInviteRequest {
+    role: String,
+    /// Invites the user with this address. Without it the invite gets a code instead.
+    email: Option<String>,
+}
+
+/// Invites someone by email address, or creates an invite code that can be passed around. The
+/// code is only returned here.
+async fn create_invite(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(collection_id): AxumPath<Uuid>,
+    Json(payload): Json<CreateInviteRequest>,
+) -> Result<(StatusCode, Json<CollectionInviteResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let role = parse_role(&payload.role)?;
+    let email = payload.email.as_deref().map(normalize_email).transpose()?;
+    find_collection(&state, user.id, collection_id, CollectionRole::Owner).await?;
+
+    let code = email.is_none().then(generate_token);
+    let code_hash = code.as_deref().map(hash_token);
+    let invite = state
+        .collection_repository()
+        .create_invite(NewCollectionInvite {
+            collection_id,
+            invited_by: user.id,
+            role,
+            email: email.as_deref(),
+            code_hash: code_hash.as_deref(),
+            expires_at: Utc::now() + Duration::days(INVITE_TTL_DAYS),
+        })
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to create collection invite");
+            internal_error()
+        })?;
+
+    info!(user_id = %user.id, %collection_id, invite_id = %invite.id, "collection invite created");
+
+    let mut response = CollectionInviteResponse::from(invite);
+    response.code = code;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+async fn revoke_invite(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((collection_id, invite_id)): AxumPath<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    find_collection(&state, user.id, collection_id, CollectionRole::Owner).await?;
+
+    let deleted = state
+        .collection_repository()
+        .delete_invite(collection_id, invite_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to revoke collection invite");
+            internal_error()
+        })?;
+
+    if !deleted {
+        return Err(invite_not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Pending invites addressed to the user's email address.
+async fn list_my_invites(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<Vec<CollectionInviteResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let invites = state
+        .collection_repository()
+        .list_invites_for_user(user.id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list invites");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        invites
+            .into_iter()
+            .map(CollectionInviteResponse::from)
+            .collect(),
+    ))
+}
+
+async fn accept_invite(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(invite_id): AxumPath<Uuid>,
+) -> Result<Json<CollectionResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let collection_id = state
+        .collection_repository()
+        .accept_invite(user.id, invite_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to accept invite");
+            internal_error()
+        })?
+        .ok_or_else(invite_not_found)?;
+
+    info!(user_id = %user.id, %collection_id, "collection invite accepted");
+
+    let collection =
+        find_collection(&state, user.id, collection_id, CollectionRole::Viewer).await?;
+    Ok(Json(CollectionResponse::from(collection)))
+}
+
+async fn decline_invite(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(invite_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let declined = state
+        .collection_repository()
+        .decline_invite(user.id, invite_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to decline invite");
+            internal_error()
+        })?;
+
+    if !declined {
+        return Err(invite_not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct RedeemInviteRequest {
+    code: String,
+}
+
+/// Joins a collection with an invite code. Members who redeem a code keep their current role.
+async fn redeem_invite(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Json(payload): Json<RedeemInviteRequest>,
+) -> Result<Json<CollectionResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let collection_id = state
+        .collection_repository()
+        .redeem_invite_code(user.id, &hash_token(payload.code.trim()))
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to redeem invite code");
+            internal_error()
+        })?
+        .ok_or_else(invite_not_found)?;
+
+    info!(user_id = %user.id, %collection_id, "collection invite code redeemed");
+
+    let collection =
+        find_collection(&state, user.id, collection_id, CollectionRole::Viewer).await?;
+    Ok(Json(CollectionResponse::from(collection)))
+}
+
+fn member_changed(change: MemberChange) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match change {
+        MemberChange::Changed => Ok(StatusCode::NO_CONTENT),
+        MemberChange::NotFound => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "member_not_found",
+                "user is not a member of the collection",
+            )),
+        )),
+        MemberChange::LastOwner => Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new(
+                "last_owner",
+                "a collection needs at least one owner",
+            )),
+        )),
+    }
+}
+
+fn parse_role(value: &str) -> Result<CollectionRole, (StatusCode, Json<ErrorResponse>)> {
+    CollectionRole::parse(value)
+        .ok_or_else(|| invalid_request("role must be one of viewer, editor, owner"))
+}
+
+fn normalize_email(email: &str) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    let email = email.trim().to_lowercase();
+    let valid = email.chars().count() <= MAX_EMAIL_CHARS
+        && email
+            .split_once('@')
+            .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'));
+    if !valid {
+        return Err(invalid_request("email must be a valid email address"));
+    }
+    Ok(email)
+}
+
+fn invalid_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+fn invite_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new(
+            "invite_not_found",
+            "invite does not exist or has expired",
+        )),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "internal_error",
+            "unexpected server error",
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Method, Request};
+    use serde_json::json;
+
+    use crate::routes::models::{CollectionDetailResponse, PlaceResponse};
+    use crate::test_utils::router::{
+        create_place, parse_json, send_json, send_multipart, Part, TestContext,
+    };
+
+    #[tokio::test]
+    async fn collection_roles_decide_who_may_read_edit_and_manage() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone())
+                .merge(crate::routes::visits::router(state.clone()))
+                .merge(crate::routes::collections::router(state.clone()))
+                .merge(super::router(state))
+        })
+        .await;
+        let owner = ctx.insert_user().await;
+        let owner_token = ctx.jwt.generate(&owner, None).expect("jwt");
+        let editor = ctx.insert_user().await;
+        let editor_token = ctx.jwt.generate(&editor, None).expect("jwt");
+        let viewer = ctx.insert_user().await;
+        let viewer_token = ctx.jwt.generate(&viewer, None).expect("jwt");
+        let stranger = ctx.insert_user().await;
+        let stranger_token = ctx.jwt.generate(&stranger, None).expect("jwt");
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/collections",
+            &owner_token,
+            json!({ "name": "Family trip" }),
+        )
+        .await;
+        let collection: CollectionResponse = parse_json(response).await;
+        assert_eq!(collection.role, "owner");
+        let collection_uri = format!("/collections/{}", collection.id);
+        let owner_place = create_place(&ctx, &owner_token, "Harbour Cafe", vec![]).await;
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("{collection_uri}/places"),
+            &owner_token,
+            json!({ "place_id": owner_place.id }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("/places/{}/visits", owner_place.id),
+            &owner_token,
+            json!({ "visited_on": "2024-05-01", "rating": 4 }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // The editor is invited by email and the viewer with a code.
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("{collection_uri}/invites"),
+            &owner_token,
+            json!({ "role": "editor", "email": editor.email.as_deref().unwrap().to_uppercase() }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let email_invite: CollectionInviteResponse = parse_json(response).await;
+        assert!(email_invite.code.is_none());
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("{collection_uri}/invites"),
+            &owner_token,
+            json!({ "role": "viewer" }),
+        )
+        .await;
+        let code_invite: CollectionInviteResponse = parse_json(response).await;
+        let code = code_invite.code.expect("code is returned on creation");
+
+        // Invites can only be accepted by their addressee.
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("/invites/{}/accept", email_invite.id),
+            &stranger_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(&ctx, Method::GET, "/invites", &editor_token, json!({})).await;
+        let pending: Vec<CollectionInviteResponse> = parse_json(response).await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].collection_name, "Family trip");
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("/invites/{}/accept", email_invite.id),
+            &editor_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let joined: CollectionResponse = parse_json(response).await;
+        assert_eq!(joined.role, "editor");
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/invites/redeem",
+            &viewer_token,
+            json!({ "code": code }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/invites/redeem",
+            &viewer_token,
+            json!({ "code": "not-a-code" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Everyone sees the collection and its places with their visits, strangers do not.
+        for token in [&owner_token, &editor_token, &viewer_token] {
+            let response = send_json(&ctx, Method::GET, &collection_uri, token, json!({})).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let response = send_json(
+                &ctx,
+                Method::GET,
+                &format!("/places/{}", owner_place.id),
+                token,
+                json!({}),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let place: PlaceResponse = parse_json(response).await;
+            assert_eq!(place.visit_count, 1);
+            assert_eq!(place.average_rating, Some(4.0));
+            let response = send_json(
+                &ctx,
+                Method::GET,
+                &format!("/places/{}/visits", owner_place.id),
+                token,
+                json!({}),
+            )
+            .await;
+            let visits: Vec<serde_json::Value> = parse_json(response).await;
+            assert_eq!(visits.len(), 1);
+        }
+        for uri in [
+            collection_uri.clone(),
+            format!("/places/{}", owner_place.id),
+            format!("/places/{}/visits", owner_place.id),
+        ] {
+            let response = send_json(&ctx, Method::GET, &uri, &stranger_token, json!({})).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("{collection_uri}/members"),
+            &viewer_token,
+            json!({}),
+        )
+        .await;
+        let members: Vec<CollectionMemberResponse> = parse_json(response).await;
+        let roles: Vec<&str> = members.iter().map(|member| member.role.as_str()).collect();
+        assert_eq!(roles, ["owner", "editor", "viewer"]);
+
+        // Editors add their own places and edit places in the collection, viewers cannot.
+        let editor_place = create_place(&ctx, &editor_token, "Lighthouse Diner", vec![]).await;
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("{collection_uri}/places"),
+            &editor_token,
+            json!({ "place_id": editor_place.id }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let detail: CollectionDetailResponse = parse_json(response).await;
+        assert_eq!(detail.places.len(), 2);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("{collection_uri}/places"),
+            &viewer_token,
+            json!({ "place_id": editor_place.id, "position": 0 }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = patch_place(
+            &ctx,
+            &editor_token,
+            owner_place.id,
+            vec![
+                Part::text("note", "Book ahead on weekends"),
+                Part::text("category", "Brunch"),
+                Part::text("tags", r#"["seafood"]"#),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let edited: PlaceResponse = parse_json(response).await;
+        assert_eq!(edited.user_id, owner.id);
+        assert_eq!(edited.note.as_deref(), Some("Book ahead on weekends"));
+        assert_eq!(edited.tags, ["seafood"]);
+        let category_owner: Uuid =
+            sqlx::query_scalar("SELECT user_id FROM categories WHERE id = $1")
+                .bind(edited.category_id)
+                .fetch_one(&ctx.pool)
+                .await
+                .expect("category");
+        assert_eq!(category_owner, owner.id);
+        let response = patch_place(
+            &ctx,
+            &editor_token,
+            owner_place.id,
+            vec![Part::text("visibility", "public")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = patch_place(
+            &ctx,
+            &viewer_token,
+            owner_place.id,
+            vec![Part::text("note", "Overrated")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/places/{}", owner_place.id),
+            &editor_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Only owners rename the collection and manage members.
+        for token in [&editor_token, &viewer_token] {
+            let response = send_json(
+                &ctx,
+                Method::PATCH,
+                &collection_uri,
+                token,
+                json!({ "name": "Mine now" }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            let response = send_json(
+                &ctx,
+                Method::DELETE,
+                &format!("{collection_uri}/members/{}", owner.id),
+                token,
+                json!({}),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &format!("{collection_uri}/members/{}", viewer.id),
+            &owner_token,
+            json!({ "role": "editor" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // The last owner cannot step down or leave.
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &format!("{collection_uri}/members/{}", owner.id),
+            &owner_token,
+            json!({ "role": "viewer" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("{collection_uri}/leave"),
+            &owner_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        // Leaving takes the member's places out of the collection and ends their access.
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("{collection_uri}/leave"),
+            &editor_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send_json(&ctx, Method::GET, &collection_uri, &owner_token, json!({})).await;
+        let detail: CollectionDetailResponse = parse_json(response).await;
+        let place_ids: Vec<Uuid> = detail.places.iter().map(|place| place.id).collect();
+        assert_eq!(place_ids, [owner_place.id]);
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/places/{}", owner_place.id),
+            &editor_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn deleting_an_account_closes_the_gaps_in_shared_collections() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone())
+                .merge(crate::routes::collections::router(state.clone()))
+                .merge(crate::routes::users::router(state.clone()))
+                .merge(super::router(state))
+        })
+        .await;
+        let owner = ctx.insert_user().await;
+        let owner_token = ctx.jwt.generate(&owner, None).expect("jwt");
+        let editor = ctx.insert_user().await;
+        let editor_token = ctx.jwt.generate(&editor, None).expect("jwt");
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/collections",
+            &owner_token,
+            json!({ "name": "Weekend" }),
+        )
+        .await;
+        let collection: CollectionResponse = parse_json(response).await;
+        let collection_uri = format!("/collections/{}", collection.id);
+        sqlx::query(
+            "INSERT INTO collection_members (collection_id, user_id, role) VALUES ($1, $2, 'editor')",
+        )
+        .bind(collection.id)
+        .bind(editor.id)
+        .execute(&ctx.pool)
+        .await
+        .expect("add editor");
+
+        let first = create_place(&ctx, &owner_token, "Bakery", vec![]).await;
+        let second = create_place(&ctx, &editor_token, "Boat Tour", vec![]).await;
+        let third = create_place(&ctx, &owner_token, "Night Market", vec![]).await;
+        for (token, place) in [
+            (&owner_token, &first),
+            (&editor_token, &second),
+            (&owner_token, &third),
+        ] {
+            let response = send_json(
+                &ctx,
+                Method::POST,
+                &format!("{collection_uri}/places"),
+                token,
+                json!({ "place_id": place.id }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = send_json(&ctx, Method::DELETE, "/usr", &editor_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = send_json(&ctx, Method::GET, &collection_uri, &owner_token, json!({})).await;
+        let detail: CollectionDetailResponse = parse_json(response).await;
+        let ids: Vec<Uuid> = detail.places.iter().map(|place| place.id).collect();
+        assert_eq!(ids, [first.id, third.id]);
+        let positions: Vec<i32> = sqlx::query_scalar(
+            "SELECT position FROM collection_places WHERE collection_id = $1 ORDER BY position",
+        )
+        .bind(collection.id)
+        .fetch_all(&ctx.pool)
+        .await
+        .expect("positions");
+        assert_eq!(positions, [0, 1]);
+    }
+
+    #[tokio::test]
+    async fn collections_outlive_the_account_of_their_creator() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::collections::router(state.clone())
+                .merge(crate::routes::users::router(state.clone()))
+                .merge(super::router(state))
+        })
+        .await;
+        let creator = ctx.insert_user().await;
+        let creator_token = ctx.jwt.generate(&creator, None).expect("jwt");
+        let co_owner = ctx.insert_user().await;
+        let co_owner_token = ctx.jwt.generate(&co_owner, None).expect("jwt");
+        let editor = ctx.insert_user().await;
+        let editor_token = ctx.jwt.generate(&editor, None).expect("jwt");
+        let viewer = ctx.insert_user().await;
+
+        let mut collections = Vec::new();
+        for name in ["Co-owned", "Handed over", "Alone"] {
+            let response = send_json(
+                &ctx,
+                Method::POST,
+                "/collections",
+                &creator_token,
+                json!({ "name": name }),
+            )
+            .await;
+            let collection: CollectionResponse = parse_json(response).await;
+            collections.push(collection.id);
+        }
+        let [co_owned, handed_over, alone] = collections[..] else {
+            unreachable!()
+        };
+        for (collection_id, user_id, role, joined) in [
+            (co_owned, co_owner.id, "owner", "1 hour"),
+            (handed_over, viewer.id, "viewer", "1 hour"),
+            (handed_over, editor.id, "editor", "2 hours"),
+        ] {
+            sqlx::query(
+                r#"
+                INSERT INTO collection_members (collection_id, user_id, role, joined_at)
+                VALUES ($1, $2, $3, NOW() - $4::INTERVAL)
+                "#,
+            )
+            .bind(collection_id)
+            .bind(user_id)
+            .bind(role)
+            .bind(joined)
+            .execute(&ctx.pool)
+            .await
+            .expect("add member");
+        }
+
+        let response = send_json(&ctx, Method::DELETE, "/usr", &creator_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // Other owners keep the collection, and without one the earliest member takes over.
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/collections/{co_owned}/members"),
+            &co_owner_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let members: Vec<CollectionMemberResponse> = parse_json(response).await;
+        let roles: Vec<(Uuid, &str)> = members
+            .iter()
+            .map(|member| (member.user_id, member.role.as_str()))
+            .collect();
+        assert_eq!(roles, [(co_owner.id, "owner")]);
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/collections/{handed_over}/members"),
+            &editor_token,
+            json!({}),
+        )
+        .await;
+        let members: Vec<CollectionMemberResponse> = parse_json(response).await;
+        let roles: Vec<(Uuid, &str)> = members
+            .iter()
+            .map(|member| (member.user_id, member.role.as_str()))
+            .collect();
+        assert_eq!(roles, [(editor.id, "owner"), (viewer.id, "viewer")]);
+
+        let remaining: Vec<(Uuid, Option<Uuid>)> =
+            sqlx::query_as("SELECT id, user_id FROM collections ORDER BY name")
+                .fetch_all(&ctx.pool)
+                .await
+                .expect("collections");
+        assert_eq!(remaining.len(), 2);
+        assert!(!remaining.iter().any(|(id, _)| *id == alone));
+        assert!(remaining.iter().all(|(_, creator_id)| creator_id.is_none()));
+    }
+
+    async fn patch_place(
+        ctx: &TestContext,
+        token: &str,
+        place_id: Uuid,
+        parts: Vec<Part>,
+    ) -> axum::response::Response {
+        send_multipart(
+            ctx,
+            Request::patch(format!("/places/{place_id}")),
+            token,
+            parts,
+        )
+        .await
+    }
+}
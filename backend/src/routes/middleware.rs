@@ -2,16 +2,37 @@ use axum::{
     body::Body,
     extract::State,
     http::{header, Request, StatusCode},
-    middleware::Next,
+    middleware::{self, Next},
     response::Response,
-    Json,
+    Json, Router,
 };
 use tracing::error;
+use uuid::Uuid;
 
-use crate::{app_state::AppState, jwt::split_bearer_token};
+use crate::{
+    app_state::AppState,
+    jwt::{split_bearer_token, JwtClaims},
+    personal_token::{self, Scope},
+};
 
 use super::models::ErrorResponse;
 
+/// The caller of an endpoint behind `api_auth`.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: Uuid,
+    /// Scopes of the personal access token used, or `None` for a JWT, which may do anything.
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl AuthUser {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope))
+    }
+}
+
 pub async fn jwt_auth(
     State(state): State<AppState>,
     mut req: Request<Body>,
@@ -20,14 +41,113 @@ pub async fn jwt_auth(
     let token = extract_token(req.headers())
         .ok_or_else(|| unauthorized("missing Authorization bearer token"))?;
 
+    let claims = verify_jwt(&state, token).await?;
+    req.extensions_mut().insert(claims);
+
+    Ok(next.run(req).await)
+}
+
+/// Accepts a JWT or a personal access token and stores the caller as an `AuthUser`. Routes that
+/// personal access tokens may call restrict them further with `require_scope`.
+pub async fn api_auth(
+    State(state): State<AppState>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let token = extract_token(req.headers())
+        .ok_or_else(|| unauthorized("missing Authorization bearer token"))?;
+
+    let user = if personal_token::is_personal_token(token) {
+        let record = state
+            .token_repository()
+            .find_active_personal_token(&personal_token::hash(token))
+            .await
+            .map_err(|error| {
+                error!(?error, "failed to look up personal access token");
+                internal_error()
+            })?
+            .ok_or_else(|| unauthorized("invalid, expired or revoked token"))?;
+
+        AuthUser {
+            id: record.user_id,
+            scopes: Some(
+                record
+                    .scopes
+                    .iter()
+                    .filter_map(|scope| Scope::parse(scope))
+                    .collect(),
+            ),
+        }
+    } else {
+        let claims = verify_jwt(&state, token).await?;
+        AuthUser {
+            id: claims.sub,
+            scopes: None,
+        }
+    };
+    req.extensions_mut().insert(user);
+
+    Ok(next.run(req).await)
+}
+
+/// Rejects callers whose token lacks `scope`. Must run after `api_auth`.
+pub async fn require_scope(
+    scope: Scope,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let allowed = req
+        .extensions()
+        .get::<AuthUser>()
+        .is_some_and(|user| user.has_scope(scope));
+    if !allowed {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(
+                "insufficient_scope",
+                format!("token lacks the {} scope", scope.as_str()),
+            )),
+        ));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Personal access tokens may only call the routes of `router` when they carry `scope`.
+pub fn with_scope(router: Router<AppState>, scope: Scope) -> Router<AppState> {
+    router.route_layer(middleware::from_fn(
+        move |req: Request<Body>, next: Next| require_scope(scope, req, next),
+    ))
+}
+
+async fn verify_jwt(
+    state: &AppState,
+    token: &str,
+) -> Result<JwtClaims, (StatusCode, Json<ErrorResponse>)> {
     let claims = state.jwt_manager().verify(token).map_err(|error| {
         error!(?error, "failed to verify JWT");
         unauthorized("invalid or expired token")
     })?;
 
-    req.extensions_mut().insert(claims);
+    let revoked = state
+        .token_repository()
+        .is_access_token_revoked(claims.sub, claims.jti, claims.iat, claims.sid)
+        .await
+        .map_err(|error| {
+            error!(?error, "failed to check token revocation");
+            internal_error()
+        })?;
+    if revoked {
+        return Err(unauthorized("token has been revoked"));
+    }
 
-    Ok(next.run(req).await)
+    if let Some(session_id) = claims.sid {
+        if let Err(error) = state.token_repository().touch_session(session_id).await {
+            error!(?error, %session_id, "failed to update session activity");
+        }
+    }
+
+    Ok(claims)
 }
 
 fn extract_token(headers: &axum::http::HeaderMap) -> Option<&str> {
@@ -43,3 +163,13 @@ fn unauthorized(message: &'static str) -> (StatusCode, Json<ErrorResponse>) {
         Json(ErrorResponse::new("unauthorized", message)),
     )
 }
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "internal_error",
+            "unexpected server error",
+        )),
+    )
+}
@@ -1,9 +1,20 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::Serialize;
 use uuid::Uuid;
 
-use crate::repository::auth::UserRecord;
-use crate::repository::place::{PlaceImageRecord, PlaceRecord};
+use crate::repository::auth::{IdentityRecord, UserRecord};
+use crate::repository::category::CategoryRecord;
+use crate::repository::collection::{
+    CollectionInviteRecord, CollectionMemberRecord, CollectionRecord,
+};
+use crate::repository::comment::{CommentRecord, ReactionCountRecord, ReactionRecord};
+use crate::repository::place::{PlaceClusterRecord, PlaceImageRecord, PlaceRecord, TagRecord};
+use crate::repository::share::ShareLinkRecord;
+use crate::repository::social::{ConnectionRecord, FeedEventRecord};
+use crate::repository::token::{PersonalTokenRecord, SessionRecord};
+use crate::repository::visit::VisitRecord;
+
+use super::users::UPLOADED_AVATAR_PATH;
 
 #[cfg_attr(test, derive(serde::Deserialize))]
 #[derive(Serialize)]
@@ -12,6 +23,10 @@ pub struct UserResponse {
     pub email: Option<String>,
     pub name: Option<String>,
     pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub home_city: Option<String>,
+    /// Profile fields that are no longer refreshed from the OAuth provider on login.
+    pub user_owned_fields: Vec<String>,
 }
 
 impl From<UserRecord> for UserResponse {
@@ -21,10 +36,511 @@ impl From<UserRecord> for UserResponse {
             email: value.email,
             name: value.name,
             avatar_url: value.avatar_url,
+            bio: value.bio,
+            home_city: value.home_city,
+            user_owned_fields: value.user_owned_fields,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct IdentityResponse {
+    pub id: Uuid,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub linked_at: DateTime<Utc>,
+}
+
+impl From<IdentityRecord> for IdentityResponse {
+    fn from(value: IdentityRecord) -> Self {
+        Self {
+            id: value.id,
+            provider: value.provider,
+            provider_user_id: value.provider_user_id,
+            linked_at: value.linked_at,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub provider: String,
+    pub device_label: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// Whether this is the session the request was made with.
+    pub current: bool,
+}
+
+impl SessionResponse {
+    pub fn from_record(value: SessionRecord, current_session: Option<Uuid>) -> Self {
+        Self {
+            current: current_session == Some(value.id),
+            id: value.id,
+            provider: value.provider,
+            device_label: value.device_label,
+            user_agent: value.user_agent,
+            ip_address: value.ip_address,
+            created_at: value.created_at,
+            last_seen_at: value.last_seen_at,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct PersonalTokenResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// The secret itself. Only returned once, when the token is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<PersonalTokenRecord> for PersonalTokenResponse {
+    fn from(value: PersonalTokenRecord) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            scopes: value.scopes,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            last_used_at: value.last_used_at,
+            token: None,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct CategoryResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub color: Option<String>,
+    pub icon_key: Option<String>,
+    pub sort_order: i32,
+    pub place_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<CategoryRecord> for CategoryResponse {
+    fn from(value: CategoryRecord) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            color: value.color,
+            icon_key: value.icon_key,
+            sort_order: value.sort_order,
+            place_count: value.place_count,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct CollectionResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// The requesting user's role: `viewer`, `editor` or `owner`.
+    pub role: String,
+    pub place_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<CollectionRecord> for CollectionResponse {
+    fn from(value: CollectionRecord) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            description: value.description,
+            role: value.role.as_str().to_string(),
+            place_count: value.place_count,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+/// A collection together with its places in their manual order.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct CollectionDetailResponse {
+    #[serde(flatten)]
+    pub collection: CollectionResponse,
+    pub places: Vec<PlaceResponse>,
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct CollectionMemberResponse {
+    pub user_id: Uuid,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub role: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+impl From<CollectionMemberRecord> for CollectionMemberResponse {
+    fn from(value: CollectionMemberRecord) -> Self {
+        Self {
+            user_id: value.user_id,
+            name: value.name,
+            avatar_url: avatar_url_for(value.user_id, value.avatar_url),
+            role: value.role.as_str().to_string(),
+            joined_at: value.joined_at,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct CollectionInviteResponse {
+    pub id: Uuid,
+    pub collection_id: Uuid,
+    pub collection_name: String,
+    pub role: String,
+    /// `null` for invites that are redeemed with a code.
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// The invite code. Only returned once, when a code invite is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+impl From<CollectionInviteRecord> for CollectionInviteResponse {
+    fn from(value: CollectionInviteRecord) -> Self {
+        Self {
+            id: value.id,
+            collection_id: value.collection_id,
+            collection_name: value.collection_name,
+            role: value.role.as_str().to_string(),
+            email: value.email,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            code: None,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct ShareLinkResponse {
+    pub id: Uuid,
+    pub place_id: Option<Uuid>,
+    pub collection_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The secret part of the link. Only returned once, when the link is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<ShareLinkRecord> for ShareLinkResponse {
+    fn from(value: ShareLinkRecord) -> Self {
+        Self {
+            id: value.id,
+            place_id: value.place_id,
+            collection_id: value.collection_id,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            token: None,
+        }
+    }
+}
+
+/// What `GET /shared/{token}` shows of a place. Notes, status, visit history and anything else
+/// that is only meant for the owner are left out.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct SharedPlaceResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub category: String,
+    pub location: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub address: Option<String>,
+    pub tags: Vec<String>,
+    pub images: Vec<SharedImageResponse>,
+}
+
+impl SharedPlaceResponse {
+    /// Image URLs point at the share link, so they can be fetched without logging in.
+    pub fn from_place(place: PlaceResponse, token: &str) -> Self {
+        Self {
+            id: place.id,
+            name: place.name,
+            category: place.category,
+            location: place.location,
+            latitude: place.latitude,
+            longitude: place.longitude,
+            address: place.address,
+            tags: place.tags,
+            images: place
+                .images
+                .into_iter()
+                .map(|image| SharedImageResponse {
+                    download_url: format!("/shared/{token}/images/{}", image.id),
+                    id: image.id,
+                    caption: image.caption,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What `GET /places/{id}` shows of a place the user can only see through its visibility, such
+/// as one opened from the feed. Notes, status, plans, tags and visit history stay with the owner.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct VisiblePlaceResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub category: String,
+    pub location: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub address: Option<String>,
+    /// `friends` or `public`.
+    pub visibility: String,
+    pub images: Vec<SharedImageResponse>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl VisiblePlaceResponse {
+    pub fn from_record(place: PlaceRecord, images: Vec<PlaceImageRecord>) -> Self {
+        Self {
+            images: images
+                .into_iter()
+                .map(|image| SharedImageResponse {
+                    download_url: format!("/places/{}/images/{}", place.id, image.id),
+                    id: image.id,
+                    caption: image.caption,
+                })
+                .collect(),
+            id: place.id,
+            user_id: place.user_id,
+            name: place.name,
+            category: place.category,
+            location: place.location,
+            latitude: place.latitude,
+            longitude: place.longitude,
+            address: place.address,
+            visibility: place.visibility.as_str().to_string(),
+            created_at: place.created_at,
+            updated_at: place.updated_at,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct SharedImageResponse {
+    pub id: Uuid,
+    pub caption: Option<String>,
+    pub download_url: String,
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct SharedCollectionResponse {
+    pub name: String,
+    pub description: Option<String>,
+    pub places: Vec<SharedPlaceResponse>,
+}
+
+/// Read-only view behind a share link.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SharedResponse {
+    Place(SharedPlaceResponse),
+    Collection(SharedCollectionResponse),
+}
+
+/// Someone the user follows, is followed by or has blocked.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct ConnectionResponse {
+    pub user_id: Uuid,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ConnectionRecord> for ConnectionResponse {
+    fn from(value: ConnectionRecord) -> Self {
+        Self {
+            user_id: value.user_id,
+            name: value.name,
+            avatar_url: avatar_url_for(value.user_id, value.avatar_url),
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct UserSummaryResponse {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl UserSummaryResponse {
+    fn new(id: Uuid, name: Option<String>, avatar_url: Option<String>) -> Self {
+        Self {
+            id,
+            name,
+            avatar_url: avatar_url_for(id, avatar_url),
+        }
+    }
+}
+
+/// The avatar URL of a user as others see it. Uploaded avatars are served to other users by
+/// `GET /users/{id}/avatar`, while avatars of OAuth providers are absolute URLs and stay as
+/// they are.
+fn avatar_url_for(user_id: Uuid, avatar_url: Option<String>) -> Option<String> {
+    avatar_url.map(|url| match url.strip_prefix(UPLOADED_AVATAR_PATH) {
+        Some(query) => format!("/users/{user_id}/avatar{query}"),
+        None => url,
+    })
+}
+
+/// What the feed shows of a place. Notes, tags and images stay with the owner.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct FeedPlaceResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub category: String,
+    pub location: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub address: Option<String>,
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct FeedEventResponse {
+    pub id: Uuid,
+    /// `place_added`, `place_visited` or `place_shared`.
+    pub kind: String,
+    pub created_at: DateTime<Utc>,
+    pub user: UserSummaryResponse,
+    pub place: FeedPlaceResponse,
+    /// Set for `place_visited` events, as is the rating when the visit has one.
+    pub visited_on: Option<NaiveDate>,
+    pub rating: Option<i16>,
+}
+
+impl From<FeedEventRecord> for FeedEventResponse {
+    fn from(value: FeedEventRecord) -> Self {
+        Self {
+            id: value.id,
+            kind: value.kind.as_str().to_string(),
+            created_at: value.created_at,
+            user: UserSummaryResponse::new(value.user_id, value.user_name, value.user_avatar_url),
+            place: FeedPlaceResponse {
+                id: value.place_id,
+                name: value.place_name,
+                category: value.category,
+                location: value.location,
+                latitude: value.latitude,
+                longitude: value.longitude,
+                address: value.address,
+            },
+            visited_on: value.visited_on,
+            rating: value.rating,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct CommentResponse {
+    pub id: Uuid,
+    pub place_id: Uuid,
+    pub user: UserSummaryResponse,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<CommentRecord> for CommentResponse {
+    fn from(value: CommentRecord) -> Self {
+        Self {
+            id: value.id,
+            place_id: value.place_id,
+            user: UserSummaryResponse::new(value.user_id, value.user_name, value.user_avatar_url),
+            body: value.body,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct ReactionResponse {
+    pub place_id: Uuid,
+    pub user: UserSummaryResponse,
+    pub emoji: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ReactionRecord> for ReactionResponse {
+    fn from(value: ReactionRecord) -> Self {
+        Self {
+            place_id: value.place_id,
+            user: UserSummaryResponse::new(value.user_id, value.user_name, value.user_avatar_url),
+            emoji: value.emoji,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct ReactionCountResponse {
+    pub emoji: String,
+    pub count: i64,
+}
+
+impl From<ReactionCountRecord> for ReactionCountResponse {
+    fn from(value: ReactionCountRecord) -> Self {
+        Self {
+            emoji: value.emoji,
+            count: value.count,
         }
     }
 }
 
+/// One page of `GET /feed`. `next_cursor` is `null` on the last page.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct FeedPageResponse {
+    pub events: Vec<FeedEventResponse>,
+    pub next_cursor: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct ErrorResponse {
     pub error: &'static str,
@@ -46,10 +562,29 @@ pub struct PlaceResponse {
     pub id: Uuid,
     pub user_id: Uuid,
     pub name: String,
+    pub category_id: Uuid,
     pub category: String,
     pub location: String,
     pub note: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub address: Option<String>,
+    pub place_provider_id: Option<String>,
+    /// `want_to_go`, `visited`, `favorite` or `archived`.
+    pub status: String,
+    pub planned_on: Option<NaiveDate>,
+    pub priority: Option<i16>,
+    /// `private`, `friends` or `public`.
+    pub visibility: String,
+    pub tags: Vec<String>,
     pub images: Vec<PlaceImageResponse>,
+    pub visit_count: i64,
+    pub last_visited_on: Option<NaiveDate>,
+    /// Mean rating of the rated visits.
+    pub average_rating: Option<f64>,
+    pub comment_count: i64,
+    /// Reactions by emoji, most used first.
+    pub reaction_counts: Vec<ReactionCountResponse>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -60,9 +595,103 @@ impl From<PlaceRecord> for PlaceResponse {
             id: value.id,
             user_id: value.user_id,
             name: value.name,
+            category_id: value.category_id,
             category: value.category,
             location: value.location,
             note: value.note,
+            latitude: value.latitude,
+            longitude: value.longitude,
+            address: value.address,
+            place_provider_id: value.place_provider_id,
+            status: value.status.as_str().to_string(),
+            planned_on: value.planned_on,
+            priority: value.priority,
+            visibility: value.visibility.as_str().to_string(),
+            tags: Vec::new(),
+            images: Vec::new(),
+            visit_count: 0,
+            last_visited_on: None,
+            average_rating: None,
+            comment_count: 0,
+            reaction_counts: Vec::new(),
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+/// One page of `GET /places`. `next_cursor` is `null` on the last page.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct PlacePageResponse {
+    pub places: Vec<PlaceResponse>,
+    pub next_cursor: Option<String>,
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct NearbyPlaceResponse {
+    #[serde(flatten)]
+    pub place: PlaceResponse,
+    /// Great-circle distance from the requested point in metres.
+    pub distance_m: f64,
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct PlaceSearchResultResponse {
+    #[serde(flatten)]
+    pub place: PlaceResponse,
+    /// Relevance score. Only meaningful for ordering results of the same search.
+    pub rank: f32,
+    pub snippet: Option<String>,
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct TagResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub place_count: i64,
+}
+
+impl From<TagRecord> for TagResponse {
+    fn from(value: TagRecord) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            place_count: value.place_count,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct VisitResponse {
+    pub id: Uuid,
+    pub place_id: Uuid,
+    pub visited_on: NaiveDate,
+    pub rating: Option<i16>,
+    pub spend_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub companions: Vec<String>,
+    pub note: Option<String>,
+    pub images: Vec<PlaceImageResponse>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<VisitRecord> for VisitResponse {
+    fn from(value: VisitRecord) -> Self {
+        Self {
+            id: value.id,
+            place_id: value.place_id,
+            visited_on: value.visited_on,
+            rating: value.rating,
+            spend_cents: value.spend_cents,
+            currency: value.currency,
+            companions: value.companions,
+            note: value.note,
             images: Vec::new(),
             created_at: value.created_at,
             updated_at: value.updated_at,
@@ -70,10 +699,65 @@ impl From<PlaceRecord> for PlaceResponse {
     }
 }
 
+/// Result of a map viewport query: single markers when zoomed in, clusters otherwise.
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum PlacesInBoundsResponse {
+    Places { places: Vec<MapPlaceResponse> },
+    Clusters { clusters: Vec<PlaceClusterResponse> },
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct MapPlaceResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub category: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl From<PlaceRecord> for MapPlaceResponse {
+    fn from(value: PlaceRecord) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            category: value.category,
+            latitude: value.latitude,
+            longitude: value.longitude,
+        }
+    }
+}
+
+#[cfg_attr(test, derive(serde::Deserialize))]
+#[derive(Serialize)]
+pub struct PlaceClusterResponse {
+    pub count: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub top_categories: Vec<String>,
+    /// The place itself when the cluster holds only one.
+    pub place_id: Option<Uuid>,
+}
+
+impl From<PlaceClusterRecord> for PlaceClusterResponse {
+    fn from(value: PlaceClusterRecord) -> Self {
+        Self {
+            count: value.count,
+            latitude: value.latitude,
+            longitude: value.longitude,
+            top_categories: value.top_categories,
+            place_id: value.place_id,
+        }
+    }
+}
+
 #[cfg_attr(test, derive(serde::Deserialize))]
 #[derive(Serialize)]
 pub struct PlaceImageResponse {
     pub id: Uuid,
+    pub visit_id: Option<Uuid>,
     pub caption: Option<String>,
     pub download_url: String,
     pub created_at: DateTime<Utc>,
@@ -84,6 +768,7 @@ impl PlaceImageResponse {
         let download_url = format!("/places/{}/images/{}", record.place_id, record.id);
         Self {
             id: record.id,
+            visit_id: record.visit_id,
             caption: record.caption,
             download_url,
             created_at: record.created_at,
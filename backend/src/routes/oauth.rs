@@ -1,22 +1,49 @@
+use std::net::SocketAddr;
+
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    routing::post,
+    extract::{rejection::JsonRejection, ConnectInfo, Extension, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::Redirect,
+    routing::{get, post},
     Json, Router,
 };
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 
 use crate::app_state::AppState;
 use crate::auth_service::AuthError;
-use crate::jwt::JwtError;
-use crate::repository::auth::UserRecord;
+use crate::id_token::IdTokenError;
+use crate::jwt::{JwtClaims, JwtError};
+use crate::refresh_token::RefreshTokenError;
+use crate::repository::auth::{NewLoginState, UserRecord};
+use crate::repository::token::NewSession;
 
+use super::middleware::jwt_auth;
 use super::models::{ErrorResponse, UserResponse};
 
+/// How long a user has to finish signing in at the provider after `/start`.
+const LOGIN_STATE_TTL_MINUTES: i64 = 10;
+/// Longest device label, user agent or IP address stored on a session.
+const MAX_SESSION_DETAIL_CHARS: usize = 256;
+
 pub fn router(state: AppState) -> Router {
+    let middleware_state = state.clone();
+
+    let authenticated = Router::new()
+        .route("/auth/logout", post(logout))
+        .route("/auth/logout-all", post(logout_all))
+        .route_layer(middleware::from_fn_with_state(middleware_state, jwt_auth));
+
     Router::new()
-        .route("/auth/:provider/callback", post(complete_callback))
+        .route("/auth/:provider/start", get(start_login))
+        .route(
+            "/auth/:provider/callback",
+            get(complete_redirect_callback).post(complete_callback),
+        )
+        .route("/auth/refresh", post(refresh_session))
+        .merge(authenticated)
         .with_state(state)
 }
 
@@ -29,6 +56,79 @@ struct ProviderPath {
 struct ExchangeRequest {
     code: String,
     code_verifier: Option<String>,
+    nonce: Option<String>,
+    /// Name for the session shown in `GET /usr/sessions`, e.g. "Pixel 8".
+    device_label: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RedirectCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LogoutRequest {
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LogoutAllRequest {
+    before: Option<DateTime<Utc>>,
+}
+
+/// Client details recorded on the session a login creates.
+struct SessionClient {
+    device_label: Option<String>,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl SessionClient {
+    fn from_request(
+        headers: &HeaderMap,
+        peer: Option<ConnectInfo<SocketAddr>>,
+        device_label: Option<String>,
+    ) -> Self {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        // Only shown to the user, so the first X-Forwarded-For hop is good enough behind a proxy.
+        let ip_address = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .or_else(|| peer.map(|ConnectInfo(addr)| addr.ip().to_string()));
+
+        Self {
+            device_label: clean_detail(device_label),
+            user_agent: clean_detail(user_agent),
+            ip_address: clean_detail(ip_address),
+        }
+    }
+}
+
+fn clean_detail(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| {
+            value
+                .trim()
+                .chars()
+                .take(MAX_SESSION_DETAIL_CHARS)
+                .collect::<String>()
+        })
+        .filter(|value| !value.is_empty())
 }
 
 #[cfg_attr(test, derive(Deserialize))]
@@ -36,11 +136,102 @@ struct ExchangeRequest {
 struct LoginResponse {
     user: UserResponse,
     jwt_token: String,
+    refresh_token: String,
+}
+
+async fn start_login(
+    State(state): State<AppState>,
+    Path(path): Path<ProviderPath>,
+) -> Result<Redirect, (StatusCode, Json<ErrorResponse>)> {
+    let Some(service) = state.auth_service(&path.provider) else {
+        info!(provider = %path.provider, "provider not configured");
+        return Err(provider_not_configured(&path.provider));
+    };
+
+    let request = service.authorization_request();
+    state
+        .auth_repository()
+        .insert_login_state(NewLoginState {
+            state: &request.state,
+            provider: &path.provider,
+            code_verifier: &request.code_verifier,
+            nonce: &request.nonce,
+            expires_at: Utc::now() + Duration::minutes(LOGIN_STATE_TTL_MINUTES),
+        })
+        .await
+        .map_err(|error| {
+            error!(?error, "failed to store login state");
+            storage_error()
+        })?;
+
+    info!(provider = %path.provider, "redirecting to provider");
+    Ok(Redirect::to(request.url.as_str()))
+}
+
+/// Provider redirect target for logins started at `/start`. PKCE and nonce come from the stored
+/// login state rather than the client.
+async fn complete_redirect_callback(
+    State(state): State<AppState>,
+    Path(path): Path<ProviderPath>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Query(query): Query<RedirectCallbackQuery>,
+) -> Result<Json<LoginResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if let Some(error) = query.error {
+        info!(provider = %path.provider, %error, "provider rejected authorization");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "authorization_failed",
+                format!("provider returned error '{error}'"),
+            )),
+        ));
+    }
+
+    let (Some(code), Some(login_state)) = (query.code, query.state) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "invalid_request",
+                "code and state are required",
+            )),
+        ));
+    };
+
+    let pending = state
+        .auth_repository()
+        .take_login_state(&login_state, &path.provider)
+        .await
+        .map_err(|error| {
+            error!(?error, "failed to load login state");
+            storage_error()
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "invalid_state",
+                    "login state is unknown, expired or already used",
+                )),
+            )
+        })?;
+
+    finish_login(
+        state,
+        path.provider,
+        code,
+        Some(&pending.code_verifier),
+        Some(&pending.nonce),
+        SessionClient::from_request(&headers, peer, None),
+    )
+    .await
 }
 
 async fn complete_callback(
     State(state): State<AppState>,
     Path(path): Path<ProviderPath>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(payload): Json<ExchangeRequest>,
 ) -> Result<Json<LoginResponse>, (StatusCode, Json<ErrorResponse>)> {
     finish_login(
@@ -48,6 +239,8 @@ async fn complete_callback(
         path.provider,
         payload.code,
         payload.code_verifier.as_deref(),
+        payload.nonce.as_deref(),
+        SessionClient::from_request(&headers, peer, payload.device_label),
     )
     .await
 }
@@ -57,6 +250,8 @@ async fn finish_login(
     provider: String,
     code: String,
     code_verifier: Option<&str>,
+    nonce: Option<&str>,
+    client: SessionClient,
 ) -> Result<Json<LoginResponse>, (StatusCode, Json<ErrorResponse>)> {
     info!(
         %provider,
@@ -70,17 +265,169 @@ async fn finish_login(
     let jwt_manager = state.jwt_manager();
 
     let user = service
-        .complete_oauth_flow(&code, code_verifier)
+        .complete_oauth_flow(&code, code_verifier, nonce)
         .await
         .map_err(map_auth_error)?;
 
-    let jwt_token = jwt_manager.generate(&user).map_err(map_jwt_error)?;
+    let session = state
+        .token_repository()
+        .create_session(NewSession {
+            user_id: user.id,
+            provider: &provider,
+            device_label: client.device_label.as_deref(),
+            user_agent: client.user_agent.as_deref(),
+            ip_address: client.ip_address.as_deref(),
+        })
+        .await
+        .map_err(|error| {
+            error!(?error, "failed to create session");
+            storage_error()
+        })?;
+
+    let jwt_token = jwt_manager
+        .generate(&user, Some(session.id))
+        .map_err(map_jwt_error)?;
+    let refresh_token = state
+        .refresh_token_manager()
+        .issue(user.id, session.id)
+        .await
+        .map_err(map_refresh_token_error)?;
+
+    info!(%provider, user_id = %user.id, session_id = %session.id, "login successful");
+    Ok(Json(LoginResponse::from_user(
+        user,
+        jwt_token,
+        refresh_token,
+    )))
+}
+
+async fn refresh_session(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<LoginResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let rotated = state
+        .refresh_token_manager()
+        .rotate(&payload.refresh_token)
+        .await
+        .map_err(map_refresh_token_error)?;
 
-    info!(%provider, user_id = %user.id, "login successful");
-    Ok(Json(LoginResponse::from_user(user, jwt_token)))
+    let user = state
+        .auth_repository()
+        .find_user_by_id(rotated.user_id)
+        .await
+        .map_err(|err| map_auth_error(AuthError::Storage(err)))?
+        .ok_or_else(|| invalid_refresh_token("refresh token does not belong to an active user"))?;
+
+    let jwt_token = state
+        .jwt_manager()
+        .generate(&user, rotated.session_id)
+        .map_err(map_jwt_error)?;
+
+    if let Some(session_id) = rotated.session_id {
+        if let Err(error) = state.token_repository().touch_session(session_id).await {
+            error!(?error, %session_id, "failed to update session activity");
+        }
+    }
+
+    info!(user_id = %user.id, "refresh token rotated");
+    Ok(Json(LoginResponse::from_user(
+        user,
+        jwt_token,
+        rotated.token,
+    )))
 }
 
-fn provider_not_configured(provider: &str) -> (StatusCode, Json<ErrorResponse>) {
+async fn logout(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    payload: Result<Json<LogoutRequest>, JsonRejection>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let payload = optional_body(payload)?;
+
+    let expires_at = Utc
+        .timestamp_opt(claims.exp, 0)
+        .single()
+        .unwrap_or_else(Utc::now);
+    state
+        .token_repository()
+        .revoke_access_token(claims.jti, claims.sub, expires_at)
+        .await
+        .map_err(|error| {
+            error!(?error, "failed to revoke access token");
+            storage_error()
+        })?;
+
+    if let Some(refresh_token) = payload.refresh_token.as_deref() {
+        state
+            .refresh_token_manager()
+            .revoke(claims.sub, refresh_token)
+            .await
+            .map_err(map_refresh_token_error)?;
+    }
+
+    if let Some(session_id) = claims.sid {
+        state
+            .token_repository()
+            .revoke_session(claims.sub, session_id)
+            .await
+            .map_err(|error| {
+                error!(?error, "failed to revoke session");
+                storage_error()
+            })?;
+    }
+
+    info!(user_id = %claims.sub, jti = %claims.jti, "logged out");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn logout_all(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    payload: Result<Json<LogoutAllRequest>, JsonRejection>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let payload = optional_body(payload)?;
+
+    let now = Utc::now();
+    let before = payload.before.unwrap_or(now);
+    if before > now {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "invalid_request",
+                "before must not be in the future",
+            )),
+        ));
+    }
+
+    state
+        .token_repository()
+        .revoke_all_for_user(claims.sub, before)
+        .await
+        .map_err(|error| {
+            error!(?error, "failed to revoke tokens for user");
+            storage_error()
+        })?;
+
+    info!(user_id = %claims.sub, %before, "logged out everywhere");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Logout bodies are optional: a request without a JSON body gets the defaults, but a body that
+/// does not parse is rejected rather than read as empty.
+fn optional_body<T: Default>(
+    payload: Result<Json<T>, JsonRejection>,
+) -> Result<T, (StatusCode, Json<ErrorResponse>)> {
+    match payload {
+        Ok(Json(payload)) => Ok(payload),
+        Err(JsonRejection::MissingJsonContentType(_)) => Ok(T::default()),
+        Err(err) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request", err.body_text())),
+        )),
+    }
+}
+
+pub(super) fn provider_not_configured(provider: &str) -> (StatusCode, Json<ErrorResponse>) {
     (
         StatusCode::NOT_FOUND,
         Json(ErrorResponse::new(
@@ -90,7 +437,7 @@ fn provider_not_configured(provider: &str) -> (StatusCode, Json<ErrorResponse>)
     )
 }
 
-fn map_auth_error(error: AuthError) -> (StatusCode, Json<ErrorResponse>) {
+pub(super) fn map_auth_error(error: AuthError) -> (StatusCode, Json<ErrorResponse>) {
     error!(?error, "OAuth login failed");
     match error {
         AuthError::TokenExchange(_) => (
@@ -107,6 +454,27 @@ fn map_auth_error(error: AuthError) -> (StatusCode, Json<ErrorResponse>) {
                 "failed to fetch user information from provider",
             )),
         ),
+        AuthError::IdToken(IdTokenError::Jwks(_)) => (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse::new(
+                "jwks_fetch_failed",
+                "failed to fetch signing keys from provider",
+            )),
+        ),
+        AuthError::IdToken(_) => (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse::new(
+                "invalid_id_token",
+                "provider id_token failed verification",
+            )),
+        ),
+        AuthError::MissingIdToken => (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse::new(
+                "missing_id_token",
+                "provider did not return an id_token",
+            )),
+        ),
         AuthError::Storage(_) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse::new(
@@ -128,11 +496,47 @@ fn map_jwt_error(error: JwtError) -> (StatusCode, Json<ErrorResponse>) {
     )
 }
 
+fn map_refresh_token_error(error: RefreshTokenError) -> (StatusCode, Json<ErrorResponse>) {
+    match error {
+        RefreshTokenError::Invalid => invalid_refresh_token("refresh token is invalid"),
+        RefreshTokenError::Expired => invalid_refresh_token("refresh token has expired"),
+        RefreshTokenError::Reused { .. } => (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse::new(
+                "refresh_token_reused",
+                "refresh token was already used; please sign in again",
+            )),
+        ),
+        RefreshTokenError::Storage(error) => {
+            error!(?error, "refresh token storage failure");
+            storage_error()
+        }
+    }
+}
+
+fn storage_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "storage_error",
+            "unexpected storage error",
+        )),
+    )
+}
+
+fn invalid_refresh_token(message: &'static str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(ErrorResponse::new("invalid_refresh_token", message)),
+    )
+}
+
 impl LoginResponse {
-    fn from_user(user: UserRecord, jwt_token: String) -> Self {
+    fn from_user(user: UserRecord, jwt_token: String, refresh_token: String) -> Self {
         Self {
             user: UserResponse::from(user),
             jwt_token,
+            refresh_token,
         }
     }
 }
@@ -144,11 +548,16 @@ mod tests {
     use crate::auth_service::AuthService;
     use crate::db::create_pool;
     use crate::jwt::JwtManager;
-    use crate::oauth_config::OAuthProviderConfig;
+    use crate::refresh_token::RefreshTokenManager;
     use crate::repository::auth::AuthRepository;
     use crate::repository::image_store::ImageStore;
-    use crate::repository::place::PlaceRepository;
+    use crate::repository::token::TokenRepository;
+    use crate::routes::models::SessionResponse;
     use crate::sql_init::run_initialization;
+    use crate::test_utils::oidc::{
+        id_token_claims, jwks, provider_config, provider_metadata, TestSigningKey, TEST_CLIENT_ID,
+    };
+    use crate::test_utils::router::TestContext;
     use axum::body::Body;
     use axum::http::Request;
     use http_body_util::BodyExt;
@@ -156,7 +565,7 @@ mod tests {
     use sqlx::PgPool;
     use std::collections::HashMap;
     use tower::ServiceExt;
-    use wiremock::matchers::{header, method, path};
+    use wiremock::matchers::{body_string_contains, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     const TEST_JWT_SECRET: &str = "jwt-test-secret";
@@ -170,12 +579,20 @@ mod tests {
         let pool = setup_pool().await;
         let mock_server = MockServer::start().await;
         let app = super::router(build_state(&mock_server, pool.clone()));
+        let key = TestSigningKey::generate("key-1");
+
+        let mut claims = id_token_claims(&mock_server.uri(), "google-user-123");
+        claims["email"] = "user@example.com".into();
+        claims["name"] = "Test User".into();
+        claims["picture"] = "https://example.com/avatar.png".into();
+        claims["nonce"] = "login-nonce".into();
 
         let access_token_response = json!({
             "access_token": "mock-access-token",
             "token_type": "Bearer",
             "expires_in": 3600,
-            "refresh_token": "mock-refresh-token"
+            "refresh_token": "mock-refresh-token",
+            "id_token": key.sign(&claims)
         });
 
         Mock::given(method("POST"))
@@ -184,23 +601,16 @@ mod tests {
             .mount(&mock_server)
             .await;
 
-        let user_info_response = json!({
-            "sub": "google-user-123",
-            "email": "user@example.com",
-            "name": "Test User",
-            "picture": "https://example.com/avatar.png"
-        });
-
         Mock::given(method("GET"))
-            .and(path("/userinfo"))
-            .and(header("authorization", "Bearer mock-access-token"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(user_info_response))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(jwks(&[&key])))
             .mount(&mock_server)
             .await;
 
         let payload = json!({
             "code": "auth-code",
-            "code_verifier": "verifier"
+            "code_verifier": "verifier",
+            "nonce": "login-nonce"
         });
 
         let response = app
@@ -248,6 +658,477 @@ mod tests {
                 .await
                 .expect("stored identity");
         assert_eq!(identities, "google-user-123");
+        assert!(!parsed.refresh_token.is_empty());
+
+        let stored_hash: String =
+            sqlx::query_scalar("SELECT token_hash FROM refresh_tokens WHERE user_id = $1")
+                .bind(parsed.user.id)
+                .fetch_one(&pool)
+                .await
+                .expect("stored refresh token");
+        assert_ne!(
+            stored_hash, parsed.refresh_token,
+            "refresh token must be stored hashed"
+        );
+    }
+
+    #[tokio::test]
+    async fn start_redirects_to_provider_and_get_callback_completes_login() {
+        let pool = setup_pool().await;
+        let mock_server = MockServer::start().await;
+        let app = super::router(build_state(&mock_server, pool.clone()));
+
+        let location = start_login(&app, "google").await;
+        assert_eq!(location.path(), "/auth");
+        let params: HashMap<String, String> = location.query_pairs().into_owned().collect();
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], TEST_CLIENT_ID);
+        assert_eq!(params["redirect_uri"], "https://example.com/callback");
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert!(params["scope"].split(' ').any(|scope| scope == "openid"));
+
+        // The verifier stays on the server and is only sent in the token exchange.
+        let code_verifier: String =
+            sqlx::query_scalar("SELECT code_verifier FROM oauth_login_states WHERE state = $1")
+                .bind(&params["state"])
+                .fetch_one(&pool)
+                .await
+                .expect("stored login state");
+        assert!(!location.as_str().contains(&code_verifier));
+
+        let key = TestSigningKey::generate("key-1");
+        let mut claims = id_token_claims(&mock_server.uri(), "google-user-123");
+        claims["email"] = "user@example.com".into();
+        claims["nonce"] = params["nonce"].clone().into();
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains(format!(
+                "code_verifier={code_verifier}"
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "mock-access-token",
+                "token_type": "Bearer",
+                "id_token": key.sign(&claims)
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(jwks(&[&key])))
+            .mount(&mock_server)
+            .await;
+
+        let callback = format!(
+            "/auth/google/callback?code=auth-code&state={}",
+            params["state"]
+        );
+        let (status, body) = get_json(&app, &callback).await;
+        assert_eq!(status, StatusCode::OK);
+        let login: LoginResponse = serde_json::from_value(body).expect("login response");
+        assert_eq!(login.user.email.as_deref(), Some("user@example.com"));
+
+        let (status, body) = get_json(&app, &callback).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_state");
+    }
+
+    #[tokio::test]
+    async fn get_callback_rejects_unknown_expired_and_foreign_state() {
+        let pool = setup_pool().await;
+        let mock_server = MockServer::start().await;
+        let app = super::router(build_state(&mock_server, pool.clone()));
+
+        let (status, body) = get_json(&app, "/auth/google/callback?code=c&state=unknown").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_state");
+
+        let (status, body) = get_json(&app, "/auth/google/callback?error=access_denied").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "authorization_failed");
+
+        // A state issued for one provider cannot complete a login with another.
+        let state = query_param(&start_login(&app, "google").await, "state");
+        let (status, body) =
+            get_json(&app, &format!("/auth/other/callback?code=c&state={state}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_state");
+        let pending: i64 =
+            sqlx::query_scalar("SELECT count(*) FROM oauth_login_states WHERE state = $1")
+                .bind(&state)
+                .fetch_one(&pool)
+                .await
+                .expect("count login states");
+        assert_eq!(
+            pending, 1,
+            "a callback at the wrong provider must not burn the state"
+        );
+
+        let state = query_param(&start_login(&app, "google").await, "state");
+        sqlx::query("UPDATE oauth_login_states SET expires_at = NOW() - INTERVAL '1 minute'")
+            .execute(&pool)
+            .await
+            .expect("expire login states");
+        let (status, body) =
+            get_json(&app, &format!("/auth/google/callback?code=c&state={state}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_state");
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_token_and_detects_reuse() {
+        let pool = setup_pool().await;
+        let mock_server = MockServer::start().await;
+        let app = super::router(build_state(&mock_server, pool.clone()));
+        mount_provider_mocks(&mock_server).await;
+
+        let login = login(&app).await;
+
+        let (status, rotated) = refresh(&app, &login.refresh_token).await;
+        assert_eq!(status, StatusCode::OK);
+        let rotated: LoginResponse = serde_json::from_value(rotated).expect("login response");
+        assert_eq!(rotated.user.id, login.user.id);
+        assert_ne!(rotated.refresh_token, login.refresh_token);
+        let jwt = JwtManager::new(TEST_JWT_SECRET.to_string(), 3600);
+        assert_eq!(
+            jwt.verify(&rotated.jwt_token).expect("valid jwt").sub,
+            login.user.id
+        );
+
+        // Presenting the already-rotated token again is treated as theft.
+        let (status, body) = refresh(&app, &login.refresh_token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "refresh_token_reused");
+
+        // The whole family is revoked, including the token issued by the rotation.
+        let (status, body) = refresh(&app, &rotated.refresh_token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "invalid_refresh_token");
+
+        let active: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM refresh_tokens WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(login.user.id)
+        .fetch_one(&pool)
+        .await
+        .expect("count tokens");
+        assert_eq!(active, 0);
+    }
+
+    #[tokio::test]
+    async fn refresh_rejects_unknown_and_expired_tokens() {
+        let pool = setup_pool().await;
+        let mock_server = MockServer::start().await;
+        let app = super::router(build_state(&mock_server, pool.clone()));
+        mount_provider_mocks(&mock_server).await;
+
+        let (status, body) = refresh(&app, "not-a-real-token").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "invalid_refresh_token");
+
+        let login = login(&app).await;
+        sqlx::query("UPDATE refresh_tokens SET expires_at = NOW() - INTERVAL '1 minute'")
+            .execute(&pool)
+            .await
+            .expect("expire tokens");
+
+        let (status, body) = refresh(&app, &login.refresh_token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "invalid_refresh_token");
+    }
+
+    #[tokio::test]
+    async fn revoking_a_session_signs_only_that_device_out() {
+        let pool = setup_pool().await;
+        let mock_server = MockServer::start().await;
+        let state = build_state(&mock_server, pool.clone());
+        let app = super::router(state.clone()).merge(crate::routes::users::router(state));
+        mount_provider_mocks(&mock_server).await;
+
+        let phone = login_from_device(&app, "Pixel 8").await;
+        let laptop = login_from_device(&app, "Work laptop").await;
+
+        let (status, body) = get_json_with_token(&app, "/usr/sessions", &phone.jwt_token).await;
+        assert_eq!(status, StatusCode::OK);
+        let sessions: Vec<SessionResponse> = serde_json::from_value(body).expect("sessions");
+        assert_eq!(sessions.len(), 2);
+        let current = sessions
+            .iter()
+            .find(|s| s.current)
+            .expect("current session");
+        assert_eq!(current.device_label.as_deref(), Some("Pixel 8"));
+        assert_eq!(current.provider, "google");
+        assert_eq!(current.user_agent.as_deref(), Some("local-guide-tests/1.0"));
+        assert_eq!(current.ip_address.as_deref(), Some("203.0.113.7"));
+        let laptop_session = sessions.iter().find(|s| !s.current).expect("other session");
+
+        let uri = format!("/usr/sessions/{}", laptop_session.id);
+        assert_eq!(
+            delete_with_token(&app, &uri, &phone.jwt_token).await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            delete_with_token(&app, &uri, &phone.jwt_token).await,
+            StatusCode::NOT_FOUND
+        );
+
+        let (status, _) = get_json_with_token(&app, "/usr", &laptop.jwt_token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = refresh(&app, &laptop.refresh_token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // The phone keeps working, and refreshed tokens stay bound to its session.
+        let (status, rotated) = refresh(&app, &phone.refresh_token).await;
+        assert_eq!(status, StatusCode::OK);
+        let rotated: LoginResponse = serde_json::from_value(rotated).expect("login response");
+        let jwt = JwtManager::new(TEST_JWT_SECRET.to_string(), 3600);
+        assert_eq!(
+            jwt.verify(&rotated.jwt_token).expect("valid jwt").sid,
+            Some(current.id)
+        );
+        let (status, _) = get_json_with_token(&app, "/usr", &rotated.jwt_token).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_presented_token_only() {
+        let ctx = TestContext::new(|state| {
+            super::router(state.clone()).merge(crate::routes::users::router(state))
+        })
+        .await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+        let other_device = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let response = ctx
+            .app
+            .clone()
+            .oneshot(
+                Request::post("/auth/logout")
+                    .header("Authorization", format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("logout request");
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        assert_eq!(get_profile(&ctx, &token).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get_profile(&ctx, &other_device).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn logout_all_rejects_tokens_issued_before_cutoff() {
+        let ctx = TestContext::new(|state| {
+            super::router(state.clone()).merge(crate::routes::users::router(state))
+        })
+        .await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let future = chrono::Utc::now() + chrono::Duration::minutes(5);
+        let response = logout_all(&ctx, &token, json!({ "before": future })).await;
+        assert_eq!(response, StatusCode::BAD_REQUEST);
+
+        // A cutoff in the past leaves tokens issued after it untouched.
+        let before = chrono::Utc::now() - chrono::Duration::minutes(5);
+        let response = logout_all(&ctx, &token, json!({ "before": before })).await;
+        assert_eq!(response, StatusCode::NO_CONTENT);
+        assert_eq!(get_profile(&ctx, &token).await, StatusCode::OK);
+
+        // A malformed cutoff is rejected instead of revoking everything.
+        let response = logout_all(&ctx, &token, json!({ "before": "yesterday" })).await;
+        assert_eq!(response, StatusCode::BAD_REQUEST);
+        assert_eq!(get_profile(&ctx, &token).await, StatusCode::OK);
+
+        let response = logout_all(&ctx, &token, json!({})).await;
+        assert_eq!(response, StatusCode::NO_CONTENT);
+        assert_eq!(get_profile(&ctx, &token).await, StatusCode::UNAUTHORIZED);
+
+        // A login right after, even within the same second, is not caught by the cutoff.
+        let session = TokenRepository::new(ctx.pool.clone())
+            .create_session(NewSession {
+                user_id: user.id,
+                provider: "google",
+                device_label: None,
+                user_agent: None,
+                ip_address: None,
+            })
+            .await
+            .expect("session");
+        let token = ctx.jwt.generate(&user, Some(session.id)).expect("jwt");
+        assert_eq!(get_profile(&ctx, &token).await, StatusCode::OK);
+    }
+
+    async fn get_profile(ctx: &TestContext, token: &str) -> StatusCode {
+        ctx.app
+            .clone()
+            .oneshot(
+                Request::get("/usr")
+                    .header("Authorization", format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("profile request")
+            .status()
+    }
+
+    async fn logout_all(ctx: &TestContext, token: &str, payload: serde_json::Value) -> StatusCode {
+        ctx.app
+            .clone()
+            .oneshot(
+                Request::post("/auth/logout-all")
+                    .header("Authorization", format!("Bearer {token}"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("logout-all request")
+            .status()
+    }
+
+    async fn mount_provider_mocks(mock_server: &MockServer) {
+        let key = TestSigningKey::generate("key-1");
+        let mut claims = id_token_claims(&mock_server.uri(), "google-user-123");
+        claims["email"] = "user@example.com".into();
+        claims["name"] = "Test User".into();
+
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "mock-access-token",
+                "token_type": "Bearer",
+                "id_token": key.sign(&claims)
+            })))
+            .mount(mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(jwks(&[&key])))
+            .mount(mock_server)
+            .await;
+    }
+
+    async fn login(app: &axum::Router) -> LoginResponse {
+        let payload = json!({ "code": "auth-code", "code_verifier": "verifier" });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/auth/google/callback")
+                    .header("content-type", "application/json")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("login request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).expect("login response")
+    }
+
+    async fn start_login(app: &axum::Router, provider: &str) -> reqwest::Url {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(format!("/auth/{provider}/start"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("start request");
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response.headers()["location"].to_str().expect("location");
+        reqwest::Url::parse(location).expect("absolute redirect")
+    }
+
+    fn query_param(url: &reqwest::Url, name: &str) -> String {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .expect("query param")
+    }
+
+    async fn get_json(app: &axum::Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .expect("get request");
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).expect("json body"))
+    }
+
+    async fn login_from_device(app: &axum::Router, device_label: &str) -> LoginResponse {
+        let payload = json!({ "code": "auth-code", "device_label": device_label });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/auth/google/callback")
+                    .header("content-type", "application/json")
+                    .header("user-agent", "local-guide-tests/1.0")
+                    .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("login request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).expect("login response")
+    }
+
+    async fn get_json_with_token(
+        app: &axum::Router,
+        uri: &str,
+        token: &str,
+    ) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(uri)
+                    .header("Authorization", format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("get request");
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+        (status, body)
+    }
+
+    async fn delete_with_token(app: &axum::Router, uri: &str, token: &str) -> StatusCode {
+        app.clone()
+            .oneshot(
+                Request::delete(uri)
+                    .header("Authorization", format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("delete request")
+            .status()
+    }
+
+    async fn refresh(app: &axum::Router, refresh_token: &str) -> (StatusCode, serde_json::Value) {
+        let payload = json!({ "refresh_token": refresh_token });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/auth/refresh")
+                    .header("content-type", "application/json")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("refresh request");
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).expect("json body"))
     }
 
     async fn setup_pool() -> PgPool {
@@ -263,7 +1144,7 @@ mod tests {
         run_initialization(&pool).await.expect("apply schema");
 
         sqlx::query(
-            "TRUNCATE TABLE place_images, places, oauth_identities, users RESTART IDENTITY",
+            "TRUNCATE TABLE place_images, places, oauth_identities, users RESTART IDENTITY CASCADE",
         )
         .execute(&pool)
         .await
@@ -274,26 +1155,22 @@ mod tests {
 
     fn build_state(mock_server: &MockServer, pool: PgPool) -> AppState {
         let repository = AuthRepository::new(pool.clone());
-        let place_repository = PlaceRepository::new(pool);
-        let config = OAuthProviderConfig {
-            provider_id: "google".to_string(),
-            client_id: "client-id".to_string(),
-            auth_url: format!("{}/auth", mock_server.uri()),
-            token_url: format!("{}/token", mock_server.uri()),
-            userinfo_url: format!("{}/userinfo", mock_server.uri()),
-            redirect_uri: "https://example.com/callback".to_string(),
-        };
-
-        let service = AuthService::new(repository.clone(), config)
-            .expect("initialize auth service for tests");
+        let token_repository = TokenRepository::new(pool.clone());
+        let service = AuthService::new(
+            repository.clone(),
+            provider_config(&mock_server.uri()),
+            provider_metadata(&mock_server.uri()),
+        )
+        .expect("initialize auth service for tests");
 
         let mut providers = HashMap::new();
         providers.insert("google".to_string(), service);
         AppState::new(
             providers,
             JwtManager::new(TEST_JWT_SECRET.to_string(), 3600),
+            RefreshTokenManager::new(token_repository, 3600),
             repository,
-            place_repository,
+            pool,
             ImageStore::new(temp_image_dir()).expect("image store"),
         )
     }
@@ -1,26 +1,56 @@
 use std::collections::VecDeque;
 
 use axum::{
-    extract::{multipart::Multipart, DefaultBodyLimit, Extension, Path as AxumPath, State},
+    extract::{
+        multipart::Multipart, rejection::QueryRejection, DefaultBodyLimit, Extension,
+        Path as AxumPath, Query, State,
+    },
     http::{header, HeaderMap, HeaderValue, StatusCode},
     middleware,
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{get, patch, post},
     Json, Router,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, NaiveDate, Utc};
 use mime_guess::mime;
+use serde::{Deserialize, Serialize};
 use tracing::error;
 use uuid::Uuid;
 
 use crate::app_state::AppState;
-use crate::jwt::JwtClaims;
+use crate::geo::{cluster_cell_degrees, BoundingBox};
+use crate::personal_token::Scope;
+use crate::repository::category::CategoryRecord;
 use crate::repository::image_store::ImageUpload;
 use crate::repository::place::{
-    NewPlace, NewPlaceImage, PlaceRecord, PlaceRepository, PlaceRepositoryError, UpdatePlace,
+    NewPlace, NewPlaceImage, PlaceCursor, PlaceListQuery, PlaceRecord, PlaceRepository,
+    PlaceRepositoryError, PlaceSort, PlaceSortKey, PlaceStatus, PlaceVisibility, UpdatePlace,
 };
 
-use super::middleware::jwt_auth;
-use super::models::{ErrorResponse, PlaceImageResponse, PlaceResponse};
+use super::categories::{MAX_CATEGORY_NAME_CHARS, UNCATEGORIZED};
+use super::middleware::{api_auth, with_scope, AuthUser};
+use super::models::{
+    ErrorResponse, MapPlaceResponse, NearbyPlaceResponse, PlaceClusterResponse, PlaceImageResponse,
+    PlacePageResponse, PlaceResponse, PlaceSearchResultResponse, PlacesInBoundsResponse,
+    ReactionCountResponse, TagResponse, VisiblePlaceResponse,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+const MAX_TAGS_PER_PLACE: usize = 20;
+const MAX_TAG_CHARS: usize = 50;
+const DEFAULT_SEARCH_RESULTS: i64 = 20;
+const MAX_SEARCH_RESULTS: i64 = 50;
+const MAX_SEARCH_QUERY_CHARS: usize = 200;
+const MAX_NEARBY_RADIUS_M: f64 = 100_000.0;
+const MAX_MAP_ZOOM: f64 = 22.0;
+/// Viewports at this zoom level or below are always clustered.
+const CLUSTER_MAX_ZOOM: u8 = 14;
+/// Above this many places a viewport is clustered even when zoomed in.
+const MAX_MAP_PLACES: usize = 500;
+const CLUSTER_TOP_CATEGORIES: i32 = 3;
 
 // The default Axum body limit is 2MB, which is too small for typical phone photos.
 const MAX_MULTIPART_SIZE_BYTES: usize = 25 * 1024 * 1024;
@@ -28,15 +58,24 @@ const MAX_MULTIPART_SIZE_BYTES: usize = 25 * 1024 * 1024;
 pub fn router(state: AppState) -> Router {
     let middleware_state = state.clone();
 
-    Router::new()
-        .route("/places", post(create_place).get(list_places))
-        .route(
-            "/places/:id",
-            get(get_place).patch(update_place).delete(delete_place),
-        )
+    let read = Router::new()
+        .route("/places", get(list_places))
+        .route("/places/nearby", get(list_nearby_places))
+        .route("/places/in-bounds", get(list_places_in_bounds))
+        .route("/places/search", get(search_places))
+        .route("/tags", get(list_tags))
+        .route("/places/:id", get(get_place));
+    let write = Router::new()
+        .route("/places", post(create_place))
+        .route("/places/:id", patch(update_place).delete(delete_place));
+    let images = Router::new()
         .route("/places/:id/images", get(list_images))
-        .route("/places/:place_id/images/:image_id", get(get_place_image))
-        .route_layer(middleware::from_fn_with_state(middleware_state, jwt_auth))
+        .route("/places/:place_id/images/:image_id", get(get_place_image));
+
+    with_scope(read, Scope::PlacesRead)
+        .merge(with_scope(write, Scope::PlacesWrite))
+        .merge(with_scope(images, Scope::ImagesRead))
+        .route_layer(middleware::from_fn_with_state(middleware_state, api_auth))
         .layer(DefaultBodyLimit::max(MAX_MULTIPART_SIZE_BYTES))
         .with_state(state)
 }
@@ -45,9 +84,19 @@ pub fn router(state: AppState) -> Router {
 struct IncomingPlace {
     id: Option<Uuid>,
     name: Option<String>,
+    category_id: Option<String>,
     category: Option<String>,
     location: Option<String>,
     note: Option<String>,
+    latitude: Option<String>,
+    longitude: Option<String>,
+    address: Option<String>,
+    place_provider_id: Option<String>,
+    status: Option<PlaceStatus>,
+    planned_on: Option<NaiveDate>,
+    priority: Option<i16>,
+    visibility: Option<PlaceVisibility>,
+    tags: Option<Vec<String>>,
     images: Vec<IncomingImage>,
 }
 
@@ -59,7 +108,7 @@ struct IncomingImage {
 
 async fn create_place(
     State(state): State<AppState>,
-    Extension(claims): Extension<JwtClaims>,
+    Extension(user): Extension<AuthUser>,
     mut multipart: Multipart,
 ) -> Result<Json<PlaceResponse>, (StatusCode, Json<ErrorResponse>)> {
     let mut form = IncomingPlace::default();
@@ -96,6 +145,12 @@ async fn create_place(
                         .to_string(),
                 );
             }
+            Some("tags") => {
+                form.tags = Some(parse_tags(&read_text_field(field, "tags").await?)?);
+            }
+            Some("category_id") => {
+                form.category_id = Some(read_text_field(field, "category_id").await?);
+            }
             Some("category") => {
                 form.category = Some(
                     field
@@ -135,6 +190,26 @@ async fn create_place(
                         .to_string(),
                 );
             }
+            Some("latitude") => form.latitude = Some(read_text_field(field, "latitude").await?),
+            Some("longitude") => form.longitude = Some(read_text_field(field, "longitude").await?),
+            Some("address") => form.address = Some(read_text_field(field, "address").await?),
+            Some("place_provider_id") => {
+                form.place_provider_id = Some(read_text_field(field, "place_provider_id").await?)
+            }
+            Some("status") => {
+                form.status = Some(parse_status(&read_text_field(field, "status").await?)?)
+            }
+            Some("planned_on") => {
+                form.planned_on = parse_planned_on(&read_text_field(field, "planned_on").await?)?
+            }
+            Some("priority") => {
+                form.priority = parse_priority(&read_text_field(field, "priority").await?)?
+            }
+            Some("visibility") => {
+                form.visibility = Some(parse_visibility(
+                    &read_text_field(field, "visibility").await?,
+                )?)
+            }
             Some("image") => {
                 let file_name = field.file_name().map(|value| value.to_owned());
                 let bytes = field.bytes().await.map_err(|err| {
@@ -174,8 +249,26 @@ async fn create_place(
 
     let place_id = form.id.ok_or_else(|| missing_field("id"))?;
     let name = form.name.ok_or_else(|| missing_field("name"))?;
-    let category = form.category.ok_or_else(|| missing_field("category"))?;
     let location = form.location.ok_or_else(|| missing_field("location"))?;
+    let coordinates = parse_coordinates(form.latitude.as_deref(), form.longitude.as_deref())?;
+    let category = match resolve_category(
+        &state,
+        user.id,
+        form.category_id.as_deref(),
+        form.category.as_deref(),
+    )
+    .await?
+    {
+        Some(category) => category,
+        None => state
+            .category_repository()
+            .find_or_create_by_name(user.id, UNCATEGORIZED)
+            .await
+            .map_err(|err| {
+                error!(?err, "failed to resolve default category");
+                internal_error()
+            })?,
+    };
 
     let repository = state.place_repository();
     let image_store = state.image_store();
@@ -191,11 +284,21 @@ async fn create_place(
 
     let new_place = NewPlace {
         id: place_id,
-        user_id: claims.sub,
+        user_id: user.id,
         name: &name,
-        category: &category,
+        category_id: category.id,
+        category: &category.name,
         location: &location,
         note: form.note.as_deref(),
+        latitude: coordinates.as_ref().map(|point| point.latitude),
+        longitude: coordinates.as_ref().map(|point| point.longitude),
+        address: non_empty(form.address.as_deref()),
+        place_provider_id: non_empty(form.place_provider_id.as_deref()),
+        status: form.status.unwrap_or_default(),
+        planned_on: form.planned_on,
+        priority: form.priority,
+        visibility: form.visibility.unwrap_or_default(),
+        tags: form.tags.as_deref().unwrap_or_default(),
     };
 
     let image_payloads: Vec<NewPlaceImage<'_>> = stored_images
@@ -208,7 +311,7 @@ async fn create_place(
         })
         .collect();
 
-    let (record, inserted_images) = match repository
+    let (record, _inserted_images) = match repository
         .create_place_with_images(new_place, &image_payloads)
         .await
     {
@@ -220,86 +323,529 @@ async fn create_place(
         }
     };
 
-    Ok(Json(enrich_place(
-        record,
-        inserted_images
-            .into_iter()
-            .map(PlaceImageResponse::from_record)
-            .collect(),
-    )))
+    Ok(Json(place_response(&state, user.id, record).await?))
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    limit: Option<i64>,
+    cursor: Option<String>,
+    category_id: Option<Uuid>,
+    category: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    updated_after: Option<DateTime<Utc>>,
+    updated_before: Option<DateTime<Utc>>,
+    has_images: Option<bool>,
+    /// Comma-separated statuses.
+    status: Option<String>,
+    planned_after: Option<NaiveDate>,
+    planned_before: Option<NaiveDate>,
+    /// Comma-separated tag names.
+    tag: Option<String>,
+    #[serde(default)]
+    tag_match: TagMatch,
+    #[serde(default)]
+    sort: SortField,
+    order: Option<SortOrder>,
+    lat: Option<f64>,
+    lng: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Name,
+    Distance,
 }
 
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Contents of the opaque `cursor` handed to clients. The sort and order are included so a
+/// cursor cannot be replayed against a listing it does not belong to.
+#[derive(Serialize, Deserialize)]
+struct CursorToken {
+    sort: SortField,
+    descending: bool,
+    key: String,
+    id: Uuid,
+}
+
+/// Without `limit` or `cursor` every place is returned as a bare array, as older clients expect.
+/// With either, a single page is returned together with the cursor of the next one.
 async fn list_places(
     State(state): State<AppState>,
-    Extension(claims): Extension<JwtClaims>,
-) -> Result<Json<Vec<PlaceResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    Extension(user): Extension<AuthUser>,
+    query: Result<Query<ListQuery>, QueryRejection>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let Query(query) = query.map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request", err.body_text())),
+        )
+    })?;
+
+    let sort = match query.sort {
+        SortField::CreatedAt => PlaceSort::CreatedAt,
+        SortField::UpdatedAt => PlaceSort::UpdatedAt,
+        SortField::Name => PlaceSort::Name,
+        SortField::Distance => match (query.lat, query.lng) {
+            (Some(latitude), Some(longitude)) => {
+                if !latitude.is_finite() || latitude.abs() > 90.0 {
+                    return Err(bad_request("lat must be between -90 and 90"));
+                }
+                if !longitude.is_finite() || longitude.abs() > 180.0 {
+                    return Err(bad_request("lng must be between -180 and 180"));
+                }
+                PlaceSort::Distance {
+                    latitude,
+                    longitude,
+                }
+            }
+            _ => return Err(bad_request("sort=distance requires lat and lng")),
+        },
+    };
+    let descending = match query.order {
+        Some(order) => matches!(order, SortOrder::Desc),
+        None => matches!(query.sort, SortField::CreatedAt | SortField::UpdatedAt),
+    };
+
+    let paginated = query.limit.is_some() || query.cursor.is_some();
+    let limit = match query.limit {
+        Some(limit) if !(1..=MAX_PAGE_SIZE).contains(&limit) => {
+            return Err(bad_request("limit must be between 1 and 100"));
+        }
+        Some(limit) => limit,
+        None => DEFAULT_PAGE_SIZE,
+    };
+    let after = query
+        .cursor
+        .as_deref()
+        .map(|cursor| decode_cursor(cursor, query.sort, descending))
+        .transpose()?;
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in query.tag.as_deref().unwrap_or_default().split(',') {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    let mut statuses: Vec<PlaceStatus> = Vec::new();
+    for status in query.status.as_deref().unwrap_or_default().split(',') {
+        let status = status.trim();
+        if status.is_empty() {
+            continue;
+        }
+        let status = parse_status(status)?;
+        if !statuses.contains(&status) {
+            statuses.push(status);
+        }
+    }
+
+    let list_query = PlaceListQuery {
+        category_id: query.category_id,
+        category: non_empty(query.category.as_deref()),
+        created_after: query.created_after,
+        created_before: query.created_before,
+        updated_after: query.updated_after,
+        updated_before: query.updated_before,
+        has_images: query.has_images,
+        statuses: &statuses,
+        planned_after: query.planned_after,
+        planned_before: query.planned_before,
+        tags: &tags,
+        all_tags: matches!(query.tag_match, TagMatch::All),
+        sort,
+        descending,
+        after,
+        // One extra row tells whether another page follows.
+        limit: paginated.then_some(limit + 1),
+    };
+
     let repository = state.place_repository();
-    let places = repository.list_for_user(claims.sub).await.map_err(|err| {
-        error!(?err, "failed to list places");
-        internal_error()
+    let mut places = repository
+        .list_for_user(user.id, &list_query)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list places");
+            internal_error()
+        })?;
+
+    let next_cursor = if paginated && places.len() as i64 > limit {
+        places.truncate(limit as usize);
+        places
+            .last()
+            .map(|last| encode_cursor(&last.cursor(sort), query.sort, descending))
+    } else {
+        None
+    };
+
+    let places = places.into_iter().map(|listed| listed.place).collect();
+    let responses = place_responses(&state, user.id, places).await?;
+
+    if paginated {
+        Ok(Json(PlacePageResponse {
+            places: responses,
+            next_cursor,
+        })
+        .into_response())
+    } else {
+        Ok(Json(responses).into_response())
+    }
+}
+
+fn encode_cursor(cursor: &PlaceCursor, sort: SortField, descending: bool) -> String {
+    let key = match &cursor.key {
+        PlaceSortKey::Time(value) => value.to_rfc3339(),
+        PlaceSortKey::Name(value) => value.clone(),
+        PlaceSortKey::Distance(value) => value.to_string(),
+    };
+    let token = CursorToken {
+        sort,
+        descending,
+        key,
+        id: cursor.id,
+    };
+    let json = serde_json::to_vec(&token).expect("cursor serializes");
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+fn decode_cursor(
+    cursor: &str,
+    sort: SortField,
+    descending: bool,
+) -> Result<PlaceCursor, (StatusCode, Json<ErrorResponse>)> {
+    let invalid = || {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "invalid_cursor",
+                "cursor is malformed or belongs to a different sort order",
+            )),
+        )
+    };
+
+    let token = URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice::<CursorToken>(&json).ok())
+        .filter(|token| token.sort == sort && token.descending == descending)
+        .ok_or_else(invalid)?;
+
+    let key = match sort {
+        SortField::CreatedAt | SortField::UpdatedAt => DateTime::parse_from_rfc3339(&token.key)
+            .map(|value| PlaceSortKey::Time(value.with_timezone(&Utc)))
+            .map_err(|_| invalid())?,
+        SortField::Name => PlaceSortKey::Name(token.key),
+        SortField::Distance => token
+            .key
+            .parse()
+            .map(PlaceSortKey::Distance)
+            .map_err(|_| invalid())?,
+    };
+
+    Ok(PlaceCursor { key, id: token.id })
+}
+
+async fn list_tags(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<Vec<TagResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let tags = state
+        .place_repository()
+        .list_tags_for_user(user.id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list tags");
+            internal_error()
+        })?;
+
+    Ok(Json(tags.into_iter().map(TagResponse::from).collect()))
+}
+
+#[derive(Deserialize)]
+struct NearbyQuery {
+    lat: f64,
+    lng: f64,
+    radius_m: f64,
+}
+
+async fn list_nearby_places(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    query: Result<Query<NearbyQuery>, QueryRejection>,
+) -> Result<Json<Vec<NearbyPlaceResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let Query(query) = query.map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request", err.body_text())),
+        )
+    })?;
+    if !query.lat.is_finite() || query.lat.abs() > 90.0 {
+        return Err(bad_request("lat must be between -90 and 90"));
+    }
+    if !query.lng.is_finite() || query.lng.abs() > 180.0 {
+        return Err(bad_request("lng must be between -180 and 180"));
+    }
+    if !(query.radius_m > 0.0 && query.radius_m <= MAX_NEARBY_RADIUS_M) {
+        return Err(bad_request("radius_m must be between 0 and 100000"));
+    }
+
+    let repository = state.place_repository();
+    let places = repository
+        .list_nearby_for_user(user.id, query.lat, query.lng, query.radius_m)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list nearby places");
+            internal_error()
+        })?;
+
+    let distances: Vec<f64> = places.iter().map(|nearby| nearby.distance_m).collect();
+    let places = places.into_iter().map(|nearby| nearby.place).collect();
+    let responses = place_responses(&state, user.id, places)
+        .await?
+        .into_iter()
+        .zip(distances)
+        .map(|(place, distance_m)| NearbyPlaceResponse { place, distance_m })
+        .collect();
+
+    Ok(Json(responses))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    limit: Option<i64>,
+}
+
+async fn search_places(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    query: Result<Query<SearchQuery>, QueryRejection>,
+) -> Result<Json<Vec<PlaceSearchResultResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let Query(query) = query.map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request", err.body_text())),
+        )
+    })?;
+    let limit = query.limit.unwrap_or(DEFAULT_SEARCH_RESULTS);
+    if !(1..=MAX_SEARCH_RESULTS).contains(&limit) {
+        return Err(bad_request("limit must be between 1 and 50"));
+    }
+    if query.q.chars().count() > MAX_SEARCH_QUERY_CHARS {
+        return Err(bad_request("q must be at most 200 characters"));
+    }
+    let Some(ts_query) = prefix_ts_query(&query.q) else {
+        return Err(bad_request("q must contain at least one letter or digit"));
+    };
+
+    let repository = state.place_repository();
+    let results = repository
+        .search_for_user(user.id, &ts_query, query.q.trim(), limit)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to search places");
+            internal_error()
+        })?;
+
+    let mut scores = Vec::with_capacity(results.len());
+    let mut places = Vec::with_capacity(results.len());
+    for result in results {
+        scores.push((result.rank, result.snippet));
+        places.push(result.place);
+    }
+    let responses = place_responses(&state, user.id, places)
+        .await?
+        .into_iter()
+        .zip(scores)
+        .map(|(place, (rank, snippet))| PlaceSearchResultResponse {
+            place,
+            rank,
+            snippet,
+        })
+        .collect();
+
+    Ok(Json(responses))
+}
+
+/// Turns free text into a `tsquery` requiring a prefix match for every word, so results already
+/// show up while the user is still typing. Punctuation is dropped, which also keeps tsquery
+/// operators in the input from being interpreted.
+fn prefix_ts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word.to_lowercase()))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+#[derive(Deserialize)]
+struct InBoundsQuery {
+    sw: String,
+    ne: String,
+    zoom: f64,
+}
+
+async fn list_places_in_bounds(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    query: Result<Query<InBoundsQuery>, QueryRejection>,
+) -> Result<Json<PlacesInBoundsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Query(query) = query.map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("invalid_request", err.body_text())),
+        )
     })?;
+    let (south, west) = parse_corner(&query.sw, "sw")?;
+    let (north, east) = parse_corner(&query.ne, "ne")?;
+    if south > north {
+        return Err(bad_request("sw must not be north of ne"));
+    }
+    if !(0.0..=MAX_MAP_ZOOM).contains(&query.zoom) {
+        return Err(bad_request("zoom must be between 0 and 22"));
+    }
+    // Map libraries report fractional zoom levels while animating.
+    let zoom = query.zoom.floor() as u8;
+
+    let repository = state.place_repository();
+    let bbox = BoundingBox::from_corners(south, west, north, east);
 
-    let mut responses = Vec::with_capacity(places.len());
-    for place in places {
-        let images = load_images_for_place(&repository, claims.sub, place.id)
+    if zoom > CLUSTER_MAX_ZOOM {
+        let places = repository
+            .list_in_bounds_for_user(user.id, &bbox, MAX_MAP_PLACES as i64 + 1)
             .await
             .map_err(|err| {
-                error!(?err, "failed to load images for place");
+                error!(?err, "failed to list places in bounds");
                 internal_error()
             })?;
-        responses.push(enrich_place(place, images));
+        if places.len() <= MAX_MAP_PLACES {
+            return Ok(Json(PlacesInBoundsResponse::Places {
+                places: places.into_iter().map(MapPlaceResponse::from).collect(),
+            }));
+        }
     }
 
-    Ok(Json(responses))
+    let clusters = repository
+        .cluster_in_bounds_for_user(
+            user.id,
+            &bbox,
+            cluster_cell_degrees(zoom),
+            CLUSTER_TOP_CATEGORIES,
+        )
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to cluster places in bounds");
+            internal_error()
+        })?;
+
+    Ok(Json(PlacesInBoundsResponse::Clusters {
+        clusters: clusters
+            .into_iter()
+            .map(PlaceClusterResponse::from)
+            .collect(),
+    }))
+}
+
+/// Parses a `lat,lng` viewport corner.
+fn parse_corner(
+    value: &str,
+    field: &'static str,
+) -> Result<(f64, f64), (StatusCode, Json<ErrorResponse>)> {
+    let invalid = || {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "invalid_request",
+                format!("{field} must be a 'lat,lng' pair in range"),
+            )),
+        )
+    };
+    let (latitude, longitude) = value.split_once(',').ok_or_else(invalid)?;
+    let latitude = parse_degrees(latitude.trim(), field, 90.0).map_err(|_| invalid())?;
+    let longitude = parse_degrees(longitude.trim(), field, 180.0).map_err(|_| invalid())?;
+    Ok((latitude, longitude))
 }
 
+/// Owners and collection members get the whole place. Users who can only see it through its
+/// visibility, such as followers opening it from the feed, get a `VisiblePlaceResponse`.
 async fn get_place(
     State(state): State<AppState>,
-    Extension(claims): Extension<JwtClaims>,
+    Extension(user): Extension<AuthUser>,
     AxumPath(place_id): AxumPath<Uuid>,
-) -> Result<Json<PlaceResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     let repository = state.place_repository();
     let place = repository
-        .find_for_user(claims.sub, place_id)
+        .find_for_user(user.id, place_id)
         .await
         .map_err(|err| {
             error!(?err, "failed to load place");
             internal_error()
-        })?
-        .ok_or_else(place_not_found)?;
+        })?;
+    if let Some(place) = place {
+        return Ok(Json(place_response(&state, user.id, place).await?).into_response());
+    }
 
-    let images = load_images_for_place(&repository, claims.sub, place.id)
+    let (place, images) = repository
+        .find_visible_for_user(user.id, place_id)
         .await
         .map_err(|err| {
-            error!(?err, "failed to load images for place");
+            error!(?err, "failed to load visible place");
             internal_error()
-        })?;
+        })?
+        .ok_or_else(place_not_found)?;
 
-    Ok(Json(enrich_place(place, images)))
+    Ok(Json(VisiblePlaceResponse::from_record(place, images)).into_response())
 }
 
 async fn update_place(
     State(state): State<AppState>,
-    Extension(claims): Extension<JwtClaims>,
+    Extension(user): Extension<AuthUser>,
     AxumPath(place_id): AxumPath<Uuid>,
     mut multipart: Multipart,
 ) -> Result<Json<PlaceResponse>, (StatusCode, Json<ErrorResponse>)> {
     let repository = state.place_repository();
 
-    // Verify place belongs to user.
-    repository
-        .find_for_user(claims.sub, place_id)
+    // Owners and editors of a collection the place is in may change it.
+    let Some(existing) = repository
+        .find_editable_for_user(user.id, place_id)
         .await
         .map_err(|err| {
             error!(?err, "failed to verify place");
             internal_error()
         })?
-        .ok_or_else(place_not_found)?;
+    else {
+        return Err(missing_place_access(&state, user.id, place_id).await);
+    };
 
     let mut update = UpdatePlace::default();
     let mut incoming_images = Vec::new();
     let mut image_ids: VecDeque<Uuid> = VecDeque::new();
     let mut delete_image_ids: Vec<Uuid> = Vec::new();
+    let mut latitude = None;
+    let mut longitude = None;
+    let mut category_id = None;
+    let mut category = None;
 
     while let Some(field) = multipart.next_field().await.map_err(|err| {
         error!(?err, "failed to read form-data field");
@@ -311,9 +857,38 @@ async fn update_place(
 
         match name {
             "name" => update.name = Some(read_text_field(field, "name").await?),
-            "category" => update.category = Some(read_text_field(field, "category").await?),
+            "category_id" => category_id = Some(read_text_field(field, "category_id").await?),
+            "tags" => update.tags = Some(parse_tags(&read_text_field(field, "tags").await?)?),
+            "category" => category = Some(read_text_field(field, "category").await?),
             "location" => update.location = Some(read_text_field(field, "location").await?),
             "note" => update.note = Some(read_text_field(field, "note").await?),
+            "latitude" => latitude = Some(read_text_field(field, "latitude").await?),
+            "longitude" => longitude = Some(read_text_field(field, "longitude").await?),
+            "address" => {
+                let address = read_text_field(field, "address").await?;
+                update.address = Some(Some(address).filter(|value| !value.is_empty()));
+            }
+            "place_provider_id" => {
+                let provider_id = read_text_field(field, "place_provider_id").await?;
+                update.place_provider_id =
+                    Some(Some(provider_id).filter(|value| !value.is_empty()));
+            }
+            "status" => {
+                update.status = Some(parse_status(&read_text_field(field, "status").await?)?)
+            }
+            "planned_on" => {
+                update.planned_on = Some(parse_planned_on(
+                    &read_text_field(field, "planned_on").await?,
+                )?)
+            }
+            "priority" => {
+                update.priority = Some(parse_priority(&read_text_field(field, "priority").await?)?)
+            }
+            "visibility" => {
+                update.visibility = Some(parse_visibility(
+                    &read_text_field(field, "visibility").await?,
+                )?)
+            }
             "image" => {
                 let file_name = field.file_name().map(|value| value.to_owned());
                 let bytes = field.bytes().await.map_err(|err| {
@@ -352,6 +927,34 @@ async fn update_place(
         return Err(missing_field("image_id for every image"));
     }
 
+    // Editors may change a place but not who else gets to see it.
+    if update.visibility.is_some() && existing.user_id != user.id {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(
+                "forbidden",
+                "only the owner can change the visibility of a place",
+            )),
+        ));
+    }
+
+    // Categories belong to the owner of the place, also when an editor makes the change.
+    if let Some(category) = resolve_category(
+        &state,
+        existing.user_id,
+        category_id.as_deref(),
+        category.as_deref(),
+    )
+    .await?
+    {
+        update.category_id = Some(category.id);
+        update.category = Some(category.name);
+    }
+    if let Some(point) = parse_coordinates_update(latitude.as_deref(), longitude.as_deref())? {
+        update.latitude = Some(point.as_ref().map(|point| point.latitude));
+        update.longitude = Some(point.as_ref().map(|point| point.longitude));
+    }
+
     let image_store = state.image_store();
     let uploads = prepare_uploads(incoming_images)?;
     let stored_images = if uploads.is_empty() {
@@ -378,7 +981,7 @@ async fn update_place(
 
     let (place, _inserted_images, deleted_images) = match repository
         .update_place_with_images(
-            claims.sub,
+            user.id,
             place_id,
             update,
             &new_image_payloads,
@@ -402,26 +1005,18 @@ async fn update_place(
         .remove_files(place_id, &deleted_file_names)
         .await;
 
-    // Build response with current images.
-    let images = load_images_for_place(&repository, claims.sub, place_id)
-        .await
-        .map_err(|err| {
-            error!(?err, "failed to load images after update");
-            internal_error()
-        })?;
-
-    Ok(Json(enrich_place(place, images)))
+    Ok(Json(place_response(&state, user.id, place).await?))
 }
 
 async fn list_images(
     State(state): State<AppState>,
-    Extension(claims): Extension<JwtClaims>,
+    Extension(user): Extension<AuthUser>,
     AxumPath(place_id): AxumPath<Uuid>,
 ) -> Result<Json<Vec<PlaceImageResponse>>, (StatusCode, Json<ErrorResponse>)> {
     let repository = state.place_repository();
     // Ensure place exists and images obey policy
     repository
-        .find_for_user(claims.sub, place_id)
+        .find_for_user(user.id, place_id)
         .await
         .map_err(|err| {
             error!(?err, "failed to verify place");
@@ -429,7 +1024,7 @@ async fn list_images(
         })?
         .ok_or_else(place_not_found)?;
 
-    let images = load_images_for_place(&repository, claims.sub, place_id)
+    let images = load_images_for_place(&repository, user.id, place_id)
         .await
         .map_err(|err| {
             error!(?err, "failed to load images");
@@ -441,12 +1036,12 @@ async fn list_images(
 
 async fn get_place_image(
     State(state): State<AppState>,
-    Extension(claims): Extension<JwtClaims>,
+    Extension(user): Extension<AuthUser>,
     AxumPath((place_id, image_id)): AxumPath<(Uuid, Uuid)>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     let repository = state.place_repository();
     let image = repository
-        .find_image_for_user(claims.sub, image_id)
+        .find_image_for_user(user.id, image_id)
         .await
         .map_err(|err| {
             error!(?err, "failed to load place");
@@ -481,21 +1076,21 @@ async fn get_place_image(
 
 async fn delete_place(
     State(state): State<AppState>,
-    Extension(claims): Extension<JwtClaims>,
+    Extension(user): Extension<AuthUser>,
     AxumPath(place_id): AxumPath<Uuid>,
 ) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
     let repository = state.place_repository();
     let image_store = state.image_store();
 
     let Some((_place, _images)) = repository
-        .delete_place_for_user(claims.sub, place_id)
+        .delete_place_for_user(user.id, place_id)
         .await
         .map_err(|err| {
             error!(?err, "failed to delete place");
             internal_error()
         })?
     else {
-        return Err(place_not_found());
+        return Err(missing_place_access(&state, user.id, place_id).await);
     };
 
     image_store.remove_place_dir(place_id).await;
@@ -517,52 +1112,285 @@ async fn load_images_for_place(
     Ok(images)
 }
 
-fn enrich_place(place: PlaceRecord, images: Vec<PlaceImageResponse>) -> PlaceResponse {
-    let mut response = PlaceResponse::from(place);
-    response.images = images;
-    response
-}
+/// Finds the category a place form refers to. `category_id` must name one of the user's
+/// categories. Without it, `category` is treated as a name and matched case-insensitively, creating
+/// the category when the user has none by that name.
+async fn resolve_category(
+    state: &AppState,
+    user_id: Uuid,
+    category_id: Option<&str>,
+    category: Option<&str>,
+) -> Result<Option<CategoryRecord>, (StatusCode, Json<ErrorResponse>)> {
+    let repository = state.category_repository();
+
+    if let Some(category_id) = non_empty(category_id.map(str::trim)) {
+        let category_id = parse_uuid(category_id, "category_id")?;
+        let category = repository
+            .find_for_user(user_id, category_id)
+            .await
+            .map_err(|err| {
+                error!(?err, "failed to load category");
+                internal_error()
+            })?
+            .ok_or_else(|| bad_request("category_id does not refer to one of your categories"))?;
+        return Ok(Some(category));
+    }
 
-fn missing_field(field: &'static str) -> (StatusCode, Json<ErrorResponse>) {
-    (
-        StatusCode::BAD_REQUEST,
-        Json(ErrorResponse::new(
-            "invalid_request",
-            format!("missing required field: {}", field),
-        )),
-    )
-}
+    let Some(name) = non_empty(category.map(str::trim)) else {
+        return Ok(None);
+    };
+    if name.chars().count() > MAX_CATEGORY_NAME_CHARS {
+        return Err(bad_request("category must be at most 100 characters"));
+    }
+    let category = repository
+        .find_or_create_by_name(user_id, name)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to resolve category");
+            internal_error()
+        })?;
 
-fn bad_request(message: &'static str) -> (StatusCode, Json<ErrorResponse>) {
-    (
-        StatusCode::BAD_REQUEST,
-        Json(ErrorResponse::new("invalid_request", message)),
-    )
+    Ok(Some(category))
 }
 
-async fn read_text_field(
-    field: axum::extract::multipart::Field<'_>,
-    label: &'static str,
-) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
-    field
-        .text()
+/// Builds responses for a list of places. Images, tags and visit aggregates are loaded for all
+/// places at once, so the number of queries does not grow with the number of places.
+pub(super) async fn place_responses(
+    state: &AppState,
+    user_id: Uuid,
+    places: Vec<PlaceRecord>,
+) -> Result<Vec<PlaceResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let repository = state.place_repository();
+    let place_ids: Vec<Uuid> = places.iter().map(|place| place.id).collect();
+    let mut images = repository
+        .list_images_for_places(user_id, &place_ids)
         .await
         .map_err(|err| {
-            error!(?err, "invalid {} field", label);
-            (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse::new(
-                    "invalid_request",
-                    format!("{} must be text", label),
-                )),
-            )
-        })
-        .map(|s| s.trim().to_string())
-}
-
-fn parse_uuid(value: &str, field: &'static str) -> Result<Uuid, (StatusCode, Json<ErrorResponse>)> {
-    Uuid::parse_str(value).map_err(|err| {
-        error!(%value, ?err, "invalid uuid");
+            error!(?err, "failed to load images for places");
+            internal_error()
+        })?;
+    let mut tags = repository
+        .list_tags_for_places(user_id, &place_ids)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load tags for places");
+            internal_error()
+        })?;
+    let mut visit_stats = state
+        .visit_repository()
+        .list_stats_for_places(user_id, &place_ids)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load visit stats for places");
+            internal_error()
+        })?;
+    let mut feedback = state
+        .comment_repository()
+        .list_counts_for_places(user_id, &place_ids)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load comment counts for places");
+            internal_error()
+        })?;
+
+    Ok(places
+        .into_iter()
+        .map(|place| {
+            let place_id = place.id;
+            let mut response = PlaceResponse::from(place);
+            response.images = images
+                .remove(&place_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(PlaceImageResponse::from_record)
+                .collect();
+            response.tags = tags.remove(&place_id).unwrap_or_default();
+            if let Some(stats) = visit_stats.remove(&place_id) {
+                response.visit_count = stats.visit_count;
+                response.last_visited_on = stats.last_visited_on;
+                response.average_rating = stats.average_rating;
+            }
+            if let Some(counts) = feedback.remove(&place_id) {
+                response.comment_count = counts.comment_count;
+                response.reaction_counts = counts
+                    .reactions
+                    .into_iter()
+                    .map(ReactionCountResponse::from)
+                    .collect();
+            }
+            response
+        })
+        .collect())
+}
+
+async fn place_response(
+    state: &AppState,
+    user_id: Uuid,
+    place: PlaceRecord,
+) -> Result<PlaceResponse, (StatusCode, Json<ErrorResponse>)> {
+    let mut responses = place_responses(state, user_id, vec![place]).await?;
+    Ok(responses.pop().expect("one response per place"))
+}
+
+/// Parses the `tags` form field, a JSON array of tag names. Names are trimmed and duplicates
+/// differing only in case are dropped. Commas are rejected because `GET /places?tag=` uses them
+/// to separate tags.
+fn parse_tags(text: &str) -> Result<Vec<String>, (StatusCode, Json<ErrorResponse>)> {
+    let names = serde_json::from_str::<Vec<String>>(text).map_err(|err| {
+        error!(?err, "invalid tags payload");
+        bad_request("tags must be a JSON array of strings")
+    })?;
+
+    let mut tags: Vec<String> = Vec::new();
+    for name in names {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_TAG_CHARS || name.contains(',') {
+            return Err(bad_request(
+                "tags must be 1 to 50 characters long and must not contain commas",
+            ));
+        }
+        if !tags
+            .iter()
+            .any(|tag| tag.to_lowercase() == name.to_lowercase())
+        {
+            tags.push(name.to_string());
+        }
+    }
+    if tags.len() > MAX_TAGS_PER_PLACE {
+        return Err(bad_request("a place can have at most 20 tags"));
+    }
+
+    Ok(tags)
+}
+
+fn parse_status(text: &str) -> Result<PlaceStatus, (StatusCode, Json<ErrorResponse>)> {
+    PlaceStatus::parse(text)
+        .ok_or_else(|| bad_request("status must be want_to_go, visited, favorite or archived"))
+}
+
+fn parse_visibility(text: &str) -> Result<PlaceVisibility, (StatusCode, Json<ErrorResponse>)> {
+    PlaceVisibility::parse(text)
+        .ok_or_else(|| bad_request("visibility must be private, friends or public"))
+}
+
+/// Parses a `YYYY-MM-DD` date. An empty value clears the planned date.
+fn parse_planned_on(text: &str) -> Result<Option<NaiveDate>, (StatusCode, Json<ErrorResponse>)> {
+    if text.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| bad_request("planned_on must be a date formatted as YYYY-MM-DD"))
+}
+
+/// Parses a priority between 1 and 5. An empty value clears it.
+fn parse_priority(text: &str) -> Result<Option<i16>, (StatusCode, Json<ErrorResponse>)> {
+    if text.is_empty() {
+        return Ok(None);
+    }
+    match text.parse::<i16>() {
+        Ok(priority) if (1..=5).contains(&priority) => Ok(Some(priority)),
+        _ => Err(bad_request(
+            "priority must be a whole number between 1 and 5",
+        )),
+    }
+}
+
+fn missing_field(field: &'static str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new(
+            "invalid_request",
+            format!("missing required field: {}", field),
+        )),
+    )
+}
+
+fn bad_request(message: &'static str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+async fn read_text_field(
+    field: axum::extract::multipart::Field<'_>,
+    label: &'static str,
+) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    field
+        .text()
+        .await
+        .map_err(|err| {
+            error!(?err, "invalid {} field", label);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "invalid_request",
+                    format!("{} must be text", label),
+                )),
+            )
+        })
+        .map(|s| s.trim().to_string())
+}
+
+struct Coordinates {
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Coordinates are optional, but a place with only one of them is rejected. Empty fields count as
+/// missing so forms can always send both.
+fn parse_coordinates(
+    latitude: Option<&str>,
+    longitude: Option<&str>,
+) -> Result<Option<Coordinates>, (StatusCode, Json<ErrorResponse>)> {
+    match (non_empty(latitude), non_empty(longitude)) {
+        (None, None) => Ok(None),
+        (Some(latitude), Some(longitude)) => Ok(Some(Coordinates {
+            latitude: parse_degrees(latitude, "latitude", 90.0)?,
+            longitude: parse_degrees(longitude, "longitude", 180.0)?,
+        })),
+        _ => Err(bad_request(
+            "latitude and longitude must be provided together",
+        )),
+    }
+}
+
+/// As `parse_coordinates`, except that sending both fields empty clears the coordinates.
+fn parse_coordinates_update(
+    latitude: Option<&str>,
+    longitude: Option<&str>,
+) -> Result<Option<Option<Coordinates>>, (StatusCode, Json<ErrorResponse>)> {
+    if latitude == Some("") && longitude == Some("") {
+        return Ok(Some(None));
+    }
+    Ok(parse_coordinates(latitude, longitude)?.map(Some))
+}
+
+fn parse_degrees(
+    value: &str,
+    field: &'static str,
+    limit: f64,
+) -> Result<f64, (StatusCode, Json<ErrorResponse>)> {
+    match value.parse::<f64>() {
+        Ok(degrees) if degrees.is_finite() && degrees.abs() <= limit => Ok(degrees),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "invalid_request",
+                format!("{field} must be a number between -{limit} and {limit}"),
+            )),
+        )),
+    }
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.filter(|value| !value.is_empty())
+}
+
+fn parse_uuid(value: &str, field: &'static str) -> Result<Uuid, (StatusCode, Json<ErrorResponse>)> {
+    Uuid::parse_str(value).map_err(|err| {
+        error!(%value, ?err, "invalid uuid");
         (
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse::new(
@@ -580,6 +1408,33 @@ fn image_io_error(message: &'static str) -> (StatusCode, Json<ErrorResponse>) {
     )
 }
 
+/// Error for a change the user may not make: `403` when they can still see the place through a
+/// collection, `404` otherwise.
+async fn missing_place_access(
+    state: &AppState,
+    user_id: Uuid,
+    place_id: Uuid,
+) -> (StatusCode, Json<ErrorResponse>) {
+    match state
+        .place_repository()
+        .find_for_user(user_id, place_id)
+        .await
+    {
+        Ok(Some(_)) => (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(
+                "forbidden",
+                "you are not allowed to change this place",
+            )),
+        ),
+        Ok(None) => place_not_found(),
+        Err(err) => {
+            error!(?err, "failed to load place");
+            internal_error()
+        }
+    }
+}
+
 fn place_not_found() -> (StatusCode, Json<ErrorResponse>) {
     (
         StatusCode::NOT_FOUND,
@@ -617,21 +1472,23 @@ fn prepare_uploads(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::body::Body;
-    use axum::http::{header, Request};
-    use tower::ServiceExt;
+    use axum::http::{Method, Request};
+    use serde_json::json;
 
-    use crate::test_utils::router::{multipart_body, parse_json, Part, TestContext};
+    use crate::test_utils::queries::count_queries;
+    use crate::test_utils::router::{
+        create_place, parse_json, send_json, send_multipart, Part, TestContext,
+    };
 
     #[tokio::test]
     async fn create_list_get_and_fetch_image() {
         let ctx = TestContext::new(super::router).await;
         let user = ctx.insert_user().await;
-        let token = ctx.jwt.generate(&user).expect("jwt");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
 
         let place_id = Uuid::new_v4();
         let image_id = Uuid::new_v4();
-        let (boundary, body) = multipart_body(vec![
+        let parts = vec![
             Part::text("id", place_id.to_string()),
             Part::text("name", "Blue Bottle"),
             Part::text("category", "Coffee"),
@@ -639,67 +1496,28 @@ mod tests {
             Part::text("note", "Try the latte"),
             Part::text("image_id", image_id.to_string()),
             Part::file("image", "image.jpg", "image/jpeg", b"IMG".to_vec()),
-        ]);
-
-        let response = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::post("/places")
-                    .header("Authorization", format!("Bearer {}", token))
-                    .header(
-                        header::CONTENT_TYPE,
-                        format!("multipart/form-data; boundary={boundary}"),
-                    )
-                    .body(Body::from(body))
-                    .unwrap(),
-            )
-            .await
-            .expect("request succeeds");
+        ];
+
+        let response = send_multipart(&ctx, Request::post("/places"), &token, parts).await;
         assert_eq!(response.status(), StatusCode::OK);
         let place: PlaceResponse = parse_json(response).await;
         assert_eq!(place.id, place_id);
         assert_eq!(place.images.len(), 1);
 
-        let list_response = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::get("/places")
-                    .header("Authorization", format!("Bearer {}", token))
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .expect("list request");
+        let list_response = get(&ctx, "/places", &token).await;
         assert_eq!(list_response.status(), StatusCode::OK);
         let places: Vec<PlaceResponse> = parse_json(list_response).await;
         assert_eq!(places.len(), 1);
 
-        let get_response = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::get(format!("/places/{place_id}"))
-                    .header("Authorization", format!("Bearer {}", token))
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .expect("get request");
+        let get_response = get(&ctx, &format!("/places/{place_id}"), &token).await;
         assert_eq!(get_response.status(), StatusCode::OK);
 
-        let image_resp = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::get(format!("/places/{place_id}/images/{image_id}"))
-                    .header("Authorization", format!("Bearer {}", token))
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .expect("image request");
+        let image_resp = get(
+            &ctx,
+            &format!("/places/{place_id}/images/{image_id}"),
+            &token,
+        )
+        .await;
         assert_eq!(image_resp.status(), StatusCode::OK);
 
         let file_name: String =
@@ -716,11 +1534,12 @@ mod tests {
     async fn update_place_adds_and_deletes_images() {
         let ctx = TestContext::new(super::router).await;
         let user = ctx.insert_user().await;
-        let token = ctx.jwt.generate(&user).expect("jwt");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
 
-        let place_id = Uuid::new_v4();
         let original_image_id = Uuid::new_v4();
-        create_place_for_test(&ctx, &token, place_id, original_image_id).await;
+        let place_id = create_place(&ctx, &token, "Sample", with_image(original_image_id))
+            .await
+            .id;
 
         let original_file: String =
             sqlx::query_scalar("SELECT file_name FROM place_images WHERE id = $1")
@@ -731,28 +1550,20 @@ mod tests {
 
         let new_image_id = Uuid::new_v4();
         let delete_payload = serde_json::to_string(&vec![original_image_id.to_string()]).unwrap();
-        let (boundary, body) = multipart_body(vec![
+        let parts = vec![
             Part::text("name", "Updated Name"),
             Part::text("delete_image_ids", delete_payload),
             Part::text("image_id", new_image_id.to_string()),
             Part::file("image", "new.jpg", "image/jpeg", b"NEW".to_vec()),
-        ]);
-
-        let response = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::patch(format!("/places/{place_id}"))
-                    .header("Authorization", format!("Bearer {}", token))
-                    .header(
-                        header::CONTENT_TYPE,
-                        format!("multipart/form-data; boundary={boundary}"),
-                    )
-                    .body(Body::from(body))
-                    .unwrap(),
-            )
-            .await
-            .expect("update request");
+        ];
+
+        let response = send_multipart(
+            &ctx,
+            Request::patch(format!("/places/{place_id}")),
+            &token,
+            parts,
+        )
+        .await;
         assert_eq!(response.status(), StatusCode::OK);
         let updated: PlaceResponse = parse_json(response).await;
         assert_eq!(updated.name, "Updated Name");
@@ -787,38 +1598,24 @@ mod tests {
     async fn user_cannot_access_foreign_place_or_image() {
         let ctx = TestContext::new(super::router).await;
         let owner = ctx.insert_user().await;
-        let owner_token = ctx.jwt.generate(&owner).expect("jwt");
+        let owner_token = ctx.jwt.generate(&owner, None).expect("jwt");
         let intruder = ctx.insert_user().await;
-        let intruder_token = ctx.jwt.generate(&intruder).expect("jwt");
+        let intruder_token = ctx.jwt.generate(&intruder, None).expect("jwt");
 
-        let place_id = Uuid::new_v4();
         let image_id = Uuid::new_v4();
-        create_place_for_test(&ctx, &owner_token, place_id, image_id).await;
-
-        let forbidden_place = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::get(format!("/places/{place_id}"))
-                    .header("Authorization", format!("Bearer {}", intruder_token))
-                    .body(Body::empty())
-                    .unwrap(),
-            )
+        let place_id = create_place(&ctx, &owner_token, "Sample", with_image(image_id))
             .await
-            .expect("intruder place request");
+            .id;
+
+        let forbidden_place = get(&ctx, &format!("/places/{place_id}"), &intruder_token).await;
         assert_eq!(forbidden_place.status(), StatusCode::NOT_FOUND);
 
-        let forbidden_image = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::get(format!("/places/{place_id}/images/{image_id}"))
-                    .header("Authorization", format!("Bearer {}", intruder_token))
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .expect("intruder image request");
+        let forbidden_image = get(
+            &ctx,
+            &format!("/places/{place_id}/images/{image_id}"),
+            &intruder_token,
+        )
+        .await;
         assert_eq!(forbidden_image.status(), StatusCode::NOT_FOUND);
     }
 
@@ -826,11 +1623,12 @@ mod tests {
     async fn delete_place_removes_records_and_files() {
         let ctx = TestContext::new(super::router).await;
         let user = ctx.insert_user().await;
-        let token = ctx.jwt.generate(&user).expect("jwt");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
 
-        let place_id = Uuid::new_v4();
         let image_id = Uuid::new_v4();
-        create_place_for_test(&ctx, &token, place_id, image_id).await;
+        let place_id = create_place(&ctx, &token, "Sample", with_image(image_id))
+            .await
+            .id;
 
         let file_name: String =
             sqlx::query_scalar("SELECT file_name FROM place_images WHERE id = $1")
@@ -841,17 +1639,14 @@ mod tests {
         let image_path = ctx.image_dir().join(place_id.to_string()).join(&file_name);
         assert!(image_path.exists());
 
-        let response = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::delete(format!("/places/{place_id}"))
-                    .header("Authorization", format!("Bearer {}", token))
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .expect("delete request");
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/places/{place_id}"),
+            &token,
+            json!({}),
+        )
+        .await;
         assert_eq!(response.status(), StatusCode::NO_CONTENT);
 
         let place_row: Option<Uuid> = sqlx::query_scalar("SELECT id FROM places WHERE id = $1")
@@ -875,61 +1670,800 @@ mod tests {
     async fn create_place_rejects_missing_image_ids() {
         let ctx = TestContext::new(super::router).await;
         let user = ctx.insert_user().await;
-        let token = ctx.jwt.generate(&user).expect("jwt");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
         let place_id = Uuid::new_v4();
 
-        let (boundary, body) = multipart_body(vec![
+        let parts = vec![
             Part::text("id", place_id.to_string()),
             Part::text("name", "No Image Id"),
             Part::text("category", "Test"),
             Part::text("location", "Nowhere"),
             Part::text("note", "bad"),
             Part::file("image", "bad.jpg", "image/jpeg", b"BYTES".to_vec()),
-        ]);
-
-        let response = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::post("/places")
-                    .header("Authorization", format!("Bearer {}", token))
-                    .header(
-                        header::CONTENT_TYPE,
-                        format!("multipart/form-data; boundary={boundary}"),
-                    )
-                    .body(Body::from(body))
-                    .unwrap(),
+        ];
+
+        let response = send_multipart(&ctx, Request::post("/places"), &token, parts).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn stores_and_validates_coordinates() {
+        let ctx = TestContext::new(super::router).await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let place_id = Uuid::new_v4();
+        let base = |extra: Vec<Part>| {
+            let mut parts = vec![
+                Part::text("id", place_id.to_string()),
+                Part::text("name", "Tartine"),
+                Part::text("category", "Bakery"),
+                Part::text("location", "Mission"),
+            ];
+            parts.extend(extra);
+            parts
+        };
+
+        for invalid in [
+            vec![Part::text("latitude", "37.76")],
+            vec![
+                Part::text("latitude", "91"),
+                Part::text("longitude", "-122.42"),
+            ],
+            vec![
+                Part::text("latitude", "37.76"),
+                Part::text("longitude", "NaN"),
+            ],
+        ] {
+            let response =
+                send_multipart(&ctx, Request::post("/places"), &token, base(invalid)).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let response = send_multipart(
+            &ctx,
+            Request::post("/places"),
+            &token,
+            base(vec![
+                Part::text("latitude", "37.7614"),
+                Part::text("longitude", "-122.4241"),
+                Part::text("address", "600 Guerrero St, San Francisco"),
+                Part::text("place_provider_id", "ChIJ-tartine"),
+            ]),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let place: PlaceResponse = parse_json(response).await;
+        assert_eq!(place.latitude, Some(37.7614));
+        assert_eq!(place.longitude, Some(-122.4241));
+        assert_eq!(
+            place.address.as_deref(),
+            Some("600 Guerrero St, San Francisco")
+        );
+        assert_eq!(place.place_provider_id.as_deref(), Some("ChIJ-tartine"));
+        assert_eq!(place.location, "Mission");
+
+        let uri = format!("/places/{place_id}");
+        let response = send_multipart(
+            &ctx,
+            Request::patch(&uri),
+            &token,
+            vec![Part::text("longitude", "-122.5")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = send_multipart(
+            &ctx,
+            Request::patch(&uri),
+            &token,
+            vec![
+                Part::text("latitude", "37.8"),
+                Part::text("longitude", "-122.5"),
+                Part::text("note", "Moved"),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let place: PlaceResponse = parse_json(response).await;
+        assert_eq!(place.latitude, Some(37.8));
+        assert_eq!(place.longitude, Some(-122.5));
+        assert_eq!(
+            place.address.as_deref(),
+            Some("600 Guerrero St, San Francisco")
+        );
+
+        // Empty values clear what was set.
+        let response = send_multipart(
+            &ctx,
+            Request::patch(&uri),
+            &token,
+            vec![
+                Part::text("latitude", ""),
+                Part::text("longitude", ""),
+                Part::text("address", ""),
+                Part::text("place_provider_id", ""),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let place: PlaceResponse = parse_json(response).await;
+        assert_eq!(place.latitude, None);
+        assert_eq!(place.longitude, None);
+        assert_eq!(place.address, None);
+        assert_eq!(place.place_provider_id, None);
+        assert_eq!(place.note.as_deref(), Some("Moved"));
+    }
+
+    #[tokio::test]
+    async fn nearby_returns_own_places_within_radius_nearest_first() {
+        let ctx = TestContext::new(super::router).await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+        let other = ctx.insert_user().await;
+        let other_token = ctx.jwt.generate(&other, None).expect("jwt");
+
+        // 0.009 degrees of latitude is roughly one kilometre.
+        let far = create_place(
+            &ctx,
+            &token,
+            "Far",
+            categorized_at("Coffee", Some((52.5560, 13.4050))),
+        )
+        .await
+        .id;
+        let near = create_place(
+            &ctx,
+            &token,
+            "Near",
+            categorized_at("Coffee", Some((52.5290, 13.4050))),
+        )
+        .await
+        .id;
+        let here = create_place(
+            &ctx,
+            &token,
+            "Here",
+            categorized_at("Coffee", Some((52.5200, 13.4050))),
+        )
+        .await
+        .id;
+        create_place(&ctx, &token, "Nowhere", categorized_at("Coffee", None)).await;
+        create_place(
+            &ctx,
+            &other_token,
+            "Foreign",
+            categorized_at("Coffee", Some((52.5200, 13.4050))),
+        )
+        .await;
+
+        let response = get(
+            &ctx,
+            "/places/nearby?lat=52.52&lng=13.405&radius_m=2000",
+            &token,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let places: Vec<NearbyPlaceResponse> = parse_json(response).await;
+        let ids: Vec<Uuid> = places.iter().map(|nearby| nearby.place.id).collect();
+        assert_eq!(ids, [here, near]);
+        assert!(places[0].distance_m < 1.0);
+        assert!((places[1].distance_m - 1000.8).abs() < 1.0);
+
+        let response = get(
+            &ctx,
+            "/places/nearby?lat=52.52&lng=13.405&radius_m=5000",
+            &token,
+        )
+        .await;
+        let places: Vec<NearbyPlaceResponse> = parse_json(response).await;
+        assert_eq!(places.last().map(|nearby| nearby.place.id), Some(far));
+
+        for uri in [
+            "/places/nearby?lat=52.52&lng=13.405",
+            "/places/nearby?lat=95&lng=13.405&radius_m=100",
+            "/places/nearby?lat=52.52&lng=13.405&radius_m=0",
+            "/places/nearby?lat=52.52&lng=13.405&radius_m=1000000",
+        ] {
+            let response = get(&ctx, uri, &token).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn in_bounds_clusters_when_zoomed_out_and_lists_places_when_zoomed_in() {
+        let ctx = TestContext::new(super::router).await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let mut berlin = Vec::new();
+        for (name, category, point) in [
+            ("Bonanza", "Coffee", (52.520, 13.400)),
+            ("The Barn", "Coffee", (52.530, 13.410)),
+            ("Buck and Breck", "Bar", (52.500, 13.390)),
+        ] {
+            berlin.push(
+                create_place(&ctx, &token, name, categorized_at(category, Some(point)))
+                    .await
+                    .id,
+            );
+        }
+        let paris = create_place(
+            &ctx,
+            &token,
+            "Telescope",
+            categorized_at("Coffee", Some((48.85, 2.35))),
+        )
+        .await
+        .id;
+        create_place(
+            &ctx,
+            &token,
+            "Stumptown",
+            categorized_at("Coffee", Some((40.72, -74.0))),
+        )
+        .await;
+        create_place(&ctx, &token, "Unplaced", categorized_at("Coffee", None)).await;
+        let fiji_east = create_place(
+            &ctx,
+            &token,
+            "Suva",
+            categorized_at("Bar", Some((-17.0, -179.9))),
+        )
+        .await
+        .id;
+        let fiji_west = create_place(
+            &ctx,
+            &token,
+            "Nadi",
+            categorized_at("Bar", Some((-17.7, 178.0))),
+        )
+        .await
+        .id;
+
+        let response = get(&ctx, "/places/in-bounds?sw=40,-5&ne=60,20&zoom=5", &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let PlacesInBoundsResponse::Clusters { clusters } = parse_json(response).await else {
+            panic!("expected clusters when zoomed out");
+        };
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].count, 3);
+        assert_eq!(clusters[0].top_categories, ["Coffee", "Bar"]);
+        assert!((clusters[0].latitude - 52.5167).abs() < 0.001);
+        assert_eq!(clusters[0].place_id, None);
+        assert_eq!(clusters[1].count, 1);
+        assert_eq!(clusters[1].place_id, Some(paris));
+
+        let response = get(
+            &ctx,
+            "/places/in-bounds?sw=52.4,13.3&ne=52.6,13.5&zoom=16.4",
+            &token,
+        )
+        .await;
+        let PlacesInBoundsResponse::Places { places } = parse_json(response).await else {
+            panic!("expected places when zoomed in");
+        };
+        let mut ids: Vec<Uuid> = places.iter().map(|place| place.id).collect();
+        ids.sort();
+        berlin.sort();
+        assert_eq!(ids, berlin);
+
+        // A viewport whose west edge is east of its east edge spans the antimeridian.
+        let response = get(
+            &ctx,
+            "/places/in-bounds?sw=-18,177&ne=-16,-179&zoom=16",
+            &token,
+        )
+        .await;
+        let PlacesInBoundsResponse::Places { places } = parse_json(response).await else {
+            panic!("expected places when zoomed in");
+        };
+        let ids: Vec<Uuid> = places.iter().map(|place| place.id).collect();
+        assert_eq!(ids, [fiji_east, fiji_west]);
+
+        for uri in [
+            "/places/in-bounds?sw=60,-5&ne=40,20&zoom=5",
+            "/places/in-bounds?sw=40,-5&ne=60,20&zoom=30",
+            "/places/in-bounds?sw=north&ne=60,20&zoom=5",
+            "/places/in-bounds?sw=40,-5&ne=60,200&zoom=5",
+        ] {
+            let response = get(&ctx, uri, &token).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn list_paginates_with_filters_and_sort_orders() {
+        let ctx = TestContext::new(super::router).await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let bonanza = create_place(
+            &ctx,
+            &token,
+            "Bonanza",
+            categorized_at("Coffee", Some((52.52, 13.405))),
+        )
+        .await
+        .id;
+        let ampelmann = create_place(
+            &ctx,
+            &token,
+            "Ampelmann",
+            categorized_at("Bar", Some((52.53, 13.405))),
+        )
+        .await
+        .id;
+        let zeit = create_place(&ctx, &token, "Zeit", categorized_at("coffee", None))
+            .await
+            .id;
+        let delta = create_place(
+            &ctx,
+            &token,
+            "Delta",
+            categorized_at("Coffee", Some((52.60, 13.405))),
+        )
+        .await
+        .id;
+        let sample = create_place(&ctx, &token, "Sample", with_image(Uuid::new_v4()))
+            .await
+            .id;
+
+        // Without limit or cursor the response stays a bare array.
+        let response = get(&ctx, "/places", &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let all: Vec<PlaceResponse> = parse_json(response).await;
+        assert_eq!(all.len(), 5);
+
+        let newest_first = [sample, delta, zeit, ampelmann, bonanza];
+        assert_eq!(
+            list_all_pages(&ctx, &token, "/places?limit=2").await,
+            newest_first
+        );
+        assert_eq!(
+            list_all_pages(&ctx, &token, "/places?sort=name&limit=2").await,
+            [ampelmann, bonanza, delta, sample, zeit]
+        );
+        assert_eq!(
+            list_all_pages(&ctx, &token, "/places?sort=created_at&order=asc&limit=3").await,
+            [bonanza, ampelmann, zeit, delta, sample]
+        );
+
+        // Places without coordinates come last when sorting by distance.
+        let by_distance = list_all_pages(
+            &ctx,
+            &token,
+            "/places?sort=distance&lat=52.52&lng=13.405&limit=2",
+        )
+        .await;
+        assert_eq!(by_distance[..3], [bonanza, ampelmann, delta]);
+        let mut unplaced = by_distance[3..].to_vec();
+        unplaced.sort();
+        let mut expected = vec![zeit, sample];
+        expected.sort();
+        assert_eq!(unplaced, expected);
+
+        assert_eq!(
+            list_all_pages(&ctx, &token, "/places?category=COFFEE&limit=10").await,
+            [sample, delta, zeit, bonanza]
+        );
+        assert_eq!(
+            list_all_pages(&ctx, &token, "/places?has_images=true&limit=10").await,
+            [sample]
+        );
+        assert_eq!(
+            list_all_pages(&ctx, &token, "/places?has_images=false&limit=10").await,
+            [delta, zeit, ampelmann, bonanza]
+        );
+
+        let zeit_created = all
+            .iter()
+            .find(|place| place.id == zeit)
+            .map(|place| {
+                place
+                    .created_at
+                    .to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+            })
+            .expect("zeit listed");
+        assert_eq!(
+            list_all_pages(
+                &ctx,
+                &token,
+                &format!("/places?created_after={zeit_created}&limit=10")
+            )
+            .await,
+            [sample, delta, zeit]
+        );
+        assert_eq!(
+            list_all_pages(
+                &ctx,
+                &token,
+                &format!("/places?created_before={zeit_created}&limit=10")
             )
+            .await,
+            [ampelmann, bonanza]
+        );
+
+        let response = get(&ctx, "/places?sort=name&limit=2", &token).await;
+        let page: PlacePageResponse = parse_json(response).await;
+        let name_cursor = page.next_cursor.expect("more pages");
+        for uri in [
+            format!("/places?cursor={name_cursor}"),
+            format!("/places?sort=name&order=desc&cursor={name_cursor}"),
+            "/places?cursor=not-a-cursor".to_string(),
+        ] {
+            let response = get(&ctx, &uri, &token).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+            let body: serde_json::Value = parse_json(response).await;
+            assert_eq!(body["error"], "invalid_cursor");
+        }
+
+        for uri in [
+            "/places?limit=0",
+            "/places?limit=101",
+            "/places?sort=distance&limit=5",
+            "/places?sort=popularity",
+            "/places?created_after=yesterday",
+        ] {
+            let response = get(&ctx, uri, &token).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn search_ranks_prefix_matches_and_tolerates_typos() {
+        let ctx = TestContext::new(super::router).await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+        let other = ctx.insert_user().await;
+        let other_token = ctx.jwt.generate(&other, None).expect("jwt");
+
+        let bottle = create_place(
+            &ctx,
+            &token,
+            "Blue Bottle",
+            noted("Coffee", "Great pour over and a quiet garden in the back"),
+        )
+        .await
+        .id;
+        let barn = create_place(
+            &ctx,
+            &token,
+            "The Barn",
+            noted("Coffee", "Roastery with a blue door"),
+        )
+        .await
+        .id;
+        let noodles = create_place(
+            &ctx,
+            &token,
+            "Noodle Bar",
+            noted("Restaurant", "Spicy dan dan noodles"),
+        )
+        .await
+        .id;
+        create_place(&ctx, &other_token, "Blue Note", noted("Bar", "Jazz")).await;
+
+        // Name matches outrank note matches, and words are matched as prefixes.
+        let results = search(&ctx, &token, "/places/search?q=blu").await;
+        let ids: Vec<Uuid> = results.iter().map(|result| result.place.id).collect();
+        assert_eq!(ids, [bottle, barn]);
+        assert!(results[0].rank > results[1].rank);
+        assert_eq!(
+            results[1].snippet.as_deref(),
+            Some("Roastery with a <mark>blue</mark> door")
+        );
+
+        let results = search(&ctx, &token, "/places/search?q=quiet%20gard").await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].place.id, bottle);
+        assert!(results[0]
+            .snippet
+            .as_deref()
+            .is_some_and(|snippet| snippet.contains("<mark>quiet</mark> <mark>garden</mark>")));
+
+        let results = search(&ctx, &token, "/places/search?q=cofee").await;
+        let mut ids: Vec<Uuid> = results.iter().map(|result| result.place.id).collect();
+        ids.sort();
+        let mut expected = vec![bottle, barn];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert!(results.iter().all(|result| result.snippet.is_none()));
+
+        let results = search(&ctx, &token, "/places/search?q=noodles%20%26%20!").await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].place.id, noodles);
+
+        // Markup in notes comes back escaped, leaving `<mark>` as the only tag.
+        let tagged = create_place(
+            &ctx,
+            &token,
+            "Tea House",
+            noted("Cafe", "Matcha & <img src=x onerror=alert(1)> mochi"),
+        )
+        .await
+        .id;
+        let results = search(&ctx, &token, "/places/search?q=mochi").await;
+        assert_eq!(results[0].place.id, tagged);
+        assert_eq!(
+            results[0].snippet.as_deref(),
+            Some("Matcha &amp; &lt;img src=x onerror=alert(1)&gt; <mark>mochi</mark>")
+        );
+
+        assert!(search(&ctx, &token, "/places/search?q=sushi")
             .await
-            .expect("request succeeds");
+            .is_empty());
+
+        for uri in [
+            "/places/search",
+            "/places/search?q=%20!%20",
+            "/places/search?q=blue&limit=0",
+        ] {
+            let response = get(&ctx, uri, &token).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    async fn search(ctx: &TestContext, token: &str, uri: &str) -> Vec<PlaceSearchResultResponse> {
+        let response = get(ctx, uri, token).await;
+        assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        parse_json(response).await
+    }
+
+    fn noted(category: &str, note: &str) -> Vec<Part> {
+        vec![Part::text("category", category), Part::text("note", note)]
+    }
+
+    #[tokio::test]
+    async fn tags_are_shared_between_places_and_filterable() {
+        let ctx = TestContext::new(super::router).await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let bistro = create_place(
+            &ctx,
+            &token,
+            "Tagged",
+            vec![Part::text("tags", r#"["Date night", "Open late"]"#)],
+        )
+        .await;
+        assert_eq!(bistro.tags, ["Date night", "Open late"]);
+        // Tags are matched ignoring case, so the first spelling sticks.
+        let park = create_place(
+            &ctx,
+            &token,
+            "Tagged",
+            vec![Part::text("tags", r#"["Kid friendly", "date NIGHT"]"#)],
+        )
+        .await;
+        assert_eq!(park.tags, ["Date night", "Kid friendly"]);
+        let untagged = create_place(&ctx, &token, "Tagged", vec![Part::text("tags", "[]")]).await;
+        assert!(untagged.tags.is_empty());
+
+        let response = get(&ctx, "/tags", &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let tags: Vec<TagResponse> = parse_json(response).await;
+        let counts: Vec<(&str, i64)> = tags
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.place_count))
+            .collect();
+        assert_eq!(
+            counts,
+            [("Date night", 2), ("Kid friendly", 1), ("Open late", 1)]
+        );
+
+        for (uri, expected) in [
+            (
+                "/places?tag=open%20late,Kid%20Friendly",
+                vec![park.id, bistro.id],
+            ),
+            (
+                "/places?tag=date%20night,open%20late&tag_match=all",
+                vec![bistro.id],
+            ),
+            (
+                "/places?tag=open%20late,kid%20friendly&tag_match=all",
+                vec![],
+            ),
+            ("/places?tag=unknown", vec![]),
+        ] {
+            let response = get(&ctx, uri, &token).await;
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            let places: Vec<PlaceResponse> = parse_json(response).await;
+            let ids: Vec<Uuid> = places.iter().map(|place| place.id).collect();
+            assert_eq!(ids, expected, "{uri}");
+        }
+
+        let response = send_multipart(
+            &ctx,
+            Request::patch(format!("/places/{}", park.id)),
+            &token,
+            vec![Part::text("tags", "[]")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let updated: PlaceResponse = parse_json(response).await;
+        assert!(updated.tags.is_empty());
+
+        let response = get(&ctx, "/tags", &token).await;
+        let tags: Vec<TagResponse> = parse_json(response).await;
+        let names: Vec<&str> = tags.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(names, ["Date night", "Open late"]);
+
+        for tags in ["date night", r#"["a,b"]"#, r#"[""]"#] {
+            let response = send_multipart(
+                &ctx,
+                Request::patch(format!("/places/{}", park.id)),
+                &token,
+                vec![Part::text("tags", tags)],
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{tags}");
+        }
+    }
+
+    #[tokio::test]
+    async fn status_planned_date_and_priority_filter_the_list() {
+        let ctx = TestContext::new(super::router).await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let wishlist = create_place(
+            &ctx,
+            &token,
+            "Planned",
+            vec![
+                Part::text("status", "want_to_go"),
+                Part::text("planned_on", "2024-07-01"),
+                Part::text("priority", "5"),
+            ],
+        )
+        .await;
+        assert_eq!(wishlist.status, "want_to_go");
+        assert_eq!(wishlist.planned_on, "2024-07-01".parse().ok());
+        assert_eq!(wishlist.priority, Some(5));
+        let later = create_place(
+            &ctx,
+            &token,
+            "Planned",
+            vec![
+                Part::text("status", "want_to_go"),
+                Part::text("planned_on", "2024-09-15"),
+            ],
+        )
+        .await;
+        // Places saved without a status are memories.
+        let memory = create_place(&ctx, &token, "Planned", Vec::new()).await;
+        assert_eq!(memory.status, "visited");
+        let archived = create_place(
+            &ctx,
+            &token,
+            "Planned",
+            vec![Part::text("status", "archived")],
+        )
+        .await;
+
+        for (uri, expected) in [
+            ("/places?status=want_to_go", vec![later.id, wishlist.id]),
+            (
+                "/places?status=visited,archived",
+                vec![archived.id, memory.id],
+            ),
+            (
+                "/places?status=want_to_go&planned_after=2024-07-01&planned_before=2024-08-01",
+                vec![wishlist.id],
+            ),
+            ("/places?planned_after=2024-08-01", vec![later.id]),
+        ] {
+            let response = get(&ctx, uri, &token).await;
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            let places: Vec<PlaceResponse> = parse_json(response).await;
+            let ids: Vec<Uuid> = places.iter().map(|place| place.id).collect();
+            assert_eq!(ids, expected, "{uri}");
+        }
+
+        // Empty values clear the planned date and priority.
+        let response = send_multipart(
+            &ctx,
+            Request::patch(format!("/places/{}", wishlist.id)),
+            &token,
+            vec![
+                Part::text("status", "favorite"),
+                Part::text("planned_on", ""),
+                Part::text("priority", ""),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let favorite: PlaceResponse = parse_json(response).await;
+        assert_eq!(favorite.status, "favorite");
+        assert_eq!(favorite.planned_on, None);
+        assert_eq!(favorite.priority, None);
+
+        for (name, value) in [
+            ("status", "someday"),
+            ("priority", "6"),
+            ("planned_on", "next week"),
+        ] {
+            let response = send_multipart(
+                &ctx,
+                Request::patch(format!("/places/{}", later.id)),
+                &token,
+                vec![Part::text(name, value)],
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{name}");
+        }
+        let response = get(&ctx, "/places?status=someday", &token).await;
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
-    async fn create_place_for_test(ctx: &TestContext, token: &str, place_id: Uuid, image_id: Uuid) {
-        let (boundary, body) = multipart_body(vec![
-            Part::text("id", place_id.to_string()),
-            Part::text("name", "Sample"),
+    #[tokio::test]
+    async fn list_loads_images_with_a_constant_number_of_queries() {
+        let ctx = TestContext::new(super::router).await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let mut counts = Vec::new();
+        for _ in 0..3 {
+            for _ in 0..4 {
+                create_place(&ctx, &token, "Sample", with_image(Uuid::new_v4())).await;
+            }
+            for uri in ["/places", "/places?limit=100"] {
+                let (response, queries) = count_queries(get(&ctx, uri, &token)).await;
+                assert_eq!(response.status(), StatusCode::OK, "{uri}");
+                counts.push((uri, queries));
+            }
+        }
+
+        let (first, rest) = counts.split_at(2);
+        assert!(first.iter().all(|(_, queries)| *queries > 0));
+        for chunk in rest.chunks(2) {
+            assert_eq!(chunk, first);
+        }
+
+        let response = get(&ctx, "/places", &token).await;
+        let places: Vec<PlaceResponse> = parse_json(response).await;
+        assert_eq!(places.len(), 12);
+        assert!(places.iter().all(|place| place.images.len() == 1));
+    }
+
+    /// Follows `next_cursor` from `uri` until the last page and returns the listed ids.
+    async fn list_all_pages(ctx: &TestContext, token: &str, uri: &str) -> Vec<Uuid> {
+        let mut ids = Vec::new();
+        let mut next = uri.to_string();
+        loop {
+            let response = get(ctx, &next, token).await;
+            assert_eq!(response.status(), StatusCode::OK, "{next}");
+            let page: PlacePageResponse = parse_json(response).await;
+            ids.extend(page.places.iter().map(|place| place.id));
+            match page.next_cursor {
+                Some(cursor) => next = format!("{uri}&cursor={cursor}"),
+                None => return ids,
+            }
+        }
+    }
+
+    /// Form fields of a place in `category`, at `coordinates` when given.
+    fn categorized_at(category: &str, coordinates: Option<(f64, f64)>) -> Vec<Part> {
+        let mut parts = vec![Part::text("category", category)];
+        if let Some((latitude, longitude)) = coordinates {
+            parts.push(Part::text("latitude", latitude.to_string()));
+            parts.push(Part::text("longitude", longitude.to_string()));
+        }
+        parts
+    }
+
+    async fn get(ctx: &TestContext, uri: &str, token: &str) -> axum::response::Response {
+        send_json(ctx, Method::GET, uri, token, json!({})).await
+    }
+
+    /// Form fields of a coffee place with one image.
+    fn with_image(image_id: Uuid) -> Vec<Part> {
+        vec![
             Part::text("category", "Coffee"),
-            Part::text("location", "Somewhere"),
             Part::text("image_id", image_id.to_string()),
             Part::file("image", "orig.jpg", "image/jpeg", vec![1, 2, 3]),
-        ]);
-
-        let response = ctx
-            .app
-            .clone()
-            .oneshot(
-                Request::post("/places")
-                    .header("Authorization", format!("Bearer {}", token))
-                    .header(
-                        header::CONTENT_TYPE,
-                        format!("multipart/form-data; boundary={boundary}"),
-                    )
-                    .body(Body::from(body))
-                    .unwrap(),
-            )
-            .await
-            .expect("create request");
-        assert_eq!(response.status(), StatusCode::OK);
+        ]
     }
 }
@@ -0,0 +1,612 @@
+use axum::{
+    extract::{Extension, Path as AxumPath, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use mime_guess::mime;
+use serde::Deserialize;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::personal_token::Scope;
+use crate::refresh_token::{generate_token, hash_token};
+use crate::repository::share::{NewShareLink, ShareLinkRecord, ShareTarget};
+
+use super::middleware::{api_auth, with_scope, AuthUser};
+use super::models::{
+    ErrorResponse, ShareLinkResponse, SharedCollectionResponse, SharedPlaceResponse, SharedResponse,
+};
+use super::places::place_responses;
+
+/// Image types shared links serve inline. Everything else is sent as an attachment.
+const INLINE_IMAGE_TYPES: [&str; 4] = ["image/jpeg", "image/png", "image/gif", "image/webp"];
+
+/// Share links are managed with the places scopes. `/shared/...` is public: the token in the path
+/// is the only credential.
+pub fn router(state: AppState) -> Router {
+    let middleware_state = state.clone();
+
+    let read = Router::new().route("/shares", get(list_shares));
+    let write = Router::new()
+        .route("/shares", post(create_share))
+        .route("/shares/:id", delete(revoke_share));
+    let public = Router::new()
+        .route("/shared/:token", get(get_shared))
+        .route("/shared/:token/images/:image_id", get(get_shared_image));
+
+    with_scope(read, Scope::PlacesRead)
+        .merge(with_scope(write, Scope::PlacesWrite))
+        .route_layer(middleware::from_fn_with_state(middleware_state, api_auth))
+        .merge(public)
+        .with_state(state)
+}
+
+async fn list_shares(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<Vec<ShareLinkResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let shares = state
+        .share_repository()
+        .list_for_user(user.id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list share links");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        shares.into_iter().map(ShareLinkResponse::from).collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct CreateShareRequest {
+    place_id: Option<Uuid>,
+    collection_id: Option<Uuid>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Creates a link to one place or one collection. The token is only returned here.
+async fn create_share(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Json(payload): Json<CreateShareRequest>,
+) -> Result<(StatusCode, Json<ShareLinkResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let target = match (payload.place_id, payload.collection_id) {
+        (Some(place_id), None) => ShareTarget::Place(place_id),
+        (None, Some(collection_id)) => ShareTarget::Collection(collection_id),
+        _ => {
+            return Err(invalid_request(
+                "exactly one of place_id and collection_id is required",
+            ))
+        }
+    };
+
+    if payload
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(invalid_request("expires_at must be in the future"));
+    }
+
+    let token = generate_token();
+    let record = state
+        .share_repository()
+        .create(NewShareLink {
+            user_id: user.id,
+            target,
+            token_hash: &hash_token(&token),
+            expires_at: payload.expires_at,
+        })
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to create share link");
+            internal_error()
+        })?
+        .ok_or_else(|| match target {
+            ShareTarget::Place(_) => place_not_found(),
+            ShareTarget::Collection(_) => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(
+                    "collection_not_found",
+                    "collection does not exist",
+                )),
+            ),
+        })?;
+
+    info!(user_id = %user.id, share_id = %record.id, "share link created");
+
+    let mut response = ShareLinkResponse::from(record);
+    response.token = Some(token);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+async fn revoke_share(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(share_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let revoked = state
+        .share_repository()
+        .revoke(user.id, share_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to revoke share link");
+            internal_error()
+        })?;
+
+    if !revoked {
+        return Err(share_not_found());
+    }
+
+    info!(user_id = %user.id, %share_id, "share link revoked");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Shows the place or collection behind a share link. Revoked and expired links look the same as
+/// links that never existed.
+async fn get_shared(
+    State(state): State<AppState>,
+    AxumPath(token): AxumPath<String>,
+) -> Result<Json<SharedResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let share = find_share(&state, &token).await?;
+
+    if let Some(place_id) = share.place_id {
+        let place = state
+            .place_repository()
+            .find_for_user(share.user_id, place_id)
+            .await
+            .map_err(|err| {
+                error!(?err, "failed to load shared place");
+                internal_error()
+            })?
+            .ok_or_else(share_not_found)?;
+        let place = place_responses(&state, share.user_id, vec![place])
+            .await?
+            .pop()
+            .expect("one response per place");
+
+        return Ok(Json(SharedResponse::Place(
+            SharedPlaceResponse::from_place(place, &token),
+        )));
+    }
+
+    let collection_id = share.collection_id.ok_or_else(share_not_found)?;
+    let repository = state.collection_repository();
+    let collection = repository
+        .find_for_user(share.user_id, collection_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load shared collection");
+            internal_error()
+        })?
+        .ok_or_else(share_not_found)?;
+    let places = repository.list_places(collection_id).await.map_err(|err| {
+        error!(?err, "failed to load shared collection places");
+        internal_error()
+    })?;
+    let places = place_responses(&state, share.user_id, places).await?;
+
+    Ok(Json(SharedResponse::Collection(SharedCollectionResponse {
+        name: collection.name,
+        description: collection.description,
+        places: places
+            .into_iter()
+            .map(|place| SharedPlaceResponse::from_place(place, &token))
+            .collect(),
+    })))
+}
+
+async fn get_shared_image(
+    State(state): State<AppState>,
+    AxumPath((token, image_id)): AxumPath<(String, Uuid)>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let share = find_share(&state, &token).await?;
+
+    let image = state
+        .share_repository()
+        .find_image(&share, image_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load shared image");
+            internal_error()
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new("not_found", "image not found")),
+            )
+        })?;
+
+    let bytes = state
+        .image_store()
+        .get_image(image.place_id, &image.file_name)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to read image from disk");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(
+                    "image_io_error",
+                    "could not read image file",
+                )),
+            )
+        })?;
+
+    // Uploads are named by their uploader, so anything but a plain raster image is handed out
+    // as a download rather than rendered on this origin.
+    let mime = mime_guess::from_path(&image.file_name).first_or(mime::APPLICATION_OCTET_STREAM);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    if INLINE_IMAGE_TYPES.contains(&mime.essence_str()) {
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(mime.as_ref())
+                .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+        );
+    } else {
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("attachment"),
+        );
+    }
+
+    Ok((headers, bytes).into_response())
+}
+
+async fn find_share(
+    state: &AppState,
+    token: &str,
+) -> Result<ShareLinkRecord, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .share_repository()
+        .find_active(&hash_token(token))
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to look up share link");
+            internal_error()
+        })?
+        .ok_or_else(share_not_found)
+}
+
+fn invalid_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+fn place_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new("not_found", "place not found")),
+    )
+}
+
+fn share_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new(
+            "share_not_found",
+            "share link does not exist",
+        )),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "internal_error",
+            "unexpected server error",
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use crate::routes::models::CollectionResponse;
+    use crate::test_utils::router::{create_place, parse_json, send_json, Part, TestContext};
+
+    #[tokio::test]
+    async fn share_links_expose_a_redacted_view_until_revoked_or_expired() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone())
+                .merge(crate::routes::collections::router(state.clone()))
+                .merge(super::router(state))
+        })
+        .await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+        let other = ctx.insert_user().await;
+        let other_token = ctx.jwt.generate(&other, None).expect("jwt");
+
+        let shared_place =
+            create_place(&ctx, &token, "Ramen Bar", noted_with_image(b"RAMEN")).await;
+        let private_place =
+            create_place(&ctx, &token, "Secret Spot", noted_with_image(b"SECRET")).await;
+        let shared_image = shared_place.images[0].id;
+        let private_image = private_place.images[0].id;
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/shares",
+            &token,
+            json!({ "place_id": shared_place.id }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let share: ShareLinkResponse = parse_json(response).await;
+        let link_token = share.token.expect("token is returned on creation");
+        let stored_hash: String =
+            sqlx::query_scalar("SELECT token_hash FROM share_links WHERE id = $1")
+                .bind(share.id)
+                .fetch_one(&ctx.pool)
+                .await
+                .expect("share link stored");
+        assert_eq!(stored_hash, hash_token(&link_token));
+
+        // The shared view needs no JWT and leaves out the owner's private fields.
+        let response = get_anonymously(&ctx, &format!("/shared/{link_token}")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let raw: serde_json::Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(raw["kind"], "place");
+        for field in [
+            "user_id",
+            "note",
+            "status",
+            "visit_count",
+            "place_provider_id",
+        ] {
+            assert!(raw.get(field).is_none(), "{field} must not be shared");
+        }
+        let SharedResponse::Place(place) = serde_json::from_slice(&body).expect("shared place")
+        else {
+            panic!("expected a shared place");
+        };
+        assert_eq!(place.name, "Ramen Bar");
+        assert_eq!(
+            place.images[0].download_url,
+            format!("/shared/{link_token}/images/{shared_image}")
+        );
+
+        let response = get_anonymously(&ctx, &place.images[0].download_url).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/jpeg");
+        assert_eq!(
+            response.headers()[header::X_CONTENT_TYPE_OPTIONS],
+            "nosniff"
+        );
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&bytes[..], b"RAMEN");
+
+        // Uploads that are not raster images are never rendered on the API origin.
+        let page_place = create_place(
+            &ctx,
+            &token,
+            "Pop-up",
+            vec![
+                Part::text("image_id", Uuid::new_v4().to_string()),
+                Part::file(
+                    "image",
+                    "page.html",
+                    "text/html",
+                    b"<script>alert(1)</script>".to_vec(),
+                ),
+            ],
+        )
+        .await;
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/shares",
+            &token,
+            json!({ "place_id": page_place.id }),
+        )
+        .await;
+        let page_share: ShareLinkResponse = parse_json(response).await;
+        let response = get_anonymously(
+            &ctx,
+            &format!(
+                "/shared/{}/images/{}",
+                page_share.token.expect("token"),
+                page_place.images[0].id
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/octet-stream"
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment"
+        );
+        assert_eq!(
+            response.headers()[header::X_CONTENT_TYPE_OPTIONS],
+            "nosniff"
+        );
+
+        // The token does not unlock images of other places.
+        let response = get_anonymously(
+            &ctx,
+            &format!("/shared/{link_token}/images/{private_image}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Collections are shared with their places in order.
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/collections",
+            &token,
+            json!({ "name": "Noodles" }),
+        )
+        .await;
+        let collection: CollectionResponse = parse_json(response).await;
+        for place_id in [private_place.id, shared_place.id] {
+            send_json(
+                &ctx,
+                Method::POST,
+                &format!("/collections/{}/places", collection.id),
+                &token,
+                json!({ "place_id": place_id }),
+            )
+            .await;
+        }
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/shares",
+            &token,
+            json!({ "collection_id": collection.id }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let collection_share: ShareLinkResponse = parse_json(response).await;
+        let collection_token = collection_share.token.expect("token");
+        let response = get_anonymously(&ctx, &format!("/shared/{collection_token}")).await;
+        let SharedResponse::Collection(shared) = parse_json(response).await else {
+            panic!("expected a shared collection");
+        };
+        assert_eq!(shared.name, "Noodles");
+        let names: Vec<&str> = shared
+            .places
+            .iter()
+            .map(|place| place.name.as_str())
+            .collect();
+        assert_eq!(names, ["Secret Spot", "Ramen Bar"]);
+        let response = get_anonymously(
+            &ctx,
+            &format!("/shared/{collection_token}/images/{private_image}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Only the owner can share a place or revoke a link.
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/shares",
+            &other_token,
+            json!({ "place_id": shared_place.id }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/shares",
+            &token,
+            json!({ "place_id": shared_place.id, "collection_id": collection.id }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/shares/{}", share.id),
+            &other_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = send_json(&ctx, Method::GET, "/shares", &token, json!({})).await;
+        let listed: Vec<ShareLinkResponse> = parse_json(response).await;
+        assert_eq!(listed.len(), 3);
+        assert!(listed.iter().all(|share| share.token.is_none()));
+
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("/shares/{}", share.id),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        for uri in [
+            format!("/shared/{link_token}"),
+            format!("/shared/{link_token}/images/{shared_image}"),
+        ] {
+            let response = get_anonymously(&ctx, &uri).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        sqlx::query(
+            "UPDATE share_links SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1",
+        )
+        .bind(collection_share.id)
+        .execute(&ctx.pool)
+        .await
+        .expect("expire link");
+        let response = get_anonymously(&ctx, &format!("/shared/{collection_token}")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Links stop working, images included, once their creator loses access.
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            "/shares",
+            &token,
+            json!({ "collection_id": collection.id }),
+        )
+        .await;
+        let collection_share: ShareLinkResponse = parse_json(response).await;
+        let collection_token = collection_share.token.expect("token");
+        let image_uri = format!("/shared/{collection_token}/images/{private_image}");
+        let response = get_anonymously(&ctx, &image_uri).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        sqlx::query("DELETE FROM collection_members WHERE collection_id = $1 AND user_id = $2")
+            .bind(collection.id)
+            .bind(user.id)
+            .execute(&ctx.pool)
+            .await
+            .expect("leave collection");
+        for uri in [format!("/shared/{collection_token}"), image_uri] {
+            let response = get_anonymously(&ctx, &uri).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    fn noted_with_image(image: &[u8]) -> Vec<Part> {
+        vec![
+            Part::text("note", "Ask for the off-menu special"),
+            Part::text("image_id", Uuid::new_v4().to_string()),
+            Part::file("image", "photo.jpg", "image/jpeg", image.to_vec()),
+        ]
+    }
+
+    async fn get_anonymously(ctx: &TestContext, uri: &str) -> axum::response::Response {
+        ctx.app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .expect("request succeeds")
+    }
+}
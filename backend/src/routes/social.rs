@@ -0,0 +1,702 @@
+use axum::{
+    extract::{Extension, Path as AxumPath, Query, State},
+    http::StatusCode,
+    middleware,
+    response::Response,
+    routing::{get, put},
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::jwt::JwtClaims;
+use crate::personal_token::Scope;
+use crate::repository::social::{FeedCursor, FollowOutcome};
+
+use super::middleware::{api_auth, jwt_auth, with_scope, AuthUser};
+use super::models::{ConnectionResponse, ErrorResponse, FeedEventResponse, FeedPageResponse};
+use super::users::{avatar_not_found, avatar_response};
+
+const DEFAULT_FEED_PAGE_SIZE: i64 = 50;
+const MAX_FEED_PAGE_SIZE: i64 = 100;
+
+/// Follows and blocks are part of the account and live under `/usr`. The feed is made of places,
+/// so it is read with the places scope like they are, and so are the avatars of people in it.
+pub fn router(state: AppState) -> Router {
+    let middleware_state = state.clone();
+
+    let account = Router::new()
+        .route("/usr/following", get(list_following))
+        .route("/usr/following/:user_id", put(follow).delete(unfollow))
+        .route("/usr/followers", get(list_followers))
+        .route("/usr/blocks", get(list_blocks))
+        .route("/usr/blocks/:user_id", put(block).delete(unblock))
+        .route_layer(middleware::from_fn_with_state(
+            middleware_state.clone(),
+            jwt_auth,
+        ));
+    let feed = with_scope(
+        Router::new()
+            .route("/feed", get(get_feed))
+            .route("/users/:user_id/avatar", get(get_user_avatar)),
+        Scope::PlacesRead,
+    )
+    .route_layer(middleware::from_fn_with_state(middleware_state, api_auth));
+
+    account.merge(feed).with_state(state)
+}
+
+async fn list_following(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+) -> Result<Json<Vec<ConnectionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let following = state
+        .social_repository()
+        .list_following(claims.sub)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list followed users");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        following
+            .into_iter()
+            .map(ConnectionResponse::from)
+            .collect(),
+    ))
+}
+
+async fn list_followers(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+) -> Result<Json<Vec<ConnectionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let followers = state
+        .social_repository()
+        .list_followers(claims.sub)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list followers");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        followers
+            .into_iter()
+            .map(ConnectionResponse::from)
+            .collect(),
+    ))
+}
+
+async fn follow(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    AxumPath(user_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    if user_id == claims.sub {
+        return Err(invalid_request("you cannot follow yourself"));
+    }
+
+    let outcome = state
+        .social_repository()
+        .follow(claims.sub, user_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to follow user");
+            internal_error()
+        })?;
+
+    match outcome {
+        FollowOutcome::Followed => {
+            info!(follower_id = %claims.sub, followee_id = %user_id, "user followed");
+            Ok(StatusCode::NO_CONTENT)
+        }
+        FollowOutcome::UnknownUser => Err(user_not_found()),
+        FollowOutcome::Blocked => Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new("blocked", "you cannot follow this user")),
+        )),
+    }
+}
+
+async fn unfollow(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    AxumPath(user_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let unfollowed = state
+        .social_repository()
+        .unfollow(claims.sub, user_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to unfollow user");
+            internal_error()
+        })?;
+
+    if !unfollowed {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "follow_not_found",
+                "you do not follow this user",
+            )),
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_blocks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+) -> Result<Json<Vec<ConnectionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let blocks = state
+        .social_repository()
+        .list_blocks(claims.sub)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list blocked users");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        blocks.into_iter().map(ConnectionResponse::from).collect(),
+    ))
+}
+
+async fn block(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    AxumPath(user_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    if user_id == claims.sub {
+        return Err(invalid_request("you cannot block yourself"));
+    }
+
+    let blocked = state
+        .social_repository()
+        .block(claims.sub, user_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to block user");
+            internal_error()
+        })?;
+
+    if !blocked {
+        return Err(user_not_found());
+    }
+
+    info!(blocker_id = %claims.sub, blocked_id = %user_id, "user blocked");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn unblock(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    AxumPath(user_id): AxumPath<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let unblocked = state
+        .social_repository()
+        .unblock(claims.sub, user_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to unblock user");
+            internal_error()
+        })?;
+
+    if !unblocked {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "block_not_found",
+                "you have not blocked this user",
+            )),
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct FeedQuery {
+    cursor: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FeedCursorToken {
+    created_at: DateTime<Utc>,
+    id: Uuid,
+}
+
+async fn get_feed(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Query(query): Query<FeedQuery>,
+) -> Result<Json<FeedPageResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let limit = query.limit.unwrap_or(DEFAULT_FEED_PAGE_SIZE);
+    if !(1..=MAX_FEED_PAGE_SIZE).contains(&limit) {
+        return Err(invalid_request("limit must be between 1 and 100"));
+    }
+    let before = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    // One extra row tells whether another page follows.
+    let mut events = state
+        .social_repository()
+        .feed(user.id, before, limit + 1)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load feed");
+            internal_error()
+        })?;
+
+    let next_cursor = if events.len() as i64 > limit {
+        events.truncate(limit as usize);
+        events.last().map(|last| encode_cursor(last.cursor()))
+    } else {
+        None
+    };
+
+    Ok(Json(FeedPageResponse {
+        events: events.into_iter().map(FeedEventResponse::from).collect(),
+        next_cursor,
+    }))
+}
+
+/// Serves another user's uploaded avatar, the target of the `avatar_url` of users in lists,
+/// the feed, comments and reactions.
+async fn get_user_avatar(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(user_id): AxumPath<Uuid>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let file_name = state
+        .social_repository()
+        .find_avatar_file_name(user.id, user_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to look up avatar");
+            internal_error()
+        })?
+        .ok_or_else(avatar_not_found)?;
+
+    avatar_response(&state, user_id, &file_name).await
+}
+
+fn encode_cursor(cursor: FeedCursor) -> String {
+    let token = FeedCursorToken {
+        created_at: cursor.created_at,
+        id: cursor.id,
+    };
+    let json = serde_json::to_vec(&token).expect("cursor serializes");
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+fn decode_cursor(cursor: &str) -> Result<FeedCursor, (StatusCode, Json<ErrorResponse>)> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice::<FeedCursorToken>(&json).ok())
+        .map(|token| FeedCursor {
+            created_at: token.created_at,
+            id: token.id,
+        })
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new("invalid_cursor", "cursor is malformed")),
+            )
+        })
+}
+
+fn invalid_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+fn user_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new("user_not_found", "user does not exist")),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "internal_error",
+            "unexpected server error",
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header, Method, Request};
+    use http_body_util::BodyExt;
+    use serde_json::json;
+
+    use crate::test_utils::router::{
+        create_place, parse_json, send_json, send_multipart, Part, TestContext,
+    };
+
+    #[tokio::test]
+    async fn feed_shows_visible_activity_of_followed_users_until_blocked() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone())
+                .merge(crate::routes::visits::router(state.clone()))
+                .merge(super::router(state))
+        })
+        .await;
+        let alice = ctx.insert_user().await;
+        let alice_token = ctx.jwt.generate(&alice, None).expect("jwt");
+        let bob = ctx.insert_user().await;
+        let bob_token = ctx.jwt.generate(&bob, None).expect("jwt");
+
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &follow_uri(bob.id),
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &follow_uri(Uuid::new_v4()),
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &follow_uri(alice.id),
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response =
+            send_json(&ctx, Method::GET, "/usr/followers", &alice_token, json!({})).await;
+        let followers: Vec<ConnectionResponse> = parse_json(response).await;
+        assert_eq!(followers.len(), 1);
+        assert_eq!(followers[0].user_id, bob.id);
+
+        let private = create_place(
+            &ctx,
+            &alice_token,
+            "Secret Spot",
+            vec![Part::text("visibility", "private")],
+        )
+        .await;
+        assert_eq!(private.visibility, "private");
+        let friends = create_place(
+            &ctx,
+            &alice_token,
+            "Family Diner",
+            vec![Part::text("visibility", "friends")],
+        )
+        .await;
+        let public = create_place(
+            &ctx,
+            &alice_token,
+            "Harbour Cafe",
+            vec![Part::text("visibility", "public")],
+        )
+        .await;
+        let opened = create_place(
+            &ctx,
+            &alice_token,
+            "Old Library",
+            vec![Part::text("visibility", "private")],
+        )
+        .await;
+        let response = send_multipart(
+            &ctx,
+            Request::patch(format!("/places/{}", opened.id)),
+            &alice_token,
+            vec![Part::text("visibility", "public")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Friends-only places show up once the follow is mutual.
+        let feed = get_feed(&ctx, &bob_token, "/feed").await;
+        let kinds: Vec<(&str, Uuid)> = feed
+            .events
+            .iter()
+            .map(|event| (event.kind.as_str(), event.place.id))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("place_shared", opened.id),
+                ("place_added", opened.id),
+                ("place_added", public.id),
+            ]
+        );
+        assert_eq!(feed.events[0].user.id, alice.id);
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &follow_uri(bob.id),
+            &alice_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let feed = get_feed(&ctx, &bob_token, "/feed").await;
+        assert_eq!(feed.events.len(), 4);
+        assert!(feed.events.iter().all(|event| event.place.id != private.id));
+        assert_eq!(feed.events[3].place.id, friends.id);
+
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &format!("/places/{}/visits", public.id),
+            &alice_token,
+            json!({ "visited_on": "2024-05-01", "rating": 4 }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // Pages follow each other without gaps or repeats.
+        let first = get_feed(&ctx, &bob_token, "/feed?limit=2").await;
+        assert_eq!(first.events[0].kind, "place_visited");
+        assert_eq!(first.events[0].rating, Some(4));
+        let cursor = first.next_cursor.expect("more events follow");
+        let second = get_feed(&ctx, &bob_token, &format!("/feed?limit=2&cursor={cursor}")).await;
+        let cursor = second.next_cursor.expect("more events follow");
+        let third = get_feed(&ctx, &bob_token, &format!("/feed?limit=2&cursor={cursor}")).await;
+        assert!(third.next_cursor.is_none());
+        let mut ids: Vec<Uuid> = [first.events, second.events, third.events]
+            .into_iter()
+            .flatten()
+            .map(|event| event.id)
+            .collect();
+        assert_eq!(ids.len(), 5);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            "/feed?cursor=garbage",
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // A block ends both follows, empties the feed and keeps it empty.
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &format!("/usr/blocks/{}", bob.id),
+            &alice_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let feed = get_feed(&ctx, &bob_token, "/feed").await;
+        assert!(feed.events.is_empty());
+        let response = send_json(&ctx, Method::GET, "/usr/following", &bob_token, json!({})).await;
+        let following: Vec<ConnectionResponse> = parse_json(response).await;
+        assert!(following.is_empty());
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &follow_uri(alice.id),
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send_json(&ctx, Method::GET, "/usr/blocks", &alice_token, json!({})).await;
+        let blocks: Vec<ConnectionResponse> = parse_json(response).await;
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].user_id, bob.id);
+    }
+
+    #[tokio::test]
+    async fn places_opened_from_the_feed_show_only_what_their_visibility_allows() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone()).merge(super::router(state))
+        })
+        .await;
+        let alice = ctx.insert_user().await;
+        let alice_token = ctx.jwt.generate(&alice, None).expect("jwt");
+        let bob = ctx.insert_user().await;
+        let bob_token = ctx.jwt.generate(&bob, None).expect("jwt");
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &follow_uri(alice.id),
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let private = create_place(
+            &ctx,
+            &alice_token,
+            "Secret Spot",
+            vec![Part::text("visibility", "private")],
+        )
+        .await;
+        create_place(
+            &ctx,
+            &alice_token,
+            "Harbour Cafe",
+            vec![
+                Part::text("visibility", "public"),
+                Part::text("note", "Ask for the window table"),
+                Part::text("image_id", Uuid::new_v4().to_string()),
+                Part::file("image", "cafe.jpg", "image/jpeg", b"CAFE".to_vec()),
+            ],
+        )
+        .await;
+
+        let feed = get_feed(&ctx, &bob_token, "/feed").await;
+        let place_id = feed.events[0].place.id;
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/places/{place_id}"),
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let place: serde_json::Value = parse_json(response).await;
+        assert_eq!(place["name"], "Harbour Cafe");
+        assert_eq!(place["user_id"], json!(alice.id));
+        assert!(place.get("note").is_none());
+        assert!(place.get("status").is_none());
+        let download_url = place["images"][0]["download_url"]
+            .as_str()
+            .expect("image")
+            .to_string();
+        let response = send_json(&ctx, Method::GET, &download_url, &bob_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&bytes[..], b"CAFE");
+
+        // Changing it still takes membership.
+        let response = send_multipart(
+            &ctx,
+            Request::patch(format!("/places/{place_id}")),
+            &bob_token,
+            vec![Part::text("name", "Mine now")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/places/{}", private.id),
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &format!("/usr/blocks/{}", bob.id),
+            &alice_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send_json(&ctx, Method::GET, &download_url, &bob_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn avatars_of_other_users_are_served_until_blocked() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::users::router(state.clone()).merge(super::router(state))
+        })
+        .await;
+        let alice = ctx.insert_user().await;
+        let alice_token = ctx.jwt.generate(&alice, None).expect("jwt");
+        let bob = ctx.insert_user().await;
+        let bob_token = ctx.jwt.generate(&bob, None).expect("jwt");
+        for (token, avatar) in [(&alice_token, b"ALICE"), (&bob_token, b"BOBBY")] {
+            let response = send_multipart(
+                &ctx,
+                Request::put("/usr/avatar"),
+                token,
+                vec![Part::file("avatar", "me.png", "image/png", avatar.to_vec())],
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // Lists point at the avatar of the user listed, not at the viewer's own.
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &follow_uri(alice.id),
+            &bob_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send_json(&ctx, Method::GET, "/usr/following", &bob_token, json!({})).await;
+        let following: Vec<ConnectionResponse> = parse_json(response).await;
+        let avatar_url = following[0].avatar_url.clone().expect("uploaded avatar");
+        assert!(avatar_url.starts_with(&format!("/users/{}/avatar?v=", alice.id)));
+        let response = send_json(&ctx, Method::GET, &avatar_url, &bob_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&bytes[..], b"ALICE");
+
+        let response = send_json(
+            &ctx,
+            Method::PUT,
+            &format!("/usr/blocks/{}", bob.id),
+            &alice_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send_json(&ctx, Method::GET, &avatar_url, &bob_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(
+            &ctx,
+            Method::GET,
+            &format!("/users/{}/avatar", bob.id),
+            &alice_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    fn follow_uri(user_id: Uuid) -> String {
+        format!("/usr/following/{user_id}")
+    }
+
+    async fn get_feed(ctx: &TestContext, token: &str, uri: &str) -> FeedPageResponse {
+        let response = send_json(ctx, Method::GET, uri, token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        parse_json(response).await
+    }
+}
@@ -1,22 +1,61 @@
 use axum::{
-    extract::{Extension, State},
-    http::StatusCode,
+    extract::{multipart::Multipart, DefaultBodyLimit, Extension, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     middleware,
-    routing::get,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
     Json, Router,
 };
-use tracing::error;
+use chrono::{DateTime, Utc};
+use mime_guess::mime;
+use serde::Deserialize;
+use tracing::{error, info};
+use uuid::Uuid;
 
 use crate::app_state::AppState;
 use crate::jwt::JwtClaims;
+use crate::personal_token::{self, Scope};
+use crate::repository::auth::{IdentityLink, IdentityUnlink, ProfileField, ProfileUpdate};
+use crate::repository::image_store::ImageUpload;
+use crate::repository::token::NewPersonalToken;
 
 use super::middleware::jwt_auth;
-use super::models::{ErrorResponse, UserResponse};
+use super::models::{
+    ErrorResponse, IdentityResponse, PersonalTokenResponse, SessionResponse, UserResponse,
+};
+use super::oauth::{map_auth_error, provider_not_configured};
+
+const MAX_TOKEN_NAME_CHARS: usize = 100;
+const MAX_NAME_CHARS: usize = 100;
+const MAX_BIO_CHARS: usize = 500;
+const MAX_HOME_CITY_CHARS: usize = 100;
+const MAX_AVATAR_SIZE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Where users fetch their own uploaded avatar. Other users get it from `/users/{id}/avatar`.
+pub(super) const UPLOADED_AVATAR_PATH: &str = "/usr/avatar";
 
 pub fn router(state: AppState) -> Router {
     let middleware_state = state.clone();
     Router::new()
-        .route("/usr", get(current_user).delete(delete_user))
+        .route(
+            "/usr",
+            get(current_user).patch(update_profile).delete(delete_user),
+        )
+        .route(
+            "/usr/avatar",
+            get(get_avatar)
+                .put(upload_avatar)
+                .layer(DefaultBodyLimit::max(MAX_AVATAR_SIZE_BYTES)),
+        )
+        .route("/usr/identities", get(list_identities).post(link_identity))
+        .route("/usr/identities/:id", delete(unlink_identity))
+        .route("/usr/sessions", get(list_sessions))
+        .route("/usr/sessions/:id", delete(revoke_session))
+        .route(
+            "/usr/tokens",
+            get(list_personal_tokens).post(create_personal_token),
+        )
+        .route("/usr/tokens/:id", delete(revoke_personal_token))
         .route_layer(middleware::from_fn_with_state(middleware_state, jwt_auth))
         .with_state(state)
 }
@@ -57,10 +96,516 @@ async fn delete_user(
     for place_id in place_ids {
         image_store.remove_place_dir(place_id).await;
     }
+    image_store.remove_avatar_dir(claims.sub).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct UpdateProfileRequest {
+    name: Option<String>,
+    bio: Option<String>,
+    home_city: Option<String>,
+    user_owned_fields: Option<Vec<String>>,
+}
+
+/// Edits the profile. Omitted fields are kept and empty strings clear a field. Setting `name`
+/// marks it as user-owned unless `user_owned_fields` is sent as well.
+async fn update_profile(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    Json(payload): Json<UpdateProfileRequest>,
+) -> Result<Json<UserResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let name = validate_profile_text(payload.name.as_deref(), "name", MAX_NAME_CHARS)?;
+    let bio = validate_profile_text(payload.bio.as_deref(), "bio", MAX_BIO_CHARS)?;
+    let home_city = validate_profile_text(
+        payload.home_city.as_deref(),
+        "home_city",
+        MAX_HOME_CITY_CHARS,
+    )?;
+
+    let user_owned_fields = match &payload.user_owned_fields {
+        Some(values) => {
+            let mut fields: Vec<String> = Vec::new();
+            for value in values {
+                let field = ProfileField::parse(value)
+                    .ok_or_else(|| invalid_request(format!("unknown profile field '{value}'")))?;
+                if !fields.iter().any(|existing| existing == field.as_str()) {
+                    fields.push(field.as_str().to_string());
+                }
+            }
+            Some(fields)
+        }
+        None => None,
+    };
+
+    let user = state
+        .auth_repository()
+        .update_profile(
+            claims.sub,
+            ProfileUpdate {
+                name,
+                bio,
+                home_city,
+                user_owned_fields: user_owned_fields.as_deref(),
+            },
+        )
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to update profile");
+            internal_error()
+        })?
+        .ok_or_else(user_not_found)?;
+
+    Ok(Json(UserResponse::from(user)))
+}
+
+fn validate_profile_text<'a>(
+    value: Option<&'a str>,
+    label: &str,
+    max_chars: usize,
+) -> Result<Option<&'a str>, (StatusCode, Json<ErrorResponse>)> {
+    let value = value.map(str::trim);
+    if value.is_some_and(|value| value.chars().count() > max_chars) {
+        return Err(invalid_request(format!(
+            "{label} must be at most {max_chars} characters"
+        )));
+    }
+    Ok(value)
+}
+
+/// Replaces the avatar with the `avatar` image of a multipart upload. The uploaded avatar is
+/// user-owned, so signing in again keeps it.
+async fn upload_avatar(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    mut multipart: Multipart,
+) -> Result<Json<UserResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(|err| {
+        error!(?err, "failed to read form-data field");
+        invalid_request("invalid multipart payload")
+    })? {
+        if field.name() != Some("avatar") {
+            continue;
+        }
+        let is_image = field
+            .content_type()
+            .is_some_and(|content_type| content_type.starts_with("image/"));
+        if !is_image {
+            return Err(invalid_request("avatar must be an image"));
+        }
+        let file_name = field.file_name().map(|value| value.to_owned());
+        let bytes = field.bytes().await.map_err(|err| {
+            error!(?err, "failed to read avatar bytes");
+            invalid_request("avatar upload failed")
+        })?;
+        upload = Some(ImageUpload {
+            id: Uuid::new_v4(),
+            file_name,
+            bytes: bytes.to_vec(),
+        });
+    }
+    let upload = upload
+        .filter(|upload| !upload.bytes.is_empty())
+        .ok_or_else(|| invalid_request("missing avatar image"))?;
+
+    let image_store = state.image_store();
+    let stored = image_store
+        .save_avatar(claims.sub, upload)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to write avatar to disk");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(
+                    "image_io_error",
+                    "could not store avatar",
+                )),
+            )
+        })?;
+
+    // The version parameter changes with every upload so clients do not show a cached avatar.
+    let avatar_url = format!("{UPLOADED_AVATAR_PATH}?v={}", stored.id);
+    let updated = state
+        .auth_repository()
+        .set_uploaded_avatar(claims.sub, &avatar_url, &stored.file_name)
+        .await;
+
+    let (user, previous_file_name) = match updated {
+        Ok(Some(updated)) => updated,
+        Ok(None) => {
+            image_store
+                .remove_avatar(claims.sub, &stored.file_name)
+                .await;
+            return Err(user_not_found());
+        }
+        Err(err) => {
+            error!(?err, "failed to save avatar");
+            image_store
+                .remove_avatar(claims.sub, &stored.file_name)
+                .await;
+            return Err(internal_error());
+        }
+    };
+
+    if let Some(previous_file_name) = previous_file_name {
+        image_store
+            .remove_avatar(claims.sub, &previous_file_name)
+            .await;
+    }
+
+    Ok(Json(UserResponse::from(user)))
+}
+
+async fn get_avatar(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let file_name = state
+        .auth_repository()
+        .find_avatar_file_name(claims.sub)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to look up avatar");
+            internal_error()
+        })?
+        .ok_or_else(avatar_not_found)?;
+
+    avatar_response(&state, claims.sub, &file_name).await
+}
+
+/// Reads an uploaded avatar from disk and serves it with the content type of its file name.
+pub(super) async fn avatar_response(
+    state: &AppState,
+    user_id: Uuid,
+    file_name: &str,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let bytes = state
+        .image_store()
+        .get_avatar(user_id, file_name)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to read avatar from disk");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(
+                    "image_io_error",
+                    "could not read avatar file",
+                )),
+            )
+        })?;
+
+    let mime = mime_guess::from_path(file_name).first_or(mime::APPLICATION_OCTET_STREAM);
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(mime.as_ref())
+            .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+    );
+
+    Ok((headers, bytes).into_response())
+}
+
+#[derive(Deserialize)]
+struct LinkIdentityRequest {
+    provider: String,
+    code: String,
+    code_verifier: Option<String>,
+    nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CreatePersonalTokenRequest {
+    name: String,
+    scopes: Vec<String>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+async fn list_identities(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+) -> Result<Json<Vec<IdentityResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let identities = state
+        .auth_repository()
+        .list_identities(claims.sub)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list identities");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        identities.into_iter().map(IdentityResponse::from).collect(),
+    ))
+}
+
+/// Completes an OAuth flow for `provider` and attaches the resulting identity to the signed-in
+/// user instead of signing in with it.
+async fn link_identity(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    Json(payload): Json<LinkIdentityRequest>,
+) -> Result<(StatusCode, Json<IdentityResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let Some(service) = state.auth_service(&payload.provider) else {
+        return Err(provider_not_configured(&payload.provider));
+    };
+
+    let profile = service
+        .fetch_identity(
+            &payload.code,
+            payload.code_verifier.as_deref(),
+            payload.nonce.as_deref(),
+        )
+        .await
+        .map_err(map_auth_error)?;
+
+    let link = state
+        .auth_repository()
+        .link_identity(claims.sub, &payload.provider, &profile.sub)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to link identity");
+            internal_error()
+        })?;
+
+    match link {
+        IdentityLink::Linked(identity) => {
+            info!(user_id = %claims.sub, provider = %payload.provider, "identity linked");
+            Ok((StatusCode::CREATED, Json(IdentityResponse::from(identity))))
+        }
+        IdentityLink::AlreadyLinked(identity) => {
+            Ok((StatusCode::OK, Json(IdentityResponse::from(identity))))
+        }
+        IdentityLink::OwnedByAnotherUser => Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new(
+                "identity_already_linked",
+                "this identity already signs in to another account",
+            )),
+        )),
+    }
+}
+
+async fn unlink_identity(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    Path(identity_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let outcome = state
+        .auth_repository()
+        .unlink_identity(claims.sub, identity_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to unlink identity");
+            internal_error()
+        })?;
+
+    match outcome {
+        IdentityUnlink::Unlinked => {
+            info!(user_id = %claims.sub, %identity_id, "identity unlinked");
+            Ok(StatusCode::NO_CONTENT)
+        }
+        IdentityUnlink::NotFound => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "identity_not_found",
+                "identity does not exist",
+            )),
+        )),
+        IdentityUnlink::LastIdentity => Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new(
+                "last_login_method",
+                "cannot remove the only remaining login method",
+            )),
+        )),
+    }
+}
 
+async fn list_sessions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+) -> Result<Json<Vec<SessionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let sessions = state
+        .token_repository()
+        .list_sessions(claims.sub)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list sessions");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|session| SessionResponse::from_record(session, claims.sid))
+            .collect(),
+    ))
+}
+
+/// Signs the device out: its refresh tokens stop working and its access tokens are rejected.
+async fn revoke_session(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let revoked = state
+        .token_repository()
+        .revoke_session(claims.sub, session_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to revoke session");
+            internal_error()
+        })?;
+
+    if !revoked {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "session_not_found",
+                "session does not exist",
+            )),
+        ));
+    }
+
+    info!(user_id = %claims.sub, %session_id, "session revoked");
     Ok(StatusCode::NO_CONTENT)
 }
 
+async fn list_personal_tokens(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+) -> Result<Json<Vec<PersonalTokenResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let tokens = state
+        .token_repository()
+        .list_personal_tokens(claims.sub)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list personal access tokens");
+            internal_error()
+        })?;
+
+    Ok(Json(
+        tokens
+            .into_iter()
+            .map(PersonalTokenResponse::from)
+            .collect(),
+    ))
+}
+
+/// Creates a personal access token. Only a JWT can do this, so a leaked token cannot be used to
+/// mint further tokens.
+async fn create_personal_token(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    Json(payload): Json<CreatePersonalTokenRequest>,
+) -> Result<(StatusCode, Json<PersonalTokenResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TOKEN_NAME_CHARS {
+        return Err(invalid_request(format!(
+            "name must be between 1 and {MAX_TOKEN_NAME_CHARS} characters"
+        )));
+    }
+
+    let mut scopes: Vec<Scope> = Vec::new();
+    for value in &payload.scopes {
+        let scope = Scope::parse(value).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "invalid_scope",
+                    format!("unknown scope '{value}'"),
+                )),
+            )
+        })?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() {
+        return Err(invalid_request("at least one scope is required"));
+    }
+
+    if payload
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(invalid_request("expires_at must be in the future"));
+    }
+
+    let token = personal_token::generate();
+    let scopes: Vec<String> = scopes
+        .into_iter()
+        .map(|scope| scope.as_str().to_string())
+        .collect();
+    let record = state
+        .token_repository()
+        .create_personal_token(NewPersonalToken {
+            user_id: claims.sub,
+            name,
+            token_hash: &personal_token::hash(&token),
+            scopes: &scopes,
+            expires_at: payload.expires_at,
+        })
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to create personal access token");
+            internal_error()
+        })?;
+
+    info!(user_id = %claims.sub, token_id = %record.id, "personal access token created");
+
+    let mut response = PersonalTokenResponse::from(record);
+    response.token = Some(token);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+async fn revoke_personal_token(
+    State(state): State<AppState>,
+    Extension(claims): Extension<JwtClaims>,
+    Path(token_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let revoked = state
+        .token_repository()
+        .revoke_personal_token(claims.sub, token_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to revoke personal access token");
+            internal_error()
+        })?;
+
+    if !revoked {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "token_not_found",
+                "token does not exist",
+            )),
+        ));
+    }
+
+    info!(user_id = %claims.sub, %token_id, "personal access token revoked");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn invalid_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+pub(super) fn avatar_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new(
+            "avatar_not_found",
+            "no avatar has been uploaded",
+        )),
+    )
+}
+
 fn user_not_found() -> (StatusCode, Json<ErrorResponse>) {
     (
         StatusCode::NOT_FOUND,
@@ -81,10 +626,12 @@ fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth_service::MockUserProfile;
     use crate::repository::auth::IdentityProfile;
     use crate::test_utils::router::{multipart_body, parse_json, Part, TestContext};
     use axum::body::Body;
     use axum::http::{header, Request};
+    use http_body_util::BodyExt;
     use tokio::time::{sleep, Duration};
     use tower::ServiceExt;
     use uuid::Uuid;
@@ -104,7 +651,7 @@ mod tests {
             .await
             .expect("insert user");
 
-        let token = ctx.jwt.generate(&user).expect("jwt");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
 
         let response = ctx
             .app
@@ -145,7 +692,7 @@ mod tests {
         })
         .await;
         let user = ctx.insert_user().await;
-        let token = ctx.jwt.generate(&user).expect("jwt");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
 
         let place_id = Uuid::new_v4();
         let image_id = Uuid::new_v4();
@@ -230,7 +777,16 @@ mod tests {
         })
         .await;
         let user = ctx.insert_user().await;
-        let token = ctx.jwt.generate(&user).expect("jwt");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let category_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO categories (id, user_id, name) VALUES ($1, $2, 'Cat') RETURNING id",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user.id)
+        .fetch_one(&ctx.pool)
+        .await
+        .expect("insert category");
 
         let app = ctx.app.clone();
         let pool = ctx.pool.clone();
@@ -251,10 +807,11 @@ mod tests {
             // Allow delete request to start and take locks first.
             sleep(Duration::from_millis(10)).await;
             sqlx::query(
-                "INSERT INTO places (id, user_id, name, category, location) VALUES ($1, $2, 'Cafe', 'Cat', 'Loc')",
+                "INSERT INTO places (id, user_id, name, category_id, category, location) VALUES ($1, $2, 'Cafe', $3, 'Cat', 'Loc')",
             )
             .bind(Uuid::new_v4())
             .bind(user_id)
+            .bind(category_id)
             .execute(&pool)
             .await
         });
@@ -285,4 +842,410 @@ mod tests {
             .unwrap();
         assert!(user_row.is_none());
     }
+
+    #[tokio::test]
+    async fn links_lists_and_unlinks_identities() {
+        let ctx = TestContext::with_mock_providers(
+            vec![("google-android", android_profile())],
+            super::router,
+        )
+        .await;
+        let user = ctx
+            .auth_repo()
+            .upsert_user_with_identity(IdentityProfile {
+                provider: "google-ios",
+                provider_user_id: "ios-123",
+                email: Some("user@example.com"),
+                name: None,
+                avatar_url: None,
+            })
+            .await
+            .expect("insert user");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let response = link(&ctx, &token, "google-android").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let linked: IdentityResponse = parse_json(response).await;
+        assert_eq!(linked.provider_user_id, "android-456");
+
+        // Linking the same identity again is a no-op.
+        let response = link(&ctx, &token, "google-android").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send(&ctx, Request::get("/usr/identities"), &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let identities: Vec<IdentityResponse> = parse_json(response).await;
+        let providers: Vec<&str> = identities.iter().map(|i| i.provider.as_str()).collect();
+        assert_eq!(providers, ["google-ios", "google-android"]);
+
+        let response = link(&ctx, &token, "github").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let uri = format!("/usr/identities/{}", Uuid::new_v4());
+        let response = send(&ctx, Request::delete(uri), &token).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let uri = format!("/usr/identities/{}", identities[0].id);
+        let response = send(&ctx, Request::delete(uri), &token).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let uri = format!("/usr/identities/{}", linked.id);
+        let response = send(&ctx, Request::delete(uri), &token).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = parse_json(response).await;
+        assert_eq!(body["error"], "last_login_method");
+    }
+
+    #[tokio::test]
+    async fn link_rejects_identity_of_another_account() {
+        let ctx = TestContext::with_mock_providers(
+            vec![("google-android", android_profile())],
+            super::router,
+        )
+        .await;
+        let other = ctx
+            .auth_repo()
+            .upsert_user_with_identity(IdentityProfile {
+                provider: "google-android",
+                provider_user_id: "android-456",
+                email: None,
+                name: None,
+                avatar_url: None,
+            })
+            .await
+            .expect("insert other user");
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let response = link(&ctx, &token, "google-android").await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = parse_json(response).await;
+        assert_eq!(body["error"], "identity_already_linked");
+
+        let owner: Uuid = sqlx::query_scalar(
+            "SELECT user_id FROM oauth_identities WHERE provider_user_id = 'android-456'",
+        )
+        .fetch_one(&ctx.pool)
+        .await
+        .unwrap();
+        assert_eq!(owner, other.id);
+    }
+
+    fn android_profile() -> MockUserProfile {
+        MockUserProfile {
+            provider_user_id: "android-456".to_string(),
+            email: Some("user@example.com".to_string()),
+            name: None,
+            avatar_url: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn personal_access_tokens_are_scoped_and_revocable() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone()).merge(super::router(state))
+        })
+        .await;
+        let user = ctx.insert_user().await;
+        let jwt = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let response = post_json(
+            &ctx,
+            "/usr/tokens",
+            &jwt,
+            serde_json::json!({ "name": "sync", "scopes": ["places:delete"] }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = post_json(
+            &ctx,
+            "/usr/tokens",
+            &jwt,
+            serde_json::json!({ "name": "sync", "scopes": ["places:read"], "expires_at": "2020-01-01T00:00:00Z" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_json(
+            &ctx,
+            "/usr/tokens",
+            &jwt,
+            serde_json::json!({ "name": " sync script ", "scopes": ["places:read", "places:read"] }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created: PersonalTokenResponse = parse_json(response).await;
+        let pat = created.token.expect("token is returned on creation");
+        assert!(pat.starts_with(personal_token::TOKEN_PREFIX));
+        assert_eq!(created.name, "sync script");
+        assert_eq!(created.scopes, ["places:read"]);
+
+        let stored_hash: String =
+            sqlx::query_scalar("SELECT token_hash FROM personal_access_tokens WHERE id = $1")
+                .bind(created.id)
+                .fetch_one(&ctx.pool)
+                .await
+                .unwrap();
+        assert_ne!(stored_hash, pat);
+
+        let response = send(&ctx, Request::get("/places"), &pat).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&ctx, Request::post("/places"), &pat).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let image_uri = format!("/places/{}/images/{}", Uuid::new_v4(), Uuid::new_v4());
+        let response = send(&ctx, Request::get(image_uri), &pat).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        // Account management stays limited to tokens obtained through a login.
+        let response = send(&ctx, Request::get("/usr/tokens"), &pat).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = send(&ctx, Request::get("/usr/tokens"), &jwt).await;
+        let listed: Vec<PersonalTokenResponse> = parse_json(response).await;
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].token.is_none());
+        assert!(listed[0].last_used_at.is_some());
+
+        let uri = format!("/usr/tokens/{}", created.id);
+        let response = send(&ctx, Request::delete(&uri), &jwt).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send(&ctx, Request::delete(&uri), &jwt).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send(&ctx, Request::get("/places"), &pat).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let expired = personal_token::generate();
+        sqlx::query(
+            r#"
+            INSERT INTO personal_access_tokens (id, user_id, name, token_hash, scopes, expires_at)
+            VALUES ($1, $2, 'old', $3, '{places:read}', NOW() - INTERVAL '1 minute')
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user.id)
+        .bind(personal_token::hash(&expired))
+        .execute(&ctx.pool)
+        .await
+        .unwrap();
+        let response = send(&ctx, Request::get("/places"), &expired).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn profile_edits_survive_the_next_login() {
+        let ctx = TestContext::new(super::router).await;
+        let repo = ctx.auth_repo();
+        let login = |name: &'static str, avatar_url: &'static str| IdentityProfile {
+            provider: "google",
+            provider_user_id: "profile-owner",
+            email: Some("owner@example.com"),
+            name: Some(name),
+            avatar_url: Some(avatar_url),
+        };
+        let user = repo
+            .upsert_user_with_identity(login("Provider Name", "https://example.com/a.png"))
+            .await
+            .expect("insert user");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let response = patch_json(
+            &ctx,
+            &token,
+            serde_json::json!({ "name": " Ana ", "bio": "Coffee first", "home_city": "Lisbon" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let profile: UserResponse = parse_json(response).await;
+        assert_eq!(profile.name.as_deref(), Some("Ana"));
+        assert_eq!(profile.bio.as_deref(), Some("Coffee first"));
+        assert_eq!(profile.home_city.as_deref(), Some("Lisbon"));
+        assert_eq!(profile.user_owned_fields, ["name"]);
+
+        // The provider still controls the avatar, but no longer the name.
+        let user = repo
+            .upsert_user_with_identity(login("Provider Name", "https://example.com/b.png"))
+            .await
+            .expect("login again");
+        assert_eq!(user.name.as_deref(), Some("Ana"));
+        assert_eq!(
+            user.avatar_url.as_deref(),
+            Some("https://example.com/b.png")
+        );
+        assert_eq!(user.bio.as_deref(), Some("Coffee first"));
+
+        let response = patch_json(&ctx, &token, serde_json::json!({ "bio": "" })).await;
+        let profile: UserResponse = parse_json(response).await;
+        assert_eq!(profile.bio, None);
+        assert_eq!(profile.home_city.as_deref(), Some("Lisbon"));
+
+        let response =
+            patch_json(&ctx, &token, serde_json::json!({ "user_owned_fields": [] })).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let user = repo
+            .upsert_user_with_identity(login("Provider Name", "https://example.com/b.png"))
+            .await
+            .expect("login again");
+        assert_eq!(user.name.as_deref(), Some("Provider Name"));
+
+        let response = patch_json(
+            &ctx,
+            &token,
+            serde_json::json!({ "user_owned_fields": ["email"] }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response =
+            patch_json(&ctx, &token, serde_json::json!({ "bio": "x".repeat(501) })).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn uploaded_avatar_replaces_previous_one_and_is_kept_on_login() {
+        let ctx = TestContext::new(super::router).await;
+        let repo = ctx.auth_repo();
+        let login = IdentityProfile {
+            provider: "google",
+            provider_user_id: "avatar-owner",
+            email: None,
+            name: Some("Owner"),
+            avatar_url: Some("https://example.com/provider.png"),
+        };
+        let user = repo
+            .upsert_user_with_identity(login.clone())
+            .await
+            .expect("insert user");
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+
+        let response = put_avatar(&ctx, &token, "notes.txt", "text/plain", b"hello".to_vec()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = put_avatar(&ctx, &token, "me.png", "image/png", vec![1, 2, 3]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let first: UserResponse = parse_json(response).await;
+        let first_url = first.avatar_url.expect("avatar url");
+        assert!(first_url.starts_with("/usr/avatar?v="));
+        assert_eq!(first.user_owned_fields, ["avatar"]);
+        let first_file = ctx
+            .image_dir()
+            .join("avatars")
+            .join(user.id.to_string())
+            .join(format!(
+                "{}.png",
+                first_url.trim_start_matches("/usr/avatar?v=")
+            ));
+        assert!(first_file.exists());
+
+        let response = put_avatar(&ctx, &token, "me.jpg", "image/jpeg", vec![4, 5]).await;
+        let second: UserResponse = parse_json(response).await;
+        assert_ne!(second.avatar_url.as_deref(), Some(first_url.as_str()));
+        assert!(!first_file.exists());
+
+        let response = send(&ctx, Request::get("/usr/avatar"), &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/jpeg"
+        );
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(bytes.as_ref(), [4, 5]);
+
+        let user = repo
+            .upsert_user_with_identity(login)
+            .await
+            .expect("login again");
+        assert_eq!(user.avatar_url, second.avatar_url);
+    }
+
+    async fn patch_json(
+        ctx: &TestContext,
+        token: &str,
+        payload: serde_json::Value,
+    ) -> axum::response::Response {
+        ctx.app
+            .clone()
+            .oneshot(
+                Request::patch("/usr")
+                    .header("Authorization", format!("Bearer {token}"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("request succeeds")
+    }
+
+    async fn put_avatar(
+        ctx: &TestContext,
+        token: &str,
+        file_name: &'static str,
+        content_type: &'static str,
+        bytes: Vec<u8>,
+    ) -> axum::response::Response {
+        let (boundary, body) =
+            multipart_body(vec![Part::file("avatar", file_name, content_type, bytes)]);
+        ctx.app
+            .clone()
+            .oneshot(
+                Request::put("/usr/avatar")
+                    .header("Authorization", format!("Bearer {token}"))
+                    .header(
+                        header::CONTENT_TYPE,
+                        format!("multipart/form-data; boundary={boundary}"),
+                    )
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .expect("request succeeds")
+    }
+
+    async fn post_json(
+        ctx: &TestContext,
+        uri: &str,
+        token: &str,
+        payload: serde_json::Value,
+    ) -> axum::response::Response {
+        ctx.app
+            .clone()
+            .oneshot(
+                Request::post(uri)
+                    .header("Authorization", format!("Bearer {token}"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("request succeeds")
+    }
+
+    async fn link(ctx: &TestContext, token: &str, provider: &str) -> axum::response::Response {
+        let payload = serde_json::json!({ "provider": provider, "code": "auth-code" });
+        ctx.app
+            .clone()
+            .oneshot(
+                Request::post("/usr/identities")
+                    .header("Authorization", format!("Bearer {token}"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("link request")
+    }
+
+    async fn send(
+        ctx: &TestContext,
+        request: axum::http::request::Builder,
+        token: &str,
+    ) -> axum::response::Response {
+        ctx.app
+            .clone()
+            .oneshot(
+                request
+                    .header("Authorization", format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("request succeeds")
+    }
 }
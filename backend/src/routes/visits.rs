@@ -0,0 +1,595 @@
+use axum::{
+    extract::{Extension, Path as AxumPath, State},
+    http::StatusCode,
+    middleware,
+    routing::{get, patch, post},
+    Json, Router,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::personal_token::Scope;
+use crate::repository::visit::{NewVisit, UpdateVisit, VisitRecord, VisitWrite};
+
+use super::middleware::{api_auth, with_scope, AuthUser};
+use super::models::{ErrorResponse, PlaceImageResponse, VisitResponse};
+
+const MAX_COMPANIONS: usize = 20;
+const MAX_COMPANION_CHARS: usize = 100;
+
+/// Visits are part of a place, so they share the places scopes.
+pub fn router(state: AppState) -> Router {
+    let middleware_state = state.clone();
+
+    let read = Router::new()
+        .route("/places/:id/visits", get(list_visits))
+        .route("/places/:id/visits/:visit_id", get(get_visit));
+    let write = Router::new()
+        .route("/places/:id/visits", post(create_visit))
+        .route(
+            "/places/:id/visits/:visit_id",
+            patch(update_visit).delete(delete_visit),
+        );
+
+    with_scope(read, Scope::PlacesRead)
+        .merge(with_scope(write, Scope::PlacesWrite))
+        .route_layer(middleware::from_fn_with_state(middleware_state, api_auth))
+        .with_state(state)
+}
+
+async fn list_visits(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(place_id): AxumPath<Uuid>,
+) -> Result<Json<Vec<VisitResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .place_repository()
+        .find_for_user(user.id, place_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to verify place");
+            internal_error()
+        })?
+        .ok_or_else(place_not_found)?;
+
+    let visits = state
+        .visit_repository()
+        .list_for_place(user.id, place_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to list visits");
+            internal_error()
+        })?;
+
+    Ok(Json(visit_responses(&state, visits).await?))
+}
+
+async fn get_visit(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((place_id, visit_id)): AxumPath<(Uuid, Uuid)>,
+) -> Result<Json<VisitResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let visit = state
+        .visit_repository()
+        .find_for_user(user.id, place_id, visit_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load visit");
+            internal_error()
+        })?
+        .ok_or_else(visit_not_found)?;
+
+    Ok(Json(visit_response(&state, visit).await?))
+}
+
+#[derive(Deserialize)]
+struct CreateVisitRequest {
+    visited_on: NaiveDate,
+    rating: Option<i16>,
+    spend_cents: Option<i64>,
+    currency: Option<String>,
+    #[serde(default)]
+    companions: Vec<String>,
+    note: Option<String>,
+    #[serde(default)]
+    image_ids: Vec<Uuid>,
+}
+
+async fn create_visit(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath(place_id): AxumPath<Uuid>,
+    Json(payload): Json<CreateVisitRequest>,
+) -> Result<(StatusCode, Json<VisitResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let rating = payload.rating.map(validate_rating).transpose()?;
+    let spend_cents = payload.spend_cents.map(validate_spend).transpose()?;
+    let currency = payload
+        .currency
+        .as_deref()
+        .map(validate_currency)
+        .transpose()?;
+    let companions = validate_companions(payload.companions)?;
+    let note = payload
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|note| !note.is_empty());
+    let image_ids = dedupe(payload.image_ids);
+
+    let created = state
+        .visit_repository()
+        .create(
+            user.id,
+            NewVisit {
+                id: Uuid::new_v4(),
+                place_id,
+                visited_on: payload.visited_on,
+                rating,
+                spend_cents,
+                currency: currency.as_deref(),
+                companions: &companions,
+                note,
+                image_ids: &image_ids,
+            },
+        )
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to create visit");
+            internal_error()
+        })?;
+
+    match created {
+        VisitWrite::Written(visit) => Ok((
+            StatusCode::CREATED,
+            Json(visit_response(&state, visit).await?),
+        )),
+        VisitWrite::NotFound => Err(place_not_found()),
+        VisitWrite::UnknownImage => Err(unknown_image()),
+    }
+}
+
+#[derive(Deserialize)]
+struct UpdateVisitRequest {
+    visited_on: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "nullable")]
+    rating: Option<Option<i16>>,
+    #[serde(default, deserialize_with = "nullable")]
+    spend_cents: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    currency: Option<Option<String>>,
+    companions: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    note: Option<Option<String>>,
+    image_ids: Option<Vec<Uuid>>,
+}
+
+/// Edits a visit. Omitted fields are kept and `null` clears an optional field. `image_ids`
+/// replaces the images attached to the visit.
+async fn update_visit(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((place_id, visit_id)): AxumPath<(Uuid, Uuid)>,
+    Json(payload): Json<UpdateVisitRequest>,
+) -> Result<Json<VisitResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let rating = payload
+        .rating
+        .map(|rating| rating.map(validate_rating).transpose())
+        .transpose()?;
+    let spend_cents = payload
+        .spend_cents
+        .map(|spend| spend.map(validate_spend).transpose())
+        .transpose()?;
+    let currency = payload
+        .currency
+        .map(|currency| currency.as_deref().map(validate_currency).transpose())
+        .transpose()?;
+    let companions = payload.companions.map(validate_companions).transpose()?;
+    let note = payload.note.map(|note| {
+        note.map(|note| note.trim().to_string())
+            .filter(|note| !note.is_empty())
+    });
+
+    let updated = state
+        .visit_repository()
+        .update(
+            user.id,
+            place_id,
+            visit_id,
+            UpdateVisit {
+                visited_on: payload.visited_on,
+                rating,
+                spend_cents,
+                currency,
+                companions,
+                note,
+                image_ids: payload.image_ids.map(dedupe),
+            },
+        )
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to update visit");
+            internal_error()
+        })?;
+
+    match updated {
+        VisitWrite::Written(visit) => Ok(Json(visit_response(&state, visit).await?)),
+        VisitWrite::NotFound => Err(visit_not_found()),
+        VisitWrite::UnknownImage => Err(unknown_image()),
+    }
+}
+
+async fn delete_visit(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    AxumPath((place_id, visit_id)): AxumPath<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let deleted = state
+        .visit_repository()
+        .delete(user.id, place_id, visit_id)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to delete visit");
+            internal_error()
+        })?;
+
+    if !deleted {
+        return Err(visit_not_found());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Builds visit responses, loading the images of all visits in one query.
+async fn visit_responses(
+    state: &AppState,
+    visits: Vec<VisitRecord>,
+) -> Result<Vec<VisitResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let visit_ids: Vec<Uuid> = visits.iter().map(|visit| visit.id).collect();
+    let mut images = state
+        .visit_repository()
+        .list_images_for_visits(&visit_ids)
+        .await
+        .map_err(|err| {
+            error!(?err, "failed to load images for visits");
+            internal_error()
+        })?;
+
+    Ok(visits
+        .into_iter()
+        .map(|visit| {
+            let visit_id = visit.id;
+            let mut response = VisitResponse::from(visit);
+            response.images = images
+                .remove(&visit_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(PlaceImageResponse::from_record)
+                .collect();
+            response
+        })
+        .collect())
+}
+
+async fn visit_response(
+    state: &AppState,
+    visit: VisitRecord,
+) -> Result<VisitResponse, (StatusCode, Json<ErrorResponse>)> {
+    let mut responses = visit_responses(state, vec![visit]).await?;
+    Ok(responses.pop().expect("one response per visit"))
+}
+
+/// Keeps an explicit `null` apart from an omitted field, which deserializes to `None`.
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+fn validate_rating(rating: i16) -> Result<i16, (StatusCode, Json<ErrorResponse>)> {
+    if !(1..=5).contains(&rating) {
+        return Err(invalid_request("rating must be between 1 and 5"));
+    }
+    Ok(rating)
+}
+
+fn validate_spend(spend_cents: i64) -> Result<i64, (StatusCode, Json<ErrorResponse>)> {
+    if spend_cents < 0 {
+        return Err(invalid_request("spend_cents must not be negative"));
+    }
+    Ok(spend_cents)
+}
+
+/// Accepts ISO 4217 style codes in any case and returns them upper-cased.
+fn validate_currency(currency: &str) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    let currency = currency.trim();
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(invalid_request(
+            "currency must be a three letter code such as EUR",
+        ));
+    }
+    Ok(currency.to_ascii_uppercase())
+}
+
+fn validate_companions(
+    companions: Vec<String>,
+) -> Result<Vec<String>, (StatusCode, Json<ErrorResponse>)> {
+    if companions.len() > MAX_COMPANIONS {
+        return Err(invalid_request(format!(
+            "a visit can have at most {MAX_COMPANIONS} companions"
+        )));
+    }
+    companions
+        .into_iter()
+        .map(|name| {
+            let name = name.trim();
+            if name.is_empty() || name.chars().count() > MAX_COMPANION_CHARS {
+                return Err(invalid_request(format!(
+                    "companions must be between 1 and {MAX_COMPANION_CHARS} characters"
+                )));
+            }
+            Ok(name.to_string())
+        })
+        .collect()
+}
+
+fn dedupe(mut ids: Vec<Uuid>) -> Vec<Uuid> {
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+fn invalid_request(message: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request", message)),
+    )
+}
+
+fn unknown_image() -> (StatusCode, Json<ErrorResponse>) {
+    invalid_request("image_ids must refer to images of the visited place")
+}
+
+fn place_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new("not_found", "place not found")),
+    )
+}
+
+fn visit_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new("visit_not_found", "visit not found")),
+    )
+}
+
+fn internal_error() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse::new(
+            "internal_error",
+            "unexpected server error",
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Method, Request};
+    use serde_json::json;
+
+    use crate::routes::models::PlaceResponse;
+    use crate::test_utils::router::{
+        create_place, parse_json, send_json, send_multipart, Part, TestContext,
+    };
+
+    #[tokio::test]
+    async fn visits_are_logged_with_images_and_aggregated_on_the_place() {
+        let ctx = TestContext::new(|state| {
+            crate::routes::places::router(state.clone()).merge(super::router(state))
+        })
+        .await;
+        let user = ctx.insert_user().await;
+        let token = ctx.jwt.generate(&user, None).expect("jwt");
+        let other = ctx.insert_user().await;
+        let other_token = ctx.jwt.generate(&other, None).expect("jwt");
+
+        let image_id = Uuid::new_v4();
+        let place = create_place(
+            &ctx,
+            &token,
+            "Blue Bottle",
+            vec![
+                Part::text("category", "Coffee"),
+                Part::text("location", "Oakland"),
+                Part::text("status", "want_to_go"),
+                Part::text("image_id", image_id.to_string()),
+                Part::file("image", "image.jpg", "image/jpeg", b"IMG".to_vec()),
+            ],
+        )
+        .await;
+        let place_id = place.id;
+        assert_eq!(place.visit_count, 0);
+        assert_eq!(place.average_rating, None);
+        assert_eq!(place.status, "want_to_go");
+
+        let visits_uri = format!("/places/{place_id}/visits");
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &visits_uri,
+            &token,
+            json!({
+                "visited_on": "2024-03-01",
+                "rating": 4,
+                "spend_cents": 1250,
+                "currency": "eur",
+                "companions": [" Sam "],
+                "note": "Tried the oat latte",
+                "image_ids": [image_id, image_id]
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let first: VisitResponse = parse_json(response).await;
+        assert_eq!(first.currency.as_deref(), Some("EUR"));
+        assert_eq!(first.companions, ["Sam"]);
+        assert_eq!(first.images.len(), 1);
+        assert_eq!(first.images[0].visit_id, Some(first.id));
+
+        let mut ids = Vec::new();
+        for payload in [
+            json!({ "visited_on": "2024-05-10", "rating": 2 }),
+            json!({ "visited_on": "2024-04-01" }),
+        ] {
+            let response = send_json(&ctx, Method::POST, &visits_uri, &token, payload).await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            let visit: VisitResponse = parse_json(response).await;
+            ids.push(visit.id);
+        }
+        let (latest, unrated) = (ids[0], ids[1]);
+
+        let response = send_json(&ctx, Method::GET, &visits_uri, &token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let visits: Vec<VisitResponse> = parse_json(response).await;
+        let order: Vec<Uuid> = visits.iter().map(|visit| visit.id).collect();
+        assert_eq!(order, [latest, unrated, first.id]);
+
+        let place = get_place(&ctx, &token, place_id).await;
+        assert_eq!(place.status, "visited");
+        assert_eq!(place.visit_count, 3);
+        assert_eq!(place.last_visited_on, "2024-05-10".parse().ok());
+        assert_eq!(place.average_rating, Some(3.0));
+        assert_eq!(place.images[0].visit_id, Some(first.id));
+
+        // Only the first visit takes a place off the wishlist.
+        let response = send_multipart(
+            &ctx,
+            Request::patch(format!("/places/{place_id}")),
+            &token,
+            vec![Part::text("status", "want_to_go")],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &visits_uri,
+            &token,
+            json!({ "visited_on": "2024-05-20" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let again: VisitResponse = parse_json(response).await;
+        let place = get_place(&ctx, &token, place_id).await;
+        assert_eq!(place.status, "want_to_go");
+        assert_eq!(place.visit_count, 4);
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("{visits_uri}/{}", again.id),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // `null` clears a field while omitted fields are kept.
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &format!("{visits_uri}/{latest}"),
+            &token,
+            json!({ "rating": null, "note": "Too busy" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let updated: VisitResponse = parse_json(response).await;
+        assert_eq!(updated.rating, None);
+        assert_eq!(updated.note.as_deref(), Some("Too busy"));
+        assert_eq!(updated.visited_on, "2024-05-10".parse().unwrap());
+
+        let response = send_json(
+            &ctx,
+            Method::PATCH,
+            &format!("{visits_uri}/{}", first.id),
+            &token,
+            json!({ "image_ids": [] }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let detached: VisitResponse = parse_json(response).await;
+        assert!(detached.images.is_empty());
+        assert_eq!(detached.spend_cents, Some(1250));
+
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("{visits_uri}/{unrated}"),
+            &token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let place = get_place(&ctx, &token, place_id).await;
+        assert_eq!(place.visit_count, 2);
+        assert_eq!(place.average_rating, Some(4.0));
+        assert_eq!(place.images.len(), 1);
+        assert_eq!(place.images[0].visit_id, None);
+
+        for payload in [
+            json!({ "visited_on": "2024-06-01", "rating": 6 }),
+            json!({ "visited_on": "2024-06-01", "spend_cents": -1 }),
+            json!({ "visited_on": "2024-06-01", "currency": "EURO" }),
+            json!({ "visited_on": "2024-06-01", "companions": [""] }),
+            json!({ "visited_on": "2024-06-01", "image_ids": [Uuid::new_v4()] }),
+        ] {
+            let response = send_json(&ctx, Method::POST, &visits_uri, &token, payload).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+        let place = get_place(&ctx, &token, place_id).await;
+        assert_eq!(place.visit_count, 2);
+
+        // Visits of other users' places are invisible.
+        let response = send_json(&ctx, Method::GET, &visits_uri, &other_token, json!({})).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(
+            &ctx,
+            Method::POST,
+            &visits_uri,
+            &other_token,
+            json!({ "visited_on": "2024-06-01" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = send_json(
+            &ctx,
+            Method::DELETE,
+            &format!("{visits_uri}/{latest}"),
+            &other_token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn get_place(ctx: &TestContext, token: &str, place_id: Uuid) -> PlaceResponse {
+        let response = send_json(
+            ctx,
+            Method::GET,
+            &format!("/places/{place_id}"),
+            token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        parse_json(response).await
+    }
+}
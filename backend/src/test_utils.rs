@@ -1,12 +1,18 @@
 #[cfg(test)]
 pub mod router {
     use crate::app_state::AppState;
+    use crate::auth_service::{AuthService, MockUserProfile};
     use crate::db::create_pool;
     use crate::jwt::JwtManager;
+    use crate::refresh_token::RefreshTokenManager;
     use crate::repository::auth::{AuthRepository, UserRecord};
     use crate::repository::image_store::ImageStore;
-    use crate::repository::place::PlaceRepository;
+    use crate::repository::token::TokenRepository;
+    use crate::routes::models::PlaceResponse;
     use crate::sql_init::run_initialization;
+    use axum::body::Body;
+    use axum::http::request::Builder;
+    use axum::http::{header, Method, Request, StatusCode};
     use axum::response::Response;
     use axum::Router;
     use http_body_util::BodyExt;
@@ -15,6 +21,7 @@ pub mod router {
     use std::collections::HashMap;
     use std::path::Path;
     use tempfile::TempDir;
+    use tower::ServiceExt;
     use uuid::Uuid;
 
     pub const TEST_JWT_SECRET: &str = "secret";
@@ -29,19 +36,34 @@ pub mod router {
 
     impl TestContext {
         pub async fn new(build_router: impl FnOnce(AppState) -> Router) -> Self {
+            Self::with_mock_providers(Vec::new(), build_router).await
+        }
+
+        /// Registers `AuthService::new_mock` providers that sign in as the given profiles.
+        pub async fn with_mock_providers(
+            mock_providers: Vec<(&str, MockUserProfile)>,
+            build_router: impl FnOnce(AppState) -> Router,
+        ) -> Self {
             let pool = setup_pool().await;
             let temp_dir = TempDir::new().expect("temp dir");
             let auth_repo = AuthRepository::new(pool.clone());
-            let place_repo = PlaceRepository::new(pool.clone());
             let image_store = ImageStore::new(temp_dir.path().to_path_buf()).expect("image store");
             let jwt = JwtManager::new(TEST_JWT_SECRET.to_string(), 3600);
+            let refresh_tokens = RefreshTokenManager::new(TokenRepository::new(pool.clone()), 3600);
 
-            let providers = HashMap::new();
+            let providers: HashMap<String, AuthService> = mock_providers
+                .into_iter()
+                .map(|(provider, profile)| {
+                    let service = AuthService::new_mock(auth_repo.clone(), provider, profile);
+                    (provider.to_string(), service)
+                })
+                .collect();
             let state = AppState::new(
                 providers,
                 jwt.clone(),
+                refresh_tokens,
                 auth_repo.clone(),
-                place_repo,
+                pool.clone(),
                 image_store,
             );
 
@@ -81,6 +103,9 @@ pub mod router {
                 email: Some(email),
                 name: Some(name),
                 avatar_url: None,
+                bio: None,
+                home_city: None,
+                user_owned_fields: Vec::new(),
             }
         }
     }
@@ -165,6 +190,71 @@ pub mod router {
         serde_json::from_slice(&bytes).expect("json response")
     }
 
+    pub async fn send_json(
+        ctx: &TestContext,
+        method: Method,
+        uri: &str,
+        token: &str,
+        payload: serde_json::Value,
+    ) -> Response {
+        ctx.app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Authorization", format!("Bearer {token}"))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(payload.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("request succeeds")
+    }
+
+    pub async fn send_multipart(
+        ctx: &TestContext,
+        request: Builder,
+        token: &str,
+        parts: Vec<Part>,
+    ) -> Response {
+        let (boundary, body) = multipart_body(parts);
+        ctx.app
+            .clone()
+            .oneshot(
+                request
+                    .header("Authorization", format!("Bearer {token}"))
+                    .header(
+                        header::CONTENT_TYPE,
+                        format!("multipart/form-data; boundary={boundary}"),
+                    )
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .expect("request succeeds")
+    }
+
+    /// Creates a place called `name` with a fresh id. Fields in `parts` come after the
+    /// defaults, so they can override the category or location.
+    pub async fn create_place(
+        ctx: &TestContext,
+        token: &str,
+        name: &str,
+        parts: Vec<Part>,
+    ) -> PlaceResponse {
+        let mut form = vec![
+            Part::text("id", Uuid::new_v4().to_string()),
+            Part::text("name", name),
+            Part::text("category", "Food"),
+            Part::text("location", "Downtown"),
+        ];
+        form.extend(parts);
+        let response = send_multipart(ctx, Request::post("/places"), token, form).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        parse_json(response).await
+    }
+
     async fn setup_pool() -> PgPool {
         let database_url = std::env::var("TEST_DATABASE_URL")
             .or_else(|_| std::env::var("DATABASE_URL"))
@@ -178,7 +268,7 @@ pub mod router {
         run_initialization(&pool).await.expect("apply schema");
 
         sqlx::query(
-            "TRUNCATE TABLE place_images, places, oauth_identities, users RESTART IDENTITY",
+            "TRUNCATE TABLE place_images, places, oauth_identities, users RESTART IDENTITY CASCADE",
         )
         .execute(&pool)
         .await
@@ -187,3 +277,127 @@ pub mod router {
         pool
     }
 }
+
+#[cfg(test)]
+pub mod oidc {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use serde_json::{json, Value};
+
+    use crate::oauth_config::{EndpointOverrides, OAuthProviderConfig};
+    use crate::oidc_discovery::ProviderMetadata;
+
+    pub const TEST_CLIENT_ID: &str = "client-id";
+
+    pub fn provider_config(issuer: &str) -> OAuthProviderConfig {
+        OAuthProviderConfig {
+            provider_id: "google".to_string(),
+            client_id: TEST_CLIENT_ID.to_string(),
+            client_secret: None,
+            scopes: vec!["openid".to_string()],
+            issuer: issuer.to_string(),
+            redirect_uri: "https://example.com/callback".to_string(),
+            userinfo_fallback: false,
+            endpoint_overrides: EndpointOverrides::default(),
+        }
+    }
+
+    /// What discovery would report for a provider mocked at `issuer`.
+    pub fn provider_metadata(issuer: &str) -> ProviderMetadata {
+        ProviderMetadata {
+            issuer: issuer.to_string(),
+            authorization_endpoint: format!("{issuer}/auth"),
+            token_endpoint: format!("{issuer}/token"),
+            userinfo_endpoint: Some(format!("{issuer}/userinfo")),
+            jwks_uri: format!("{issuer}/jwks"),
+        }
+    }
+
+    /// An Ed25519 key standing in for an OpenID provider's signing key.
+    pub struct TestSigningKey {
+        kid: String,
+        encoding_key: EncodingKey,
+        public_key: Vec<u8>,
+    }
+
+    impl TestSigningKey {
+        pub fn generate(kid: &str) -> Self {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).expect("generate key");
+            let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).expect("parse key");
+            Self {
+                kid: kid.to_string(),
+                encoding_key: EncodingKey::from_ed_der(pkcs8.as_ref()),
+                public_key: key_pair.public_key().as_ref().to_vec(),
+            }
+        }
+
+        pub fn jwk(&self) -> Value {
+            json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "alg": "EdDSA",
+                "use": "sig",
+                "kid": self.kid,
+                "x": URL_SAFE_NO_PAD.encode(&self.public_key),
+            })
+        }
+
+        pub fn sign(&self, claims: &Value) -> String {
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some(self.kid.clone());
+            encode(&header, claims, &self.encoding_key).expect("sign id_token")
+        }
+    }
+
+    pub fn jwks(keys: &[&TestSigningKey]) -> Value {
+        json!({ "keys": keys.iter().map(|key| key.jwk()).collect::<Vec<_>>() })
+    }
+
+    /// Claims a provider at `issuer` would issue for `sub`, valid for an hour.
+    pub fn id_token_claims(issuer: &str, sub: &str) -> Value {
+        let now = chrono::Utc::now().timestamp();
+        json!({
+            "iss": issuer,
+            "aud": TEST_CLIENT_ID,
+            "sub": sub,
+            "iat": now,
+            "exp": now + 3600,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod queries {
+    use std::future::Future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    /// sqlx reports every statement it executes as an event with this target.
+    const QUERY_TARGET: &str = "sqlx::query";
+
+    struct QueryCounter(Arc<AtomicUsize>);
+
+    impl<S: Subscriber> Layer<S> for QueryCounter {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            if event.metadata().target() == QUERY_TARGET {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Runs `future` and returns its output together with the number of SQL statements it
+    /// executed. Only statements issued from the current thread are counted, which covers
+    /// everything a `#[tokio::test]` drives.
+    pub async fn count_queries<F: Future>(future: F) -> (F::Output, usize) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry().with(QueryCounter(count.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let output = future.await;
+        (output, count.load(Ordering::Relaxed))
+    }
+}